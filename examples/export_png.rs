@@ -0,0 +1,17 @@
+use std::path::Path;
+use gerbers::Gerber;
+use gerbers::visualizer::GerberVisualizer;
+
+fn main() -> Result<(), Box<dyn std::error::Error>> {
+    // Path to the test file
+    let test_file = Path::new("tests/two_square_boxes.gbr");
+
+    // Parse the Gerber file
+    let gerber = Gerber::new(test_file)?;
+
+    // Render it without opening a window
+    let mut visualizer = GerberVisualizer::new(800, 600);
+    visualizer.render_to_png(&gerber.commands, Path::new("out.png"))?;
+
+    Ok(())
+}
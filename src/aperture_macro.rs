@@ -0,0 +1,249 @@
+//! # Aperture Macros
+//!
+//! This module evaluates the arithmetic expressions used in aperture macro
+//! bodies and expands macros into concrete primitives for given parameters.
+
+use std::collections::HashMap;
+use pest::iterators::Pair;
+use pest::Parser;
+use crate::{Command, Gerber, GerberParser, Rule};
+use crate::command::AMPrimitive;
+use crate::error::GerberError;
+
+impl Gerber {
+    /// Expands an aperture macro into concrete primitives
+    ///
+    /// The arguments are bound to `$1`, `$2`, ... and the variable definitions
+    /// of the macro are evaluated in order. Variables that are never assigned
+    /// evaluate to 0. Comments and variable definitions are not part of the result.
+    ///
+    /// # Arguments
+    ///
+    /// * `name` - Name of the macro, as given in its AM command
+    /// * `args` - Parameters of the macro, as given in an AD command
+    ///
+    /// # Returns
+    ///
+    /// * `Result<Vec<AMPrimitive>, GerberError>` - The primitives with all parameters resolved, or an error if the macro is not defined or can't be evaluated
+    pub fn expand_macro(&self, name: &str, args: &[f64]) -> Result<Vec<AMPrimitive>, GerberError> {
+        let body = self.commands.iter()
+            .find_map(|command| match command {
+                Command::AM(macro_name, body) if macro_name == name => Some(body),
+                _ => None,
+            })
+            .ok_or_else(|| GerberError::SemanticError(
+                format!("Aperture macro '{}' is not defined.", name)
+            ))?;
+
+        expand_primitives(body, args)
+    }
+}
+
+/// Expands a thermal primitive into the polygons it fills
+///
+/// The thermal is the ring between the outer and inner circles, with the
+/// cross of its two gaps removed, leaving four ring segments. Their curved
+/// edges are approximated by straight steps.
+///
+/// # Arguments
+///
+/// * `center` - Center of the thermal, in macro coordinates
+/// * `outer_diameter` - Diameter of the outer circle
+/// * `inner_diameter` - Diameter of the inner circle
+/// * `gap` - Width of the gaps
+/// * `rotation` - Rotation in degrees about the macro origin
+///
+/// # Returns
+///
+/// * `Vec<Vec<(f64, f64)>>` - One open polygon per ring segment, empty if the gaps cover the whole thermal
+pub fn thermal_polygons(center: (f64, f64), outer_diameter: f64, inner_diameter: f64, gap: f64, rotation: f64) -> Vec<Vec<(f64, f64)>> {
+    const STEPS: usize = 16;
+
+    let outer_radius = outer_diameter / 2.0;
+    let inner_radius = inner_diameter / 2.0;
+    let half_gap = gap / 2.0;
+    if half_gap >= outer_radius {
+        return Vec::new();
+    }
+
+    // Angles at which the straight gap edges meet the circles
+    let outer_offset = (half_gap / outer_radius).asin().to_degrees();
+    let inner_offset = (half_gap < inner_radius).then(|| (half_gap / inner_radius).asin().to_degrees());
+
+    let (sin_angle, cos_angle) = rotation.to_radians().sin_cos();
+    let point = |radius: f64, angle: f64| {
+        let (sin, cos) = angle.to_radians().sin_cos();
+        let (x, y) = (center.0 + radius * cos, center.1 + radius * sin);
+        (x * cos_angle - y * sin_angle, x * sin_angle + y * cos_angle)
+    };
+    let arc = |radius: f64, from: f64, to: f64| -> Vec<(f64, f64)> {
+        (0..=STEPS).map(|step| point(radius, from + (to - from) * step as f64 / STEPS as f64)).collect()
+    };
+
+    (0..4)
+        .map(|quadrant| {
+            let base = 90.0 * quadrant as f64;
+            let mut polygon = arc(outer_radius, base + outer_offset, base + 90.0 - outer_offset);
+            match inner_offset {
+                Some(offset) => polygon.extend(arc(inner_radius, base + 90.0 - offset, base + offset)),
+                // The gaps meet inside the inner circle, at the corner of their cross
+                None => polygon.push(point(half_gap * std::f64::consts::SQRT_2, base + 45.0)),
+            }
+            polygon
+        })
+        .collect()
+}
+
+/// Expands the body of an aperture macro into concrete primitives
+///
+/// # Arguments
+///
+/// * `body` - Primitives and variable definitions of the macro
+/// * `args` - Parameters of the macro, bound to `$1`, `$2`, ...
+///
+/// # Returns
+///
+/// * `Result<Vec<AMPrimitive>, GerberError>` - The primitives with all parameters resolved, or an error if an expression can't be evaluated
+pub fn expand_primitives(body: &[AMPrimitive], args: &[f64]) -> Result<Vec<AMPrimitive>, GerberError> {
+    let mut variables: HashMap<u32, f64> = args.iter()
+        .enumerate()
+        .map(|(index, value)| (index as u32 + 1, *value))
+        .collect();
+    let mut primitives = Vec::new();
+
+    for primitive in body {
+        match primitive {
+            AMPrimitive::Comment(_) => {},
+            AMPrimitive::VariableDefinition(number, expression) => {
+                let value = evaluate(expression, &variables)?;
+                variables.insert(*number, value);
+            },
+            AMPrimitive::Parametric(code, expressions) => {
+                let values = expressions.iter()
+                    .map(|expression| evaluate(expression, &variables))
+                    .collect::<Result<Vec<f64>, GerberError>>()?;
+                primitives.push(build_primitive(*code, &values)?);
+            },
+            primitive => primitives.push(primitive.clone()),
+        }
+    }
+
+    Ok(primitives)
+}
+
+/// Evaluates a macro expression such as `$1x2+0.5`
+///
+/// # Arguments
+///
+/// * `expression` - The expression, as written in the macro body
+/// * `variables` - Values of the macro variables, by number
+///
+/// # Returns
+///
+/// * `Result<f64, GerberError>` - The value of the expression or an error if it is malformed
+pub fn evaluate(expression: &str, variables: &HashMap<u32, f64>) -> Result<f64, GerberError> {
+    let expr = GerberParser::parse(Rule::macro_expression, expression)
+        .ok()
+        .and_then(|mut pairs| pairs.next())
+        .and_then(|pair| pair.into_inner().next())
+        .ok_or_else(|| GerberError::SemanticError(
+            format!("Invalid macro expression: {}", expression)
+        ))?;
+
+    Ok(evaluate_pair(expr, variables))
+}
+
+/// Primitive code of a macro body rule, None for comments and variable definitions
+pub(crate) fn primitive_code(rule: Rule) -> Option<u32> {
+    match rule {
+        Rule::primitive_circle => Some(1),
+        Rule::primitive_vector_line => Some(20),
+        Rule::primitive_center_line => Some(21),
+        Rule::primitive_outline => Some(4),
+        Rule::primitive_polygon => Some(5),
+        Rule::primitive_thermal => Some(7),
+        _ => None,
+    }
+}
+
+fn evaluate_pair(pair: Pair<Rule>, variables: &HashMap<u32, f64>) -> f64 {
+    match pair.as_rule() {
+        Rule::expr | Rule::term => {
+            let mut inner = pair.into_inner();
+            let mut value = inner.next().map_or(0.0, |operand| evaluate_pair(operand, variables));
+
+            while let (Some(operator), Some(operand)) = (inner.next(), inner.next()) {
+                let operand = evaluate_pair(operand, variables);
+                value = match operator.as_str() {
+                    "+" => value + operand,
+                    "-" => value - operand,
+                    "x" => value * operand,
+                    _ => value / operand,
+                };
+            }
+
+            value
+        },
+        Rule::factor => {
+            let mut sign = 1.0;
+            let mut value = 0.0;
+
+            for inner in pair.into_inner() {
+                match inner.as_rule() {
+                    Rule::unary_operator if inner.as_str() == "-" => sign = -1.0,
+                    Rule::unary_operator => {},
+                    _ => value = evaluate_pair(inner, variables),
+                }
+            }
+
+            sign * value
+        },
+        Rule::macro_variable => {
+            let number = pair.as_str().trim_start_matches('$').parse::<u32>().unwrap_or(0);
+            variables.get(&number).copied().unwrap_or(0.0)
+        },
+        _ => pair.as_str().parse::<f64>().unwrap_or(0.0),
+    }
+}
+
+/// Checks the vertex count of a polygon primitive, which the spec limits to 3 to 12
+pub(crate) fn check_polygon_vertices(vertices: u32) -> Result<(), GerberError> {
+    if !(3..=12).contains(&vertices) {
+        return Err(GerberError::SemanticError(
+            format!("Polygon primitive must have 3 to 12 vertices, not {}.", vertices)
+        ));
+    }
+    Ok(())
+}
+
+fn build_primitive(code: u32, values: &[f64]) -> Result<AMPrimitive, GerberError> {
+    let exposure = |value: f64| value != 0.0;
+
+    let primitive = match (code, values) {
+        (1, &[on, diameter, center_x, center_y]) =>
+            AMPrimitive::Circle(exposure(on), diameter, center_x, center_y, None),
+        (1, &[on, diameter, center_x, center_y, rotation]) =>
+            AMPrimitive::Circle(exposure(on), diameter, center_x, center_y, Some(rotation)),
+        (20, &[on, width, start_x, start_y, end_x, end_y, rotation]) =>
+            AMPrimitive::VectorLine(exposure(on), width, start_x, start_y, end_x, end_y, rotation),
+        (21, &[on, width, height, center_x, center_y, rotation]) =>
+            AMPrimitive::CenterLine(exposure(on), width, height, center_x, center_y, rotation),
+        (4, &[on, vertices, ref coordinates @ .., rotation]) if coordinates.len() == 2 * (vertices as usize + 1) => {
+            let points = coordinates.chunks(2).map(|point| (point[0], point[1])).collect();
+            AMPrimitive::Outline(exposure(on), points, rotation)
+        },
+        (5, &[on, vertices, center_x, center_y, diameter, rotation]) => {
+            // Evaluated vertex counts are rounded like those written literally
+            let vertices = vertices.round().max(0.0) as u32;
+            check_polygon_vertices(vertices)?;
+            AMPrimitive::Polygon(exposure(on), vertices, center_x, center_y, diameter, rotation)
+        },
+        (7, &[center_x, center_y, outer_diameter, inner_diameter, gap, rotation]) =>
+            AMPrimitive::Thermal(center_x, center_y, outer_diameter, inner_diameter, gap, rotation),
+        _ => return Err(GerberError::SemanticError(
+            format!("Wrong number of parameters for macro primitive {}: {}", code, values.len())
+        )),
+    };
+
+    Ok(primitive)
+}
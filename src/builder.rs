@@ -0,0 +1,140 @@
+//! # Gerber Builder
+//!
+//! This module builds Gerber files programmatically, so they can be generated
+//! from geometry and written out with [`Gerber::to_gerber_string`].
+
+use crate::{Command, Gerber};
+use crate::command::{ApertureDefinition, ApertureTemplate, D01Operation, D02Operation, D03Operation, FormatSpecification, Notation, Unit, ZeroOmission};
+
+/// Accumulates commands to build a Gerber file
+///
+/// The MO and FS commands are emitted at the start and M02 at the end, whatever
+/// order the methods are called in. Coordinates are given in the file unit and
+/// encoded with the format of the file.
+pub struct GerberBuilder {
+    unit: Unit,
+    format_spec: FormatSpecification,
+    steps: Vec<Step>,
+}
+
+/// A step of the body, kept until the format is known
+enum Step {
+    Command(Command),
+    Move(f64, f64),
+    Line(f64, f64),
+    Flash(f64, f64),
+}
+
+impl Default for GerberBuilder {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl GerberBuilder {
+    /// Creates a builder for a millimeter file with the `%FSLAX26Y26*%` format
+    pub fn new() -> Self {
+        GerberBuilder {
+            unit: Unit::Millimeters,
+            format_spec: FormatSpecification {
+                x_integer_digits: 2,
+                x_decimal_digits: 6,
+                y_integer_digits: 2,
+                y_decimal_digits: 6,
+                zero_omission: ZeroOmission::Leading,
+                notation: Notation::Absolute,
+            },
+            steps: Vec::new(),
+        }
+    }
+
+    /// Sets the unit of the file
+    pub fn units(mut self, unit: Unit) -> Self {
+        self.unit = unit;
+        self
+    }
+
+    /// Sets the coordinate format of the file
+    pub fn format(mut self, format_spec: FormatSpecification) -> Self {
+        self.format_spec = format_spec;
+        self
+    }
+
+    /// Defines an aperture
+    ///
+    /// # Arguments
+    ///
+    /// * `code` - The D code of the aperture, 10 or more
+    /// * `template` - The shape of the aperture
+    pub fn define_aperture(mut self, code: u32, template: ApertureTemplate) -> Self {
+        self.steps.push(Step::Command(Command::AD(ApertureDefinition { code, template })));
+        self
+    }
+
+    /// Selects the aperture used by the following draws and flashes
+    pub fn select(mut self, code: u32) -> Self {
+        self.steps.push(Step::Command(Command::Dnn(code)));
+        self
+    }
+
+    /// Moves the current point without drawing
+    pub fn move_to(mut self, x: f64, y: f64) -> Self {
+        self.steps.push(Step::Move(x, y));
+        self
+    }
+
+    /// Draws a straight line from the current point
+    pub fn line_to(mut self, x: f64, y: f64) -> Self {
+        self.steps.push(Step::Line(x, y));
+        self
+    }
+
+    /// Flashes the selected aperture
+    pub fn flash(mut self, x: f64, y: f64) -> Self {
+        self.steps.push(Step::Flash(x, y));
+        self
+    }
+
+    /// Ends the file, nothing added afterwards is kept
+    pub fn end(mut self) -> Self {
+        self.steps.push(Step::Command(Command::M02));
+        self
+    }
+
+    /// Builds the Gerber file from the accumulated commands
+    ///
+    /// # Returns
+    ///
+    /// * `Gerber` - The commands, starting with MO and FS and ending with M02
+    pub fn build(self) -> Gerber {
+        let x = |x: f64| (x * 10f64.powi(self.format_spec.x_decimal_digits as i32)).round() as i64;
+        let y = |y: f64| (y * 10f64.powi(self.format_spec.y_decimal_digits as i32)).round() as i64;
+
+        let mut commands = vec![Command::MO(self.unit), Command::FS(self.format_spec.clone())];
+        let mut linear = false;
+
+        for step in &self.steps {
+            match step {
+                Step::Command(command) if command.is_end_of_file() => break,
+                Step::Command(command) => commands.push(command.clone()),
+                Step::Move(x_value, y_value) => {
+                    commands.push(Command::D02(D02Operation { x: Some(x(*x_value)), y: Some(y(*y_value)) }));
+                },
+                Step::Line(x_value, y_value) => {
+                    // D01 needs linear plot mode, which has no default
+                    if !linear {
+                        commands.push(Command::G01);
+                        linear = true;
+                    }
+                    commands.push(Command::D01(D01Operation { x: Some(x(*x_value)), y: Some(y(*y_value)), i: None, j: None }));
+                },
+                Step::Flash(x_value, y_value) => {
+                    commands.push(Command::D03(D03Operation { x: Some(x(*x_value)), y: Some(y(*y_value)) }));
+                },
+            }
+        }
+
+        commands.push(Command::M02);
+        Gerber::from(commands)
+    }
+}
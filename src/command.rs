@@ -17,15 +17,20 @@
 /// Commands control various aspects of the Gerber image generation, including
 /// aperture definitions, coordinate format, plotting operations, and attributes.
 #[derive(Debug, PartialEq, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum Command {
     /// Comment command (G04).
     ///
     /// Comments have no effect on the image but provide human-readable information.
+    /// Attributes written as comments (`G04 #@! TF.FileFunction,Copper,L1,Top*`)
+    /// are parsed into their attribute command instead. The text is kept exactly
+    /// as written, without the space following `G04`.
     /// Example: `G04 This is a comment*`
     G04(String),
 
     /// Mode command (MO) - sets the unit to mm or inch.
     ///
+    /// The deprecated `G70*` (inch) and `G71*` (mm) codes are parsed into this command too.
     /// Example: `%MOMM*%` (millimeters)
     MO(Unit),
 
@@ -70,6 +75,37 @@ pub enum Command {
     /// Example: `G75*`
     G75,
 
+    /// Enable single-quadrant mode for arcs (G74), deprecated.
+    ///
+    /// The I/J offsets of arcs are unsigned; the center is the one giving an
+    /// arc of at most 90 degrees.
+    /// Example: `G74*`
+    G74,
+
+    /// Set absolute coordinate notation (G90), deprecated.
+    ///
+    /// Legacy files switch notation with G90/G91 instead of, or in addition
+    /// to, the FS command; the most recent of the two applies.
+    /// Example: `G90*`
+    G90,
+
+    /// Set incremental coordinate notation (G91), deprecated.
+    ///
+    /// Legacy files switch notation with G90/G91 instead of, or in addition
+    /// to, the FS command; the most recent of the two applies.
+    /// Example: `G91*`
+    G91,
+
+    /// Image name command (IN), deprecated. Has no effect on the image.
+    ///
+    /// Example: `%INPANEL_1*%`
+    IN(String),
+
+    /// Load name command (LN), deprecated. Has no effect on the image.
+    ///
+    /// Example: `%LNTOPCOPPER*%`
+    LN(String),
+
     /// Plot operation (D01) - creates draw or arc objects.
     ///
     /// Example: `X50000Y25000D01*` (draws a line)
@@ -153,12 +189,28 @@ pub enum Command {
     ///
     /// Example: `M02*`
     M02,
+
+    /// Program stop command (M00), deprecated. Ends the file like M02.
+    ///
+    /// Example: `M00*`
+    M00,
+
+    /// Optional stop command (M01), deprecated. Ends the file like M02.
+    ///
+    /// Example: `M01*`
+    M01,
+
+    /// A statement accepted by the grammar that is not interpreted yet.
+    ///
+    /// Holds the raw text of the statement, so it is kept when writing the file back.
+    Unknown(String),
 }
 
 /// Represents the unit of measurement in a Gerber file.
 ///
 /// Set by the MO command.
-#[derive(Debug, PartialEq, Clone, Eq)]
+#[derive(Debug, PartialEq, Clone, Copy, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum Unit {
     /// Millimeters (metric) - set by `%MOMM*%`
     Millimeters,
@@ -180,10 +232,43 @@ impl FromStr for Unit {
     }
 }
 
+impl Unit {
+    /// Length of the unit in millimeters
+    ///
+    /// # Returns
+    ///
+    /// * `f64` - 1.0 for millimeters, 25.4 for inches
+    pub fn mm_per_unit(&self) -> f64 {
+        match self {
+            Unit::Millimeters => 1.0,
+            Unit::Inches => 25.4,
+        }
+    }
+}
+
+/// Converts a length from one unit to another
+///
+/// # Arguments
+///
+/// * `value` - The length to convert
+/// * `from` - The unit the length is in
+/// * `to` - The unit to convert to
+///
+/// # Returns
+///
+/// * `f64` - The length in the target unit
+pub fn convert(value: f64, from: Unit, to: Unit) -> f64 {
+    if from == to {
+        return value;
+    }
+    value * from.mm_per_unit() / to.mm_per_unit()
+}
+
 /// Specifies the format for coordinate data.
 ///
 /// Set by the FS command.
-#[derive(Debug, PartialEq, Clone)]
+#[derive(Debug, PartialEq, Clone, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct FormatSpecification {
     /// Number of integer digits for X coordinates
     pub x_integer_digits: u8,
@@ -193,12 +278,110 @@ pub struct FormatSpecification {
     pub y_integer_digits: u8,
     /// Number of decimal digits for Y coordinates
     pub y_decimal_digits: u8,
+    /// Which zeros are omitted from coordinate data
+    pub zero_omission: ZeroOmission,
+    /// Whether coordinates are absolute or incremental
+    pub notation: Notation,
+}
+
+/// Represents which zeros may be omitted from coordinate data.
+///
+/// Set by the first letter of the FS command.
+#[derive(Debug, PartialEq, Clone, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum ZeroOmission {
+    /// Leading zeros are omitted (`%FSLA...`)
+    Leading,
+    /// Trailing zeros are omitted (`%FSTA...`), deprecated
+    Trailing,
+}
+
+/// Represents how coordinate data is expressed.
+///
+/// Set by the notation letter of the FS command.
+#[derive(Debug, PartialEq, Clone, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum Notation {
+    /// Coordinates are absolute, relative to the origin (`%FSLA...`)
+    Absolute,
+    /// Coordinates are relative to the current point (`%FSLI...`), deprecated
+    Incremental,
+}
+
+impl FormatSpecification {
+    /// Decodes the digits of an X coordinate into its integer value
+    ///
+    /// With trailing zero omission the omitted digits are restored, so the
+    /// value is always the same as its leading zero omission form. Values are
+    /// 64 bits wide, enough for the largest 6.6 coordinates.
+    pub fn decode_x(&self, coord_str: &str) -> Result<i64, std::num::ParseIntError> {
+        self.decode(coord_str, self.x_integer_digits + self.x_decimal_digits)
+    }
+
+    /// Decodes the digits of a Y coordinate into its integer value
+    ///
+    /// With trailing zero omission the omitted digits are restored, so the
+    /// value is always the same as its leading zero omission form.
+    pub fn decode_y(&self, coord_str: &str) -> Result<i64, std::num::ParseIntError> {
+        self.decode(coord_str, self.y_integer_digits + self.y_decimal_digits)
+    }
+
+    fn decode(&self, coord_str: &str, total_digits: u8) -> Result<i64, std::num::ParseIntError> {
+        match self.zero_omission {
+            ZeroOmission::Leading => coord_str.parse(),
+            ZeroOmission::Trailing => {
+                let digits = coord_str.trim_start_matches(['+', '-']);
+                let sign = &coord_str[..coord_str.len() - digits.len()];
+                let padding = (total_digits as usize).saturating_sub(digits.len());
+
+                format!("{}{}{}", sign, digits, "0".repeat(padding)).parse()
+            },
+        }
+    }
+
+    /// Encodes the integer value of an X coordinate into its digits
+    ///
+    /// This is the inverse of [`decode_x`](Self::decode_x).
+    pub fn encode_x(&self, x: i64) -> String {
+        self.encode(x, self.x_integer_digits + self.x_decimal_digits)
+    }
+
+    /// Encodes the integer value of a Y coordinate into its digits
+    ///
+    /// This is the inverse of [`decode_y`](Self::decode_y).
+    pub fn encode_y(&self, y: i64) -> String {
+        self.encode(y, self.y_integer_digits + self.y_decimal_digits)
+    }
+
+    fn encode(&self, value: i64, total_digits: u8) -> String {
+        match self.zero_omission {
+            ZeroOmission::Leading => value.to_string(),
+            ZeroOmission::Trailing => {
+                let sign = if value < 0 { "-" } else { "" };
+                let digits = format!("{:0width$}", value.unsigned_abs(), width = total_digits as usize);
+                let digits = digits.trim_end_matches('0');
+
+                format!("{}{}", sign, if digits.is_empty() { "0" } else { digits })
+            },
+        }
+    }
+
+    /// Converts a raw X coordinate into the file unit
+    pub fn resolve_x(&self, x: i64) -> f64 {
+        x as f64 / 10f64.powi(self.x_decimal_digits as i32)
+    }
+
+    /// Converts a raw Y coordinate into the file unit
+    pub fn resolve_y(&self, y: i64) -> f64 {
+        y as f64 / 10f64.powi(self.y_decimal_digits as i32)
+    }
 }
 
 /// Defines an aperture with its D-code and template.
 ///
 /// Created by the AD command.
 #[derive(Debug, PartialEq, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct ApertureDefinition {
     /// The aperture number (D code ≥ 10)
     pub code: u32,
@@ -211,6 +394,7 @@ pub struct ApertureDefinition {
 /// Standard apertures are predefined shapes (C, R, O, P),
 /// while macro apertures are custom shapes defined with the AM command.
 #[derive(Debug, PartialEq, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum ApertureTemplate {
     /// Circle aperture (C).
     ///
@@ -238,11 +422,25 @@ pub enum ApertureTemplate {
     Macro(String, Vec<f64>),
 }
 
+impl ApertureTemplate {
+    /// Diameter of the hole in the middle of a standard aperture, if it has one
+    pub fn hole_diameter(&self) -> Option<f64> {
+        match self {
+            ApertureTemplate::Circle(_, hole) |
+            ApertureTemplate::Rectangle(_, _, hole) |
+            ApertureTemplate::Obround(_, _, hole) |
+            ApertureTemplate::Polygon(_, _, _, hole) => *hole,
+            ApertureTemplate::Macro(_, _) => None,
+        }
+    }
+}
+
 /// Represents primitives used in aperture macros.
 ///
 /// Each primitive is a basic shape that can be combined to create
 /// complex aperture definitions.
 #[derive(Debug, PartialEq, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum AMPrimitive {
     /// Comment primitive (Code 0).
     ///
@@ -283,49 +481,113 @@ pub enum AMPrimitive {
     ///
     /// Parameters: variable number, expression
     VariableDefinition(u32, String),
+
+    /// Primitive whose parameters reference macro variables.
+    ///
+    /// The values are only known once the macro is instantiated by an AD command,
+    /// see [`Gerber::expand_macro`](crate::Gerber::expand_macro).
+    ///
+    /// Parameters: primitive code, parameter expressions
+    Parametric(u32, Vec<String>),
+}
+
+impl AMPrimitive {
+    /// Vertices of an outline or polygon primitive, rotated about the macro origin
+    ///
+    /// The list is closed: its last vertex is the first one again, as outlines
+    /// are written in the file. The first vertex of a polygon is on the
+    /// positive X axis through its center before the rotation.
+    ///
+    /// # Returns
+    ///
+    /// * `Option<Vec<(f64, f64)>>` - The vertices, or None if the primitive is neither an outline nor a polygon
+    pub fn as_polygon(&self) -> Option<Vec<(f64, f64)>> {
+        let (points, rotation) = match self {
+            AMPrimitive::Outline(_, points, rotation) => (points.clone(), rotation),
+            AMPrimitive::Polygon(_, vertices, center_x, center_y, diameter, rotation) => {
+                let points = (0..*vertices)
+                    .map(|index| {
+                        let angle = std::f64::consts::TAU * index as f64 / *vertices as f64;
+                        (center_x + diameter / 2.0 * angle.cos(), center_y + diameter / 2.0 * angle.sin())
+                    })
+                    .collect();
+                (points, rotation)
+            },
+            _ => return None,
+        };
+
+        let (sin, cos) = rotation.to_radians().sin_cos();
+        let mut polygon: Vec<(f64, f64)> = points.iter()
+            .map(|(x, y)| (x * cos - y * sin, x * sin + y * cos))
+            .collect();
+
+        if let (Some(&first), Some(&last)) = (polygon.first(), polygon.last()) {
+            if first != last {
+                polygon.push(first);
+            }
+        }
+
+        Some(polygon)
+    }
 }
 
 /// Represents the parameters for a D01 (plot) operation.
 ///
 /// D01 operations create draw or arc objects depending on the current plot mode.
-#[derive(Debug, PartialEq, Clone)]
+#[derive(Debug, PartialEq, Clone, Copy, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct D01Operation {
     /// X coordinate (optional, uses current point if not specified)
-    pub x: Option<i32>,
+    pub x: Option<i64>,
     /// Y coordinate (optional, uses current point if not specified)
-    pub y: Option<i32>,
+    pub y: Option<i64>,
     /// I offset for circular interpolation (required for arcs)
-    pub i: Option<i32>,
+    pub i: Option<i64>,
     /// J offset for circular interpolation (required for arcs)
-    pub j: Option<i32>,
+    pub j: Option<i64>,
 }
 
 /// Represents the parameters for a D02 (move) operation.
 ///
 /// D02 operations move the current point without drawing.
-#[derive(Debug, PartialEq, Clone)]
+#[derive(Debug, PartialEq, Clone, Copy, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct D02Operation {
     /// X coordinate (optional, uses current point if not specified)
-    pub x: Option<i32>,
+    pub x: Option<i64>,
     /// Y coordinate (optional, uses current point if not specified)
-    pub y: Option<i32>,
+    pub y: Option<i64>,
 }
 
 /// Represents the parameters for a D03 (flash) operation.
 ///
 /// D03 operations create a flash of the current aperture.
-#[derive(Debug, PartialEq, Clone)]
+#[derive(Debug, PartialEq, Clone, Copy, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct D03Operation {
     /// X coordinate (optional, uses current point if not specified)
-    pub x: Option<i32>,
+    pub x: Option<i64>,
     /// Y coordinate (optional, uses current point if not specified)
-    pub y: Option<i32>,
+    pub y: Option<i64>,
+}
+
+/// A D01, D02 or D03 operation, the commands that act on the current point.
+#[derive(Debug, PartialEq, Clone, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum Operation {
+    /// Plot operation (D01)
+    D01(D01Operation),
+    /// Move operation (D02)
+    D02(D02Operation),
+    /// Flash operation (D03)
+    D03(D03Operation),
 }
 
 /// Represents the polarity setting for graphical objects.
 ///
 /// Set by the LP command.
-#[derive(Debug, PartialEq, Clone)]
+#[derive(Debug, PartialEq, Clone, Copy, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum Polarity {
     /// Dark polarity - objects darken the image plane (LPD)
     Dark,
@@ -336,7 +598,8 @@ pub enum Polarity {
 /// Represents mirroring settings for graphical objects.
 ///
 /// Set by the LM command.
-#[derive(Debug, PartialEq, Clone)]
+#[derive(Debug, PartialEq, Clone, Copy, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum Mirroring {
     /// No mirroring (LMN)
     None,
@@ -352,6 +615,7 @@ pub enum Mirroring {
 ///
 /// Set by the SR command.
 #[derive(Debug, PartialEq, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct StepAndRepeat {
     /// Number of repeats in the X direction
     pub x_repeats: u32,
@@ -363,18 +627,114 @@ pub struct StepAndRepeat {
     pub y_step: f64,
 }
 
+impl Command {
+    /// Whether the command ends the file, nothing after it is part of the image
+    ///
+    /// This is M02, or the deprecated M00 and M01 that legacy files use in its place.
+    pub fn is_end_of_file(&self) -> bool {
+        matches!(self, Command::M02 | Command::M00 | Command::M01)
+    }
+}
+
 /// Implementation of Display for Command to enable pretty printing.
+///
+/// Each command is described on a single line. Coordinates are the raw
+/// integers of the file, and the ones left out are shown as the current ones.
 impl std::fmt::Display for Command {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let point = |x: &Option<i64>, y: &Option<i64>| {
+            let coordinate = |value: &Option<i64>| value.map_or("current".to_string(), |value| value.to_string());
+            format!("({}, {})", coordinate(x), coordinate(y))
+        };
+        let attribute = |f: &mut std::fmt::Formatter<'_>, kind: &str, name: &str, values: &[String]| {
+            if values.is_empty() {
+                write!(f, "{} attribute: {}", kind, name)
+            } else {
+                write!(f, "{} attribute: {} = {}", kind, name, values.join(", "))
+            }
+        };
+
         match self {
             Command::G04(comment) => write!(f, "Comment: {}", comment),
             Command::MO(unit) => write!(f, "Set units: {:?}", unit),
             Command::FS(format) => write!(f, "Format: {}.{}/{}.{}",
                                           format.x_integer_digits, format.x_decimal_digits,
                                           format.y_integer_digits, format.y_decimal_digits),
+            Command::AD(aperture_def) => write!(f, "Define aperture D{}: {}", aperture_def.code, aperture_def.template),
+            Command::AM(name, primitives) => write!(f, "Define macro {}: {} primitives", name, primitives.len()),
+            Command::Dnn(code) => write!(f, "Select aperture D{}", code),
+            Command::G01 => write!(f, "Linear interpolation"),
+            Command::G02 => write!(f, "Clockwise circular interpolation"),
+            Command::G03 => write!(f, "Counterclockwise circular interpolation"),
+            Command::G75 => write!(f, "Multi-quadrant arcs"),
+            Command::G74 => write!(f, "Single-quadrant arcs"),
+            Command::G90 => write!(f, "Absolute notation"),
+            Command::G91 => write!(f, "Incremental notation"),
+            Command::IN(name) => write!(f, "Image name: {}", name),
+            Command::LN(name) => write!(f, "Load name: {}", name),
+            Command::D01(op) => {
+                write!(f, "Plot to {}", point(&op.x, &op.y))?;
+                if op.i.is_some() || op.j.is_some() {
+                    write!(f, " around offset ({}, {})", op.i.unwrap_or(0), op.j.unwrap_or(0))?;
+                }
+                Ok(())
+            },
+            Command::D02(op) => write!(f, "Move to {}", point(&op.x, &op.y)),
+            Command::D03(op) => write!(f, "Flash at {}", point(&op.x, &op.y)),
+            Command::LP(polarity) => match polarity {
+                Polarity::Dark => write!(f, "Polarity: dark"),
+                Polarity::Clear => write!(f, "Polarity: clear"),
+            },
+            Command::LM(mirroring) => match mirroring {
+                Mirroring::None => write!(f, "Mirroring: none"),
+                Mirroring::X => write!(f, "Mirroring: X"),
+                Mirroring::Y => write!(f, "Mirroring: Y"),
+                Mirroring::XY => write!(f, "Mirroring: X and Y"),
+            },
+            Command::LR(rotation) => write!(f, "Rotation: {} degrees", rotation),
+            Command::LS(scale) => write!(f, "Scaling: {}", scale),
+            Command::G36 => write!(f, "Begin region"),
+            Command::G37 => write!(f, "End region"),
+            Command::AB(Some(code)) => write!(f, "Begin block aperture D{}", code),
+            Command::AB(None) => write!(f, "End block aperture"),
+            Command::SR(Some(sr)) => write!(f, "Begin step and repeat: {}x{} copies, step ({}, {})",
+                                            sr.x_repeats, sr.y_repeats, sr.x_step, sr.y_step),
+            Command::SR(None) => write!(f, "End step and repeat"),
+            Command::TF(name, values) => attribute(f, "File", name, values),
+            Command::TA(name, values) => attribute(f, "Aperture", name, values),
+            Command::TO(name, values) => attribute(f, "Object", name, values),
+            Command::TD(Some(name)) => write!(f, "Delete attribute: {}", name),
+            Command::TD(None) => write!(f, "Delete all attributes"),
             Command::M02 => write!(f, "End of file"),
-            // Add other command formatting here
-            _ => write!(f, "{:?}", self),
+            Command::M00 => write!(f, "Program stop"),
+            Command::M01 => write!(f, "Optional stop"),
+            Command::Unknown(text) => write!(f, "Unknown statement: {}", text),
+        }
+    }
+}
+
+/// Describes the shape of the template and its dimensions
+impl std::fmt::Display for ApertureTemplate {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ApertureTemplate::Circle(diameter, _) => write!(f, "circle, diameter {}", diameter)?,
+            ApertureTemplate::Rectangle(x, y, _) => write!(f, "rectangle, {} x {}", x, y)?,
+            ApertureTemplate::Obround(x, y, _) => write!(f, "obround, {} x {}", x, y)?,
+            ApertureTemplate::Polygon(diameter, vertices, rotation, _) => {
+                write!(f, "polygon, diameter {}, {} vertices", diameter, vertices)?;
+                if let Some(rotation) = rotation {
+                    write!(f, ", rotated {} degrees", rotation)?;
+                }
+            },
+            ApertureTemplate::Macro(name, params) => {
+                let params: Vec<String> = params.iter().map(|param| param.to_string()).collect();
+                write!(f, "macro {}({})", name, params.join(", "))?;
+            },
+        }
+
+        match self.hole_diameter() {
+            Some(hole) => write!(f, ", hole {}", hole),
+            None => Ok(()),
         }
     }
 }
\ No newline at end of file
@@ -0,0 +1,102 @@
+//! # Semantic Diff
+//!
+//! This module compares two Gerber files by what they draw rather than by
+//! their text, so reordering commands or rewriting coordinates doesn't show up
+//! as a change.
+
+use crate::Gerber;
+use crate::command::ApertureTemplate;
+use crate::geometry::DrawPrimitive;
+
+/// The differences between two Gerber files
+///
+/// Flashes and traces are compared as resolved primitives, in the file unit,
+/// and regardless of their order.
+#[derive(Debug, PartialEq, Clone, Default)]
+pub struct GerberDiff {
+    /// Flashes only in the other file
+    pub added_flashes: Vec<DrawPrimitive>,
+    /// Flashes only in this file
+    pub removed_flashes: Vec<DrawPrimitive>,
+    /// Line and arc draws only in the other file
+    pub added_traces: Vec<DrawPrimitive>,
+    /// Line and arc draws only in this file
+    pub removed_traces: Vec<DrawPrimitive>,
+    /// Apertures defined in both files with different templates, as (code, this, other)
+    pub changed_apertures: Vec<(u32, ApertureTemplate, ApertureTemplate)>,
+}
+
+impl GerberDiff {
+    /// Whether the files draw the same thing with the same apertures
+    pub fn is_empty(&self) -> bool {
+        self.added_flashes.is_empty()
+            && self.removed_flashes.is_empty()
+            && self.added_traces.is_empty()
+            && self.removed_traces.is_empty()
+            && self.changed_apertures.is_empty()
+    }
+}
+
+impl Gerber {
+    /// Compares the flashes, traces and aperture definitions with another file
+    ///
+    /// # Arguments
+    ///
+    /// * `other` - The file to compare with, usually a later revision
+    ///
+    /// # Returns
+    ///
+    /// * `GerberDiff` - What the other file adds, removes or changes
+    pub fn diff(&self, other: &Gerber) -> GerberDiff {
+        let (flashes, traces) = split_primitives(self);
+        let (other_flashes, other_traces) = split_primitives(other);
+
+        let (removed_flashes, added_flashes) = unmatched(flashes, other_flashes);
+        let (removed_traces, added_traces) = unmatched(traces, other_traces);
+
+        let other_apertures = other.aperture_map();
+        let mut changed_apertures: Vec<(u32, ApertureTemplate, ApertureTemplate)> = self.aperture_map().into_iter()
+            .filter_map(|(code, template)| {
+                let other_template = other_apertures.get(&code)?;
+                (*other_template != template).then(|| (code, template, other_template.clone()))
+            })
+            .collect();
+        changed_apertures.sort_by_key(|(code, _, _)| *code);
+
+        GerberDiff { added_flashes, removed_flashes, added_traces, removed_traces, changed_apertures }
+    }
+}
+
+/// Separates the flashes from the line and arc draws of a file
+fn split_primitives(gerber: &Gerber) -> (Vec<DrawPrimitive>, Vec<DrawPrimitive>) {
+    let mut flashes = Vec::new();
+    let mut traces = Vec::new();
+
+    for primitive in gerber.draw_primitives() {
+        match primitive {
+            DrawPrimitive::Flash { .. } => flashes.push(primitive),
+            DrawPrimitive::Line { .. } | DrawPrimitive::Arc { .. } => traces.push(primitive),
+            DrawPrimitive::RegionFill { .. } => {}
+        }
+    }
+
+    (flashes, traces)
+}
+
+/// Pairs up equal primitives of both lists, in any order
+///
+/// # Returns
+///
+/// * `(Vec<DrawPrimitive>, Vec<DrawPrimitive>)` - The primitives left unpaired in the first and second list
+fn unmatched(ours: Vec<DrawPrimitive>, mut theirs: Vec<DrawPrimitive>) -> (Vec<DrawPrimitive>, Vec<DrawPrimitive>) {
+    let mut only_ours = Vec::new();
+
+    for primitive in ours {
+        match theirs.iter().position(|other| *other == primitive) {
+            Some(position) => { theirs.swap_remove(position); },
+            None => only_ours.push(primitive),
+        }
+    }
+
+    (only_ours, theirs)
+}
@@ -0,0 +1,87 @@
+//! # Excellon Export
+//!
+//! This module derives an Excellon drill program from the holes of the
+//! flashed apertures, for fabrication houses that want a separate drill file.
+
+use std::collections::HashMap;
+use crate::{Command, Gerber};
+use crate::command::Unit;
+use crate::geometry::PlotState;
+
+impl Gerber {
+    /// Writes an Excellon drill program for the holes of the flashed apertures
+    ///
+    /// Every flash of an aperture with a hole is a hit, and holes of the same
+    /// diameter share a tool, numbered from the smallest diameter up.
+    /// Coordinates are written in the file unit with an explicit decimal point,
+    /// and millimeters are assumed if the file doesn't declare its unit.
+    /// Flashes that can't be resolved are skipped.
+    ///
+    /// # Returns
+    ///
+    /// * `String` - The Excellon program
+    pub fn to_excellon(&self) -> String {
+        let unit = self.commands.iter()
+            .find_map(|command| match command {
+                Command::MO(unit) => Some(*unit),
+                _ => None,
+            })
+            .unwrap_or(Unit::Millimeters);
+        let (unit_name, decimals) = match unit {
+            Unit::Millimeters => ("METRIC", 3),
+            Unit::Inches => ("INCH", 4),
+        };
+
+        let mut plot_state = PlotState::default();
+        let mut holes: HashMap<u32, f64> = HashMap::new();
+        let mut current_aperture = None;
+        // Hits of each hole diameter, in the order the diameters are found
+        let mut tools: Vec<(f64, Vec<(f64, f64)>)> = Vec::new();
+
+        for command in &self.commands {
+            let Ok(end) = plot_state.apply(command) else {
+                continue;
+            };
+
+            match (command, end) {
+                (Command::AD(aperture_def), _) => {
+                    match aperture_def.template.hole_diameter() {
+                        Some(diameter) => holes.insert(aperture_def.code, diameter),
+                        None => holes.remove(&aperture_def.code),
+                    };
+                },
+                (Command::Dnn(code), _) => current_aperture = Some(*code),
+                (Command::D03(_), Some(hit)) => {
+                    let Some(diameter) = current_aperture.and_then(|code| holes.get(&code).copied()) else {
+                        continue;
+                    };
+
+                    match tools.iter_mut().find(|(tool_diameter, _)| *tool_diameter == diameter) {
+                        Some((_, hits)) => hits.push(hit),
+                        None => tools.push((diameter, vec![hit])),
+                    }
+                },
+                _ => {}
+            }
+        }
+
+        tools.sort_by(|(a, _), (b, _)| a.total_cmp(b));
+
+        let mut output = format!("M48\n{}\n", unit_name);
+        for (index, (diameter, _)) in tools.iter().enumerate() {
+            output.push_str(&format!("T{:02}C{:.*}\n", index + 1, decimals, diameter));
+        }
+        output.push_str("%\nG90\nG05\n");
+
+        for (index, (_, hits)) in tools.iter().enumerate() {
+            output.push_str(&format!("T{:02}\n", index + 1));
+            for (x, y) in hits {
+                output.push_str(&format!("X{:.*}Y{:.*}\n", decimals, x, decimals, y));
+            }
+        }
+
+        output.push_str("M30\n");
+        output
+    }
+}
+
@@ -0,0 +1,828 @@
+//! # Resolved Geometry
+//!
+//! This module interprets the command stream of a parsed Gerber file,
+//! tracking the graphics state needed to turn raw integer coordinates into
+//! positions in the file unit (millimeters or inches).
+
+use std::collections::HashMap;
+use std::f64::consts::{FRAC_PI_2, TAU};
+use crate::{Command, Gerber};
+use crate::command::{ApertureTemplate, FormatSpecification, Notation};
+use crate::error::GerberError;
+use crate::state::GraphicsState;
+
+/// Largest distance between an arc of a region contour and the segments
+/// approximating it, in the file unit, unless set otherwise
+const DEFAULT_ARC_TOLERANCE: f64 = 0.001;
+
+/// The state that D01, D02 and D03 operations depend on, driven command by command
+///
+/// Tracks the current point, converting raw coordinates into the file unit,
+/// along with the selected aperture and the interpolation mode. The
+/// visualizer and [`Gerber::draw_primitives`] both walk the commands with it.
+#[derive(Debug, Default, Clone)]
+pub struct PlotState {
+    format_spec: Option<FormatSpecification>,
+    // Set by the FS command and overridden by G90/G91
+    notation: Option<Notation>,
+    current_point: (f64, f64),
+    aperture: Option<u32>,
+    interpolation_mode: InterpolationMode,
+    single_quadrant: bool,
+}
+
+/// How D01 operations plot, set by G01, G02 and G03
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum InterpolationMode {
+    /// Straight draws (G01), the mode assumed before any is set
+    #[default]
+    Linear,
+    /// Clockwise arcs (G02)
+    ClockwiseArc,
+    /// Counter-clockwise arcs (G03)
+    CounterClockwiseArc,
+}
+
+/// A drawing primitive, with its coordinates in the file unit
+#[derive(Debug, PartialEq, Clone)]
+pub enum DrawPrimitive {
+    /// A straight draw with a D01 operation in linear plot mode
+    Line {
+        from: (f64, f64),
+        to: (f64, f64),
+        aperture: u32,
+    },
+    /// A circular draw with a D01 operation in G02 or G03 mode
+    Arc {
+        from: (f64, f64),
+        to: (f64, f64),
+        center: (f64, f64),
+        clockwise: bool,
+        aperture: u32,
+    },
+    /// A D03 flash
+    Flash {
+        at: (f64, f64),
+        aperture: u32,
+    },
+    /// A G36/G37 region, with the vertices of each of its contours
+    ///
+    /// Arcs of the contours are approximated by straight segments in the
+    /// vertices and triangles, and kept as they are in the segments, which
+    /// start from the first vertex of their contour.
+    ///
+    /// The triangles of each contour follow those of the previous one, so a
+    /// hole is covered by triangles of its own: fill them even-odd to cut it out.
+    RegionFill {
+        contours: Vec<Vec<(f64, f64)>>,
+        segments: Vec<Vec<ContourSegment>>,
+        triangles: Vec<[(f64, f64); 3]>,
+    },
+}
+
+/// A segment of a region contour, drawn by a D01 operation from the end of the previous one
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum ContourSegment {
+    /// A straight segment, in linear plot mode
+    Line {
+        to: (f64, f64),
+    },
+    /// A circular segment, in G02 or G03 mode
+    Arc {
+        to: (f64, f64),
+        center: (f64, f64),
+        clockwise: bool,
+    },
+}
+
+/// Vertices and segments of a region contour
+type Contour = (Vec<(f64, f64)>, Vec<ContourSegment>);
+
+/// Iterator over the drawing primitives of a Gerber file
+///
+/// Created by [`Gerber::draw_primitives`].
+pub struct DrawPrimitives<'a> {
+    commands: std::slice::Iter<'a, Command>,
+    plot_state: PlotState,
+    state: GraphicsState,
+    // Contours of the region being read, None outside regions
+    region: Option<Vec<Contour>>,
+    arc_tolerance: f64,
+}
+
+impl DrawPrimitives<'_> {
+    /// The graphics state in effect for the primitive last returned
+    pub fn state(&self) -> &GraphicsState {
+        &self.state
+    }
+
+    /// Sets how closely region contours follow their arcs
+    ///
+    /// # Arguments
+    ///
+    /// * `tolerance` - Largest distance between an arc and the segments approximating it, in the file unit
+    ///
+    /// # Returns
+    ///
+    /// * `Self` - The iterator, approximating arcs of the regions that follow within the tolerance
+    pub fn arc_tolerance(mut self, tolerance: f64) -> Self {
+        self.arc_tolerance = tolerance;
+        self
+    }
+}
+
+impl Iterator for DrawPrimitives<'_> {
+    type Item = DrawPrimitive;
+
+    fn next(&mut self) -> Option<DrawPrimitive> {
+        for command in self.commands.by_ref() {
+            let from = self.plot_state.current_point();
+            let Ok(end) = self.plot_state.apply(command) else {
+                continue;
+            };
+            self.state.apply(command);
+
+            if let Some(contours) = &mut self.region {
+                match (command, end) {
+                    (Command::G37, _) => {
+                        let (contours, segments): (Vec<_>, Vec<_>) = self.region.take().unwrap_or_default().into_iter().unzip();
+                        let triangles = contours.iter().flat_map(|contour| triangulate(contour)).collect();
+                        return Some(DrawPrimitive::RegionFill { contours, segments, triangles });
+                    },
+                    (Command::D02(_), Some(to)) => contours.push((vec![to], Vec::new())),
+                    (Command::D01(_), Some(to)) => {
+                        // A contour starts at the current point when no D02 opened it
+                        if contours.is_empty() {
+                            contours.push((vec![from], Vec::new()));
+                        }
+                        let Some((vertices, segments)) = contours.last_mut() else {
+                            continue;
+                        };
+
+                        match self.plot_state.arc(command, from) {
+                            Some(arc) => {
+                                vertices.extend(arc.approximate(self.arc_tolerance));
+                                // The last vertex is the end point as resolved, not as computed on the circle
+                                vertices.pop();
+                                vertices.push(to);
+                                segments.push(ContourSegment::Arc { to, center: arc.center, clockwise: self.plot_state.clockwise() });
+                            },
+                            None => {
+                                vertices.push(to);
+                                segments.push(ContourSegment::Line { to });
+                            },
+                        }
+                    },
+                    _ => {}
+                }
+                continue;
+            }
+
+            match (command, end, self.state.aperture) {
+                (Command::G36, _, _) => self.region = Some(Vec::new()),
+                (Command::D01(_), Some(to), Some(aperture)) => {
+                    return Some(match self.plot_state.arc(command, from) {
+                        Some(arc) => DrawPrimitive::Arc {
+                            from,
+                            to,
+                            center: arc.center,
+                            clockwise: self.plot_state.clockwise(),
+                            aperture,
+                        },
+                        None => DrawPrimitive::Line { from, to, aperture },
+                    });
+                },
+                (Command::D03(_), Some(at), Some(aperture)) => {
+                    return Some(DrawPrimitive::Flash { at, aperture });
+                },
+                _ => {}
+            }
+        }
+
+        None
+    }
+}
+
+/// A circular arc drawn by a D01 operation, in the file unit
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub struct Arc {
+    /// Center of the circle
+    pub center: (f64, f64),
+    /// Radius of the circle
+    pub radius: f64,
+    /// Angle of the start point, in radians counter-clockwise from the X axis
+    pub start_angle: f64,
+    /// Angle of the end point, reached from the start angle in the direction of
+    /// the arc, so it is lower than the start angle for clockwise arcs
+    pub end_angle: f64,
+}
+
+impl Arc {
+    /// Signed angle swept by the arc in radians, positive counter-clockwise
+    pub fn sweep(&self) -> f64 {
+        self.end_angle - self.start_angle
+    }
+
+    /// Length of the arc, in the file unit
+    pub fn length(&self) -> f64 {
+        self.radius * self.sweep().abs()
+    }
+
+    /// Point of the circle at the given angle
+    pub fn point_at(&self, angle: f64) -> (f64, f64) {
+        (self.center.0 + self.radius * angle.cos(), self.center.1 + self.radius * angle.sin())
+    }
+
+    /// Points along the arc, so that the segments joining them stay within a
+    /// distance of it
+    ///
+    /// # Arguments
+    ///
+    /// * `tolerance` - Largest distance between the arc and the segments, in the file unit
+    ///
+    /// # Returns
+    ///
+    /// * `Vec<(f64, f64)>` - The points after the start point, ending with the end point
+    pub fn approximate(&self, tolerance: f64) -> Vec<(f64, f64)> {
+        // A chord spanning the angle strays from the circle by exactly the tolerance
+        let step = if tolerance > 0.0 && tolerance < self.radius {
+            2.0 * (1.0 - tolerance / self.radius).acos()
+        } else {
+            TAU
+        };
+        let count = (self.sweep().abs() / step).ceil().max(1.0) as usize;
+
+        (1..=count)
+            .map(|index| self.point_at(self.start_angle + self.sweep() * index as f64 / count as f64))
+            .collect()
+    }
+
+    /// The end points of the arc and every point where it is furthest along
+    /// the X or Y axis, enough to compute its bounds
+    fn extreme_points(&self) -> Vec<(f64, f64)> {
+        let (low, high) = if self.sweep() >= 0.0 {
+            (self.start_angle, self.end_angle)
+        } else {
+            (self.end_angle, self.start_angle)
+        };
+
+        let first_quadrant = (low / FRAC_PI_2).ceil() as i64;
+        let last_quadrant = (high / FRAC_PI_2).floor() as i64;
+
+        (first_quadrant..=last_quadrant)
+            .map(|quadrant| self.point_at(quadrant as f64 * FRAC_PI_2))
+            .chain([self.point_at(self.start_angle), self.point_at(self.end_angle)])
+            .collect()
+    }
+}
+
+impl PlotState {
+    /// The current point, in the file unit
+    pub fn current_point(&self) -> (f64, f64) {
+        self.current_point
+    }
+
+    /// The aperture selected by the last Dnn command, if any
+    pub fn current_aperture(&self) -> Option<u32> {
+        self.aperture
+    }
+
+    /// The interpolation mode D01 operations plot with
+    pub fn interpolation_mode(&self) -> InterpolationMode {
+        self.interpolation_mode
+    }
+
+    /// The notation coordinates are read in, None before the FS command
+    pub(crate) fn notation(&self) -> Option<Notation> {
+        self.notation.clone()
+    }
+
+    /// Whether arcs are in single quadrant mode, set by G74
+    pub(crate) fn single_quadrant(&self) -> bool {
+        self.single_quadrant
+    }
+
+    /// Updates the state with a command
+    ///
+    /// # Arguments
+    ///
+    /// * `command` - The next command of the file
+    ///
+    /// # Returns
+    ///
+    /// * `Result<Option<(f64, f64)>, GerberError>` - The new current point for D01, D02 and D03 operations, or an error if an operation precedes the FS command
+    pub fn apply(&mut self, command: &Command) -> Result<Option<(f64, f64)>, GerberError> {
+        let (x, y) = match command {
+            Command::FS(spec) => {
+                self.format_spec = Some(spec.clone());
+                self.notation = Some(spec.notation.clone());
+                return Ok(None);
+            },
+            Command::G90 => {
+                self.notation = Some(Notation::Absolute);
+                return Ok(None);
+            },
+            Command::G91 => {
+                self.notation = Some(Notation::Incremental);
+                return Ok(None);
+            },
+            Command::G01 | Command::G02 | Command::G03 => {
+                self.interpolation_mode = match command {
+                    Command::G02 => InterpolationMode::ClockwiseArc,
+                    Command::G03 => InterpolationMode::CounterClockwiseArc,
+                    _ => InterpolationMode::Linear,
+                };
+                return Ok(None);
+            },
+            Command::Dnn(code) => {
+                self.aperture = Some(*code);
+                return Ok(None);
+            },
+            Command::G74 | Command::G75 => {
+                self.single_quadrant = *command == Command::G74;
+                return Ok(None);
+            },
+            Command::D01(op) => (op.x, op.y),
+            Command::D02(op) => (op.x, op.y),
+            Command::D03(op) => (op.x, op.y),
+            _ => return Ok(None),
+        };
+
+        let spec = self.format_spec.as_ref().ok_or_else(|| GerberError::SemanticError(
+            "Coordinate operation found before the FS command.".to_string()
+        ))?;
+
+        let incremental = self.notation == Some(Notation::Incremental);
+        if let Some(x) = x {
+            let x = spec.resolve_x(x);
+            self.current_point.0 = if incremental { self.current_point.0 + x } else { x };
+        }
+        if let Some(y) = y {
+            let y = spec.resolve_y(y);
+            self.current_point.1 = if incremental { self.current_point.1 + y } else { y };
+        }
+
+        Ok(Some(self.current_point))
+    }
+
+    /// True when D01 operations draw clockwise arcs (G02)
+    pub(crate) fn clockwise(&self) -> bool {
+        self.interpolation_mode == InterpolationMode::ClockwiseArc
+    }
+
+    /// The arc drawn by a command that was just applied
+    ///
+    /// # Arguments
+    ///
+    /// * `command` - The command last given to `apply`
+    /// * `start` - The current point before the command
+    ///
+    /// # Returns
+    ///
+    /// * `Option<Arc>` - The arc for D01 operations in circular plot mode, None otherwise
+    pub fn arc(&self, command: &Command, start: (f64, f64)) -> Option<Arc> {
+        let Command::D01(op) = command else {
+            return None;
+        };
+        let clockwise = match self.interpolation_mode {
+            InterpolationMode::Linear => return None,
+            InterpolationMode::ClockwiseArc => true,
+            InterpolationMode::CounterClockwiseArc => false,
+        };
+        let spec = self.format_spec.as_ref()?;
+
+        // Omitted offsets are zero
+        let i = op.i.map_or(0.0, |i| spec.resolve_x(i));
+        let j = op.j.map_or(0.0, |j| spec.resolve_y(j));
+
+        Some(Gerber::arc_segment(start, self.current_point, i, j, clockwise, self.single_quadrant))
+    }
+}
+
+impl Gerber {
+    /// Computes the arc described by a circular D01 operation
+    ///
+    /// # Arguments
+    ///
+    /// * `start` - The current point before the operation
+    /// * `end` - The end point of the operation
+    /// * `i`, `j` - Offsets of the center from the start point
+    /// * `clockwise` - True in G02 mode, false in G03 mode
+    /// * `single_quadrant` - True in G74 mode, where the offsets are unsigned and
+    ///   the center is chosen so the arc spans at most 90 degrees
+    ///
+    /// # Returns
+    ///
+    /// * `Arc` - The arc; in multi quadrant mode, coinciding end points give a full circle
+    pub fn arc_segment(start: (f64, f64), end: (f64, f64), i: f64, j: f64, clockwise: bool, single_quadrant: bool) -> Arc {
+        let (i, j) = if single_quadrant {
+            single_quadrant_offsets(start, end, i.abs(), j.abs(), clockwise)
+        } else {
+            (i, j)
+        };
+
+        let center = (start.0 + i, start.1 + j);
+        let start_angle = (start.1 - center.1).atan2(start.0 - center.0);
+        let end_angle = (end.1 - center.1).atan2(end.0 - center.0);
+
+        let coinciding = (start.0 - end.0).abs() < 1e-12 && (start.1 - end.1).abs() < 1e-12;
+        let sweep = if coinciding {
+            if single_quadrant { 0.0 } else { TAU }
+        } else if clockwise {
+            (start_angle - end_angle).rem_euclid(TAU)
+        } else {
+            (end_angle - start_angle).rem_euclid(TAU)
+        };
+
+        Arc {
+            center,
+            radius: i.hypot(j),
+            start_angle,
+            end_angle: if clockwise { start_angle - sweep } else { start_angle + sweep },
+        }
+    }
+
+    /// Iterates over the drawing primitives, with coordinates in the file unit
+    ///
+    /// Draws and flashes made before any aperture is selected are skipped, as are
+    /// operations that can't be resolved. Arcs of region contours are
+    /// approximated within 0.001 of the file unit, see
+    /// [`DrawPrimitives::arc_tolerance`] to change it.
+    ///
+    /// # Returns
+    ///
+    /// * `DrawPrimitives` - Iterator yielding lines, arcs, flashes and regions in file order
+    pub fn draw_primitives(&self) -> DrawPrimitives<'_> {
+        DrawPrimitives {
+            commands: self.commands.iter(),
+            plot_state: PlotState::default(),
+            state: GraphicsState::default(),
+            region: None,
+            arc_tolerance: DEFAULT_ARC_TOLERANCE,
+        }
+    }
+
+    /// Resolves the coordinates of every D01, D02 and D03 operation into the file unit
+    ///
+    /// Omitted X or Y coordinates are carried forward from the previous operation,
+    /// and incremental coordinates are added to it.
+    ///
+    /// # Returns
+    ///
+    /// * `Result<Vec<(f64, f64)>, GerberError>` - One point per operation, or an error if an operation precedes the FS command
+    pub fn resolved_coordinates(&self) -> Result<Vec<(f64, f64)>, GerberError> {
+        let mut plot_state = PlotState::default();
+        let mut points = Vec::new();
+
+        for command in &self.commands {
+            if let Some(point) = plot_state.apply(command)? {
+                points.push(point);
+            }
+        }
+
+        Ok(points)
+    }
+
+    /// Resolves the position of every flash into the file unit
+    ///
+    /// Flashes are taken as they are written, so those inside block apertures
+    /// and step and repeat blocks are not moved to where the blocks are placed.
+    /// Flashes that can't be resolved, before the FS command, are skipped.
+    ///
+    /// # Returns
+    ///
+    /// * `Vec<(f64, f64)>` - One point per D03 operation, in file order
+    pub fn flash_points(&self) -> Vec<(f64, f64)> {
+        let mut plot_state = PlotState::default();
+
+        self.commands.iter()
+            .filter_map(|command| match (command, plot_state.apply(command)) {
+                (Command::D03(_), Ok(point)) => point,
+                _ => None,
+            })
+            .collect()
+    }
+
+    /// Computes the extents of the image, in the file unit
+    ///
+    /// Flashes and draws are grown by the size of the aperture used, while
+    /// region contours are taken as they are. Aperture transformations
+    /// (LM, LR, LS) are not applied.
+    ///
+    /// # Returns
+    ///
+    /// * `Option<(f64, f64, f64, f64)>` - `(min_x, min_y, max_x, max_y)`, or None if nothing is drawn or the coordinates can't be resolved
+    pub fn bounding_box(&self) -> Option<(f64, f64, f64, f64)> {
+        let mut plot_state = PlotState::default();
+        let mut tracker = BoundsTracker::default();
+
+        for command in &self.commands {
+            let start = plot_state.current_point();
+            let end = plot_state.apply(command).ok()?;
+            tracker.update(command, &plot_state, start, end);
+        }
+
+        tracker.bounds()
+    }
+
+    /// Sums the length drawn with each aperture, in the file unit
+    ///
+    /// Straight and circular D01 segments are counted, while moves, flashes and
+    /// region contours are not. Operations that can't be resolved are skipped.
+    ///
+    /// # Returns
+    ///
+    /// * `HashMap<u32, f64>` - Total drawn length, by aperture code
+    pub fn trace_lengths(&self) -> HashMap<u32, f64> {
+        let mut plot_state = PlotState::default();
+        let mut current_aperture = None;
+        let mut in_region = false;
+        let mut lengths = HashMap::new();
+
+        for command in &self.commands {
+            let start = plot_state.current_point();
+            let Ok(end) = plot_state.apply(command) else {
+                continue;
+            };
+
+            match (command, end, current_aperture) {
+                (Command::Dnn(code), _, _) => current_aperture = Some(*code),
+                (Command::G36, _, _) => in_region = true,
+                (Command::G37, _, _) => in_region = false,
+                (Command::D01(_), Some(end), Some(code)) if !in_region => {
+                    let length = match plot_state.arc(command, start) {
+                        Some(arc) => arc.length(),
+                        None => (end.0 - start.0).hypot(end.1 - start.1),
+                    };
+                    *lengths.entry(code).or_insert(0.0) += length;
+                },
+                _ => {}
+            }
+        }
+
+        lengths
+    }
+
+    /// Computes the signed area of every region contour, in the square of the file unit
+    ///
+    /// The shoelace formula is applied to the contour vertices, with arcs adding
+    /// the exact area between their chord and the circle. Counter-clockwise
+    /// contours have a positive area and clockwise ones a negative area, so
+    /// reversed contours stand out, and self-intersecting ones come out smaller
+    /// than they look. Contours are closed back to their start if they aren't.
+    ///
+    /// # Returns
+    ///
+    /// * `Vec<f64>` - The area of each contour, in file order
+    pub fn region_areas(&self) -> Vec<f64> {
+        let mut plot_state = PlotState::default();
+        let mut in_region = false;
+        // Start point and area so far of the contour being read
+        let mut contour: Option<((f64, f64), f64)> = None;
+        let mut areas = Vec::new();
+        let close = |((first_x, first_y), area): ((f64, f64), f64), (last_x, last_y): (f64, f64)| {
+            area + (last_x * first_y - first_x * last_y) / 2.0
+        };
+
+        for command in &self.commands {
+            let start = plot_state.current_point();
+            let Ok(end) = plot_state.apply(command) else {
+                continue;
+            };
+
+            match (command, end) {
+                (Command::G36, _) => in_region = true,
+                (Command::G37, _) => {
+                    in_region = false;
+                    areas.extend(contour.take().map(|contour| close(contour, start)));
+                },
+                (Command::D02(_), Some(end)) if in_region => {
+                    areas.extend(contour.take().map(|contour| close(contour, start)));
+                    contour = Some((end, 0.0));
+                },
+                (Command::D01(_), Some(end)) if in_region => {
+                    let (_, area) = contour.get_or_insert((start, 0.0));
+                    *area += (start.0 * end.1 - end.0 * start.1) / 2.0;
+
+                    // Circular segment between the chord and the arc
+                    if let Some(arc) = plot_state.arc(command, start) {
+                        let sweep = arc.sweep();
+                        *area += arc.radius * arc.radius * (sweep - sweep.sin()) / 2.0;
+                    }
+                },
+                _ => {}
+            }
+        }
+
+        areas
+    }
+}
+
+/// Grows the extents of the image command by command, as done by
+/// [`Gerber::bounding_box`]
+#[derive(Default)]
+pub(crate) struct BoundsTracker<'a> {
+    apertures: HashMap<u32, &'a ApertureTemplate>,
+    current_aperture: Option<u32>,
+    in_region: bool,
+    bounds: Option<(f64, f64, f64, f64)>,
+}
+
+impl<'a> BoundsTracker<'a> {
+    /// Takes a command into account once the plot state has applied it
+    ///
+    /// # Arguments
+    ///
+    /// * `command` - The command just applied
+    /// * `plot_state` - The plot state it was applied to
+    /// * `start` - The current point before the command
+    /// * `end` - What the plot state returned for the command
+    pub(crate) fn update(&mut self, command: &'a Command, plot_state: &PlotState, start: (f64, f64), end: Option<(f64, f64)>) {
+        let extent = if self.in_region {
+            (0.0, 0.0)
+        } else {
+            self.current_aperture
+                .and_then(|code| self.apertures.get(&code))
+                .map_or((0.0, 0.0), |template| aperture_extent(template))
+        };
+
+        match (command, end) {
+            (Command::AD(aperture_def), _) => {
+                self.apertures.insert(aperture_def.code, &aperture_def.template);
+            },
+            (Command::Dnn(code), _) => self.current_aperture = Some(*code),
+            (Command::G36, _) => self.in_region = true,
+            (Command::G37, _) => self.in_region = false,
+            (Command::D01(_), Some(end)) => {
+                extend_bounds(&mut self.bounds, start, extent);
+                extend_bounds(&mut self.bounds, end, extent);
+
+                // Arcs may bulge past their end points
+                if let Some(arc) = plot_state.arc(command, start) {
+                    for point in arc.extreme_points() {
+                        extend_bounds(&mut self.bounds, point, extent);
+                    }
+                }
+            },
+            (Command::D03(_), Some(end)) => extend_bounds(&mut self.bounds, end, extent),
+            _ => {}
+        }
+    }
+
+    /// `(min_x, min_y, max_x, max_y)` of everything seen so far, or None if nothing is drawn
+    pub(crate) fn bounds(&self) -> Option<(f64, f64, f64, f64)> {
+        self.bounds
+    }
+}
+
+/// Choose the signs of single quadrant (G74) arc offsets
+///
+/// The center must be as close as possible to equidistant from both ends, with
+/// the arc spanning at most 90 degrees in the direction of travel.
+fn single_quadrant_offsets(start: (f64, f64), end: (f64, f64), i: f64, j: f64, clockwise: bool) -> (f64, f64) {
+    let candidates = [(i, j), (-i, j), (i, -j), (-i, -j)];
+    let radius_error = |(di, dj): &(f64, f64)| {
+        let (center_x, center_y) = (start.0 + di, start.1 + dj);
+        ((end.0 - center_x).hypot(end.1 - center_y) - di.hypot(*dj)).abs()
+    };
+    let sweep = |(di, dj): &(f64, f64)| {
+        let (center_x, center_y) = (start.0 + di, start.1 + dj);
+        let start_angle = (start.1 - center_y).atan2(start.0 - center_x);
+        let end_angle = (end.1 - center_y).atan2(end.0 - center_x);
+        let sweep = if clockwise { start_angle - end_angle } else { end_angle - start_angle };
+        sweep.rem_euclid(TAU)
+    };
+
+    candidates.iter()
+        .filter(|offsets| sweep(offsets) <= FRAC_PI_2 + 1e-6)
+        .min_by(|a, b| radius_error(a).total_cmp(&radius_error(b)))
+        .copied()
+        .unwrap_or((i, j))
+}
+
+/// Half the width and height of the area covered by an aperture
+///
+/// Macro apertures are treated as having no size.
+fn aperture_extent(template: &ApertureTemplate) -> (f64, f64) {
+    match template {
+        ApertureTemplate::Circle(diameter, _) => (diameter / 2.0, diameter / 2.0),
+        ApertureTemplate::Rectangle(x, y, _) => (x / 2.0, y / 2.0),
+        ApertureTemplate::Obround(x, y, _) => (x / 2.0, y / 2.0),
+        ApertureTemplate::Polygon(diameter, _, _, _) => (diameter / 2.0, diameter / 2.0),
+        ApertureTemplate::Macro(_, _) => (0.0, 0.0),
+    }
+}
+
+fn extend_bounds(bounds: &mut Option<(f64, f64, f64, f64)>, (x, y): (f64, f64), (half_width, half_height): (f64, f64)) {
+    let (min_x, min_y, max_x, max_y) = bounds.get_or_insert((f64::MAX, f64::MAX, f64::MIN, f64::MIN));
+
+    *min_x = min_x.min(x - half_width);
+    *min_y = min_y.min(y - half_height);
+    *max_x = max_x.max(x + half_width);
+    *max_y = max_y.max(y + half_height);
+}
+
+/// Splits a simple polygon into triangles by ear clipping
+///
+/// The polygon may wind either way, and a closing vertex repeating the first
+/// one is ignored. Self-intersecting polygons are only partially covered.
+pub(crate) fn triangulate(points: &[(f64, f64)]) -> Vec<[(f64, f64); 3]> {
+    let mut polygon = points.to_vec();
+    if polygon.len() > 1 && polygon.first() == polygon.last() {
+        polygon.pop();
+    }
+
+    // Ears are convex corners, so work counter-clockwise
+    let signed_area: f64 = (0..polygon.len())
+        .map(|i| {
+            let (x1, y1) = polygon[i];
+            let (x2, y2) = polygon[(i + 1) % polygon.len()];
+            x1 * y2 - x2 * y1
+        })
+        .sum();
+    if signed_area < 0.0 {
+        polygon.reverse();
+    }
+
+    let mut triangles = Vec::new();
+    let mut remaining: Vec<usize> = (0..polygon.len()).collect();
+
+    while remaining.len() > 3 {
+        let count = remaining.len();
+        let ear = (0..count).find(|&i| {
+            let (prev, current, next) = (remaining[(i + count - 1) % count], remaining[i], remaining[(i + 1) % count]);
+            let (a, b, c) = (polygon[prev], polygon[current], polygon[next]);
+
+            orientation(a, b, c) > 0.0 && !remaining.iter()
+                .filter(|&&j| j != prev && j != current && j != next)
+                .any(|&j| in_triangle(polygon[j], a, b, c))
+        });
+
+        match ear {
+            Some(i) => {
+                triangles.push([
+                    polygon[remaining[(i + count - 1) % count]],
+                    polygon[remaining[i]],
+                    polygon[remaining[(i + 1) % count]],
+                ]);
+                remaining.remove(i);
+            },
+            None => break,
+        }
+    }
+
+    if remaining.len() == 3 {
+        triangles.push([polygon[remaining[0]], polygon[remaining[1]], polygon[remaining[2]]]);
+    }
+
+    triangles
+}
+
+/// Point in polygon test by ray casting
+pub(crate) fn in_polygon((x, y): (f64, f64), points: &[(f64, f64)]) -> bool {
+    let mut inside = false;
+
+    for (index, a) in points.iter().enumerate() {
+        let b = points[(index + 1) % points.len()];
+        if (a.1 > y) != (b.1 > y) && x < a.0 + (y - a.1) * (b.0 - a.0) / (b.1 - a.1) {
+            inside = !inside;
+        }
+    }
+
+    inside
+}
+
+/// Whether two areas, each made of closed contours filled even-odd, overlap
+///
+/// Areas only touching along an edge or at a corner don't overlap.
+pub(crate) fn contours_overlap(a: &[Vec<(f64, f64)>], b: &[Vec<(f64, f64)>]) -> bool {
+    let edges = |contours: &[Vec<(f64, f64)>]| -> Vec<((f64, f64), (f64, f64))> {
+        contours.iter()
+            .flat_map(|contour| contour.windows(2).map(|pair| (pair[0], pair[1])))
+            .collect()
+    };
+    // Even-odd over every contour, so holes are left out
+    let inside = |point: (f64, f64), contours: &[Vec<(f64, f64)>]| {
+        contours.iter().filter(|contour| in_polygon(point, contour)).count() % 2 == 1
+    };
+
+    let (a_edges, b_edges) = (edges(a), edges(b));
+    let crossing = a_edges.iter().any(|&(p1, p2)| b_edges.iter().any(|&(q1, q2)| {
+        orientation(p1, p2, q1) * orientation(p1, p2, q2) < 0.0
+            && orientation(q1, q2, p1) * orientation(q1, q2, p2) < 0.0
+    }));
+
+    crossing
+        || a.iter().flatten().any(|&point| inside(point, b))
+        || b.iter().flatten().any(|&point| inside(point, a))
+}
+
+/// Twice the signed area of a triangle, positive when counter-clockwise
+fn orientation(a: (f64, f64), b: (f64, f64), c: (f64, f64)) -> f64 {
+    (b.0 - a.0) * (c.1 - a.1) - (b.1 - a.1) * (c.0 - a.0)
+}
+
+/// Whether a point lies inside or on the edge of a counter-clockwise triangle
+fn in_triangle(point: (f64, f64), a: (f64, f64), b: (f64, f64), c: (f64, f64)) -> bool {
+    orientation(a, b, point) >= 0.0 && orientation(b, c, point) >= 0.0 && orientation(c, a, point) >= 0.0
+}
@@ -0,0 +1,156 @@
+//! # Gerber Job Files
+//!
+//! This module reads Gerber job files (`.gbrjob`), the JSON manifest listing
+//! the Gerber files of a board along with what each of them is, so a whole
+//! board can be loaded at once.
+
+use std::fs;
+use std::path::{Path, PathBuf};
+use serde_json::Value;
+use crate::Gerber;
+use crate::error::GerberError;
+
+/// What a Gerber file of a board describes, from its `FileFunction` attribute
+#[derive(Debug, PartialEq, Clone)]
+pub enum FileFunction {
+    /// A copper layer, numbered from the top, on the `Top`, `Inr` or `Bot` side
+    Copper { layer: u32, side: String },
+    /// The solder mask of a side
+    SolderMask(String),
+    /// The legend (silkscreen) of a side
+    Legend(String),
+    /// The solder paste of a side
+    Paste(String),
+    /// The board outline
+    Profile,
+    /// Any other function, with the attribute value as written
+    Other(String),
+}
+
+impl FileFunction {
+    /// Interprets the value of a `FileFunction` attribute
+    ///
+    /// # Arguments
+    ///
+    /// * `value` - The comma separated value, such as `Copper,L1,Top`
+    ///
+    /// # Returns
+    ///
+    /// * `FileFunction` - The function, or `Other` if it isn't one of the known ones
+    pub fn parse(value: &str) -> FileFunction {
+        let fields: Vec<&str> = value.split(',').map(str::trim).collect();
+
+        match fields.as_slice() {
+            ["Copper", layer, side, ..] => match layer.strip_prefix('L').and_then(|number| number.parse().ok()) {
+                Some(layer) => FileFunction::Copper { layer, side: side.to_string() },
+                None => FileFunction::Other(value.to_string()),
+            },
+            ["Soldermask", side, ..] => FileFunction::SolderMask(side.to_string()),
+            ["Legend", side, ..] => FileFunction::Legend(side.to_string()),
+            ["Paste", side, ..] => FileFunction::Paste(side.to_string()),
+            ["Profile", ..] => FileFunction::Profile,
+            _ => FileFunction::Other(value.to_string()),
+        }
+    }
+}
+
+/// A Gerber file listed in a job file
+#[derive(Debug, PartialEq, Clone)]
+pub struct JobFile {
+    /// Path of the file, relative paths being resolved from the job file
+    pub path: PathBuf,
+    /// What the file describes
+    pub function: FileFunction,
+    /// `Positive` or `Negative`, if the job file says
+    pub polarity: Option<String>,
+}
+
+/// A Gerber job file, describing the files and general specifications of a board
+#[derive(Debug, PartialEq, Clone)]
+pub struct GerberJob {
+    /// Name of the project
+    pub project: Option<String>,
+    /// Size of the board as (X, Y), in millimeters
+    pub size: Option<(f64, f64)>,
+    /// Number of copper layers
+    pub layer_count: Option<u32>,
+    /// Thickness of the board, in millimeters
+    pub board_thickness: Option<f64>,
+    /// The Gerber files of the board, in the order they are listed
+    pub files: Vec<JobFile>,
+}
+
+impl GerberJob {
+    /// Reads the job file at the given path
+    ///
+    /// # Arguments
+    ///
+    /// * `path` - Path to the `.gbrjob` file
+    ///
+    /// # Returns
+    ///
+    /// * `Result<GerberJob, GerberError>` - The job, or an error if the file couldn't be read or isn't a job file
+    pub fn from_path<P: AsRef<Path>>(path: P) -> Result<GerberJob, GerberError> {
+        let path = path.as_ref();
+        let content = fs::read_to_string(path)?;
+        let directory = path.parent().unwrap_or_else(|| Path::new(""));
+
+        let json: Value = serde_json::from_str(&content).map_err(|err| GerberError::SemanticError(
+            format!("Job file is not valid JSON: {}.", err)
+        ))?;
+
+        let specs = &json["GeneralSpecs"];
+        let size = match (specs["Size"]["X"].as_f64(), specs["Size"]["Y"].as_f64()) {
+            (Some(x), Some(y)) => Some((x, y)),
+            _ => None,
+        };
+
+        let entries = json["FilesAttributes"].as_array().ok_or_else(|| GerberError::SemanticError(
+            "Job file has no FilesAttributes list.".to_string()
+        ))?;
+
+        let files = entries.iter().enumerate()
+            .map(|(index, entry)| {
+                let file_path = entry["Path"].as_str().ok_or_else(|| GerberError::SemanticError(
+                    format!("Job file entry {} has no Path.", index)
+                ))?;
+                let function = entry["FileFunction"].as_str().ok_or_else(|| GerberError::SemanticError(
+                    format!("Job file entry {} has no FileFunction.", index)
+                ))?;
+
+                Ok(JobFile {
+                    path: directory.join(file_path),
+                    function: FileFunction::parse(function),
+                    polarity: entry["FilePolarity"].as_str().map(str::to_string),
+                })
+            })
+            .collect::<Result<Vec<_>, GerberError>>()?;
+
+        Ok(GerberJob {
+            project: specs["ProjectId"]["Name"].as_str().map(str::to_string),
+            size,
+            layer_count: specs["LayerNumber"].as_u64().map(|count| count as u32),
+            board_thickness: specs["BoardThickness"].as_f64(),
+            files,
+        })
+    }
+
+    /// Parses every Gerber file of the job
+    ///
+    /// A file that can't be read or parsed doesn't stop the others from loading,
+    /// its error is returned in its place.
+    ///
+    /// # Returns
+    ///
+    /// * `Vec<(FileFunction, Result<Gerber, GerberError>)>` - Every file with its function, in job order
+    pub fn load_all(&self) -> Vec<(FileFunction, Result<Gerber, GerberError>)> {
+        self.files.iter()
+            .map(|file| {
+                let gerber = fs::read_to_string(&file.path)
+                    .map_err(GerberError::from)
+                    .and_then(|content| content.parse());
+                (file.function.clone(), gerber)
+            })
+            .collect()
+    }
+}
@@ -1,25 +1,80 @@
 /// Module containing the Gerber command definitions and related types
 pub mod command;
+/// Module evaluating aperture macros
+pub mod aperture_macro;
+/// Module building Gerber files programmatically
+pub mod builder;
+/// Module comparing Gerber files by what they draw
+pub mod diff;
+/// Module exporting Excellon drill programs
+pub mod excellon;
+/// Module interpreting commands into coordinates in the file unit
+pub mod geometry;
+/// Module reading Gerber job files describing a whole board
+#[cfg(feature = "job")]
+pub mod job;
+/// Module answering common questions about the commands
+pub mod query;
+/// Module filling the image into pixels
+pub mod raster;
+/// Module rewriting commands in place
+pub mod transform;
+/// Module tracking the graphics state set by commands
+pub mod state;
+/// Module checking commands for semantic problems
+pub mod validation;
+#[cfg(feature = "visualizer")]
 pub mod visualizer;
+/// Module serializing commands back into Gerber text
+pub mod writer;
 
 use std::fs;
+use std::io::Read;
+use std::num::IntErrorKind;
+use std::ops::Range;
 use std::path::Path;
+use std::str::FromStr;
 use pest::iterators::Pair;
 use pest::Parser;
-use pest_derive::Parser;
 
 pub use command::Command;
-use crate::command::{ApertureDefinition, ApertureTemplate, D01Operation, D02Operation, D03Operation, FormatSpecification, Mirroring, Polarity};
-use crate::error::GerberError;
+use crate::command::{ApertureDefinition, ApertureTemplate, D01Operation, D02Operation, D03Operation, FormatSpecification, Mirroring, Notation, Polarity, ZeroOmission};
+use crate::error::{GerberError, GerberWarning};
 
-#[derive(Parser)]
-#[grammar = "gerber.pest"]
-pub struct GerberParser;
+// The grammar is an implementation detail, kept out of the public API so it can change
+mod parser {
+    use pest_derive::Parser;
+
+    #[derive(Parser)]
+    #[grammar = "gerber.pest"]
+    pub struct GerberParser;
+}
+use parser::{GerberParser, Rule};
 
 /// The main Gerber struct that contains all commands from a parsed Gerber file
+///
+/// Files are parsed with [`new`](Self::new), [`from_reader`](Self::from_reader)
+/// or `str::parse`, without going through the grammar:
+///
+/// ```
+/// use gerbers::Gerber;
+///
+/// let gerber: Gerber = "%MOMM*%\n%FSLAX26Y26*%\nM02*\n".parse().unwrap();
+/// assert_eq!(gerber.commands.len(), 3);
+/// ```
+///
+/// The grammar rules are not reachable from outside the crate:
+///
+/// ```compile_fail
+/// use gerbers::Rule;
+/// ```
 pub struct Gerber {
     /// Vector of parsed commands
     pub commands: Vec<Command>,
+    /// Deprecated constructs that were accepted and ignored while parsing
+    parse_warnings: Vec<GerberWarning>,
+    /// Line each command was parsed from, empty if the commands weren't parsed
+    source_lines: Vec<usize>,
 }
 
 impl Gerber {
@@ -34,21 +89,198 @@ impl Gerber {
     /// * `Result<Self, Box<dyn std::error::Error>>` - The parsed Gerber data or an error
     pub fn new<P: AsRef<Path>>(path: P) -> Result<Self, Box<dyn error::Error>> {
         let content = fs::read_to_string(path)?;
-        let mut pairs = GerberParser::parse(Rule::gerber_file, &content)?;
+        Ok(content.parse()?)
+    }
+
+    /// Creates a new Gerber struct by parsing everything produced by a reader
+    ///
+    /// # Arguments
+    ///
+    /// * `reader` - Source of the Gerber data
+    ///
+    /// # Returns
+    ///
+    /// * `Result<Self, GerberError>` - The parsed Gerber data or an error
+    pub fn from_reader<R: Read>(mut reader: R) -> Result<Self, GerberError> {
+        let mut content = String::new();
+        reader.read_to_string(&mut content)?;
+
+        content.parse()
+    }
+
+    /// Parses Gerber data from a reader, handing each command to a callback
+    /// instead of collecting them
+    ///
+    /// The grammar needs the whole input at once, so the text itself is still
+    /// read into memory; only the command list is never built.
+    ///
+    /// # Arguments
+    ///
+    /// * `reader` - Source of the Gerber data
+    /// * `callback` - Called once per parsed command, in file order
+    ///
+    /// # Returns
+    ///
+    /// * `Result<(), GerberError>` - Ok once every command was handed over, or the first error
+    pub fn parse_streaming<R: Read, F: FnMut(Command)>(mut reader: R, mut callback: F) -> Result<(), GerberError> {
+        let mut content = String::new();
+        reader.read_to_string(&mut content)?;
+
+        Self::parse_content(&content, |command, _| callback(command)).map(|_| ())
+    }
+
+    /// Parses Gerber data held in memory without stopping at the first error
+    ///
+    /// Statements that fail to parse are skipped and their errors collected,
+    /// while every other statement still produces its commands. Text that
+    /// doesn't match the grammar is skipped up to the end of its statement, the
+    /// next `*`, or `%` for extended commands.
+    ///
+    /// # Arguments
+    ///
+    /// * `content` - The Gerber data to parse
+    ///
+    /// # Returns
+    ///
+    /// * `(Self, Vec<GerberError>)` - The commands that parsed cleanly and every error encountered
+    pub fn parse_collecting(content: &str) -> (Self, Vec<GerberError>) {
+        let mut commands = Vec::new();
+        let mut errors = Vec::new();
+
+        let content = strip_bom(content);
+        let mut parse_warnings = Vec::new();
+        let mut source_lines = Vec::new();
+        let mut lines = LineCounter::new(content);
+        let mut position = 0;
+
+        // Statements are parsed one by one, so one not matching the grammar doesn't stop the others
+        loop {
+            let rest = content[position..].trim_start();
+            if rest.is_empty() {
+                break;
+            }
+            position = content.len() - rest.len();
+
+            let statement = match GerberParser::parse(Rule::next_statement, rest) {
+                Ok(mut pairs) => pairs.next().and_then(|statement| statement.into_inner().next()),
+                Err(err) => {
+                    errors.push(relocate_error(err.into(), content, position));
+                    let boundary = match rest.strip_prefix('%') {
+                        Some(extended) => extended.find('%').map(|index| index + 2),
+                        None => rest.find('*').map(|index| index + 1),
+                    };
+                    position += boundary.unwrap_or(rest.len());
+                    continue;
+                },
+            };
+            let Some(pair) = statement else {
+                break;
+            };
+
+            let parsed = commands.len();
+            parse_warnings.extend(format_warning(parsed, &pair));
+            let line = lines.line_at(position);
+            let end = position + pair.as_span().end();
+
+            if let Err(err) = Self::parse_pair(pair, &mut commands) {
+                // Drop anything the malformed statement already produced
+                commands.truncate(parsed);
+                errors.push(relocate_error(err, content, position));
+            }
+            source_lines.resize(commands.len(), line);
+            position = end;
+        }
+
+        if !commands.last().is_some_and(Command::is_end_of_file) {
+            errors.push(GerberError::SemanticError("The file doesn't end with an M02 command.".to_string()));
+        }
+
+        (Gerber { commands, parse_warnings, source_lines }, errors)
+    }
+
+    /// Parses Gerber data held in memory, keeping where each command comes from
+    ///
+    /// Statements producing several commands, such as regions and blocks,
+    /// give all of them the range of the whole statement.
+    ///
+    /// # Arguments
+    ///
+    /// * `content` - The Gerber data to parse
+    ///
+    /// # Returns
+    ///
+    /// * `Result<Vec<(Command, Range<usize>)>, GerberError>` - Each command with the byte range of its statement in `content`, or the first error
+    pub fn parse_with_spans(content: &str) -> Result<Vec<(Command, Range<usize>)>, GerberError> {
+        let stripped = strip_bom(content);
+        let mut pairs = GerberParser::parse(Rule::gerber_file, stripped)?;
+        let root = pairs.next()
+            .ok_or_else(|| GerberError::SemanticError("Empty Gerber file.".to_string()))?;
+
+        // Ranges still count the bytes of a stripped byte order mark
+        let offset = content.len() - stripped.len();
         let mut commands = Vec::new();
+        let mut spans = Vec::new();
 
-        if let Some(root) = pairs.next() {
-            for pair in root.into_inner() {
-                Self::parse_pair(pair, &mut commands)?;
+        for pair in root.into_inner() {
+            let span = pair.as_span();
+            Self::parse_pair(pair, &mut commands)?;
+            spans.resize(commands.len(), span.start() + offset..span.end() + offset);
+        }
+
+        Ok(commands.into_iter().zip(spans).collect())
+    }
+
+    /// Parses Gerber data held in memory, handing each command to a callback
+    /// with the line of the statement it comes from
+    ///
+    /// # Returns
+    ///
+    /// * `Result<Vec<GerberWarning>, GerberError>` - Deprecated constructs that were ignored, or the first error
+    fn parse_content<F: FnMut(Command, usize)>(content: &str, mut callback: F) -> Result<Vec<GerberWarning>, GerberError> {
+        let content = strip_bom(content);
+        let mut pairs = GerberParser::parse(Rule::gerber_file, content)?;
+        let root = pairs.next()
+            .ok_or_else(|| GerberError::SemanticError("Empty Gerber file.".to_string()))?;
+
+        // Only the format specification is retained between statements, as
+        // the coordinates that follow are decoded with it
+        let mut pending = Vec::new();
+        let mut parse_warnings = Vec::new();
+        let mut parsed = 0;
+        let mut lines = LineCounter::new(content);
+
+        for pair in root.into_inner() {
+            parse_warnings.extend(format_warning(parsed, &pair));
+            let line = lines.line_at(pair.as_span().start());
+
+            let retained = pending.len();
+            Self::parse_pair(pair, &mut pending)?;
+            parsed += pending.len() - retained;
+
+            let mut format_spec = None;
+            for command in pending.drain(retained..) {
+                if retained == 0 && format_spec.is_none() && matches!(command, Command::FS(_)) {
+                    format_spec = Some(command.clone());
+                }
+                callback(command, line);
             }
-        } else {
-            return Err(GerberError::SemanticError("Empty Gerber file.".to_string()).into());
+            pending.extend(format_spec);
         }
 
-        Ok(Gerber { commands })
+        Ok(parse_warnings)
+    }
+
+    /// Parses a single statement into commands
+    ///
+    /// Semantic errors that weren't already located at the offending part of the
+    /// statement are reported as parse errors where the statement starts.
+    fn parse_pair(pair: pest::iterators::Pair<Rule>, commands: &mut Vec<Command>) -> Result<(), GerberError> {
+        let span = pair.as_span();
+
+        Self::parse_statement(pair, commands).map_err(|err| locate_error(err, span))
     }
 
-    pub fn parse_pair(pair: pest::iterators::Pair<Rule>, commands: &mut Vec<Command>) -> Result<(), GerberError> {
+    fn parse_statement(pair: pest::iterators::Pair<Rule>, commands: &mut Vec<Command>) -> Result<(), GerberError> {
         match pair.as_rule() {
             Rule::g04 => {
                 let mut arguments = pair.clone().into_inner();
@@ -64,8 +296,26 @@ impl Gerber {
                     ).into());
                 }
 
+                // Standard attributes may be written as comments for backward compatibility
+                if let Some(attribute) = comment.as_span().as_str().trim_start().strip_prefix("#@!") {
+                    let statement = format!("%{}*%", attribute.trim());
+                    let attribute_pair = [Rule::tf, Rule::ta, Rule::to, Rule::td].into_iter()
+                        .filter_map(|rule| GerberParser::parse(rule, &statement).ok()?.next())
+                        .find(|attribute_pair| attribute_pair.as_str().len() == statement.len());
+
+                    if let Some(attribute_pair) = attribute_pair {
+                        return Self::parse_statement(attribute_pair, commands);
+                    }
+                }
+
                 commands.push(Command::G04(comment.as_span().as_str().to_string()));
             },
+            Rule::g70 => {
+                commands.push(Command::MO(command::Unit::Inches));
+            },
+            Rule::g71 => {
+                commands.push(Command::MO(command::Unit::Millimeters));
+            },
             Rule::mo => {
                 let mut arguments = pair.clone().into_inner();
 
@@ -94,12 +344,49 @@ impl Gerber {
                 commands.push(Command::MO(unit));
             },
             Rule::fs => {
-                let mut arguments = pair.clone().into_inner();
+                // Deprecated codes are reported as warnings by the caller
+                let mut arguments = pair.clone().into_inner()
+                    .filter(|p| p.as_rule() != Rule::deprecated_code)
+                    .peekable();
                 let mut format_spec = FormatSpecification {
                     x_integer_digits: 0,
                     x_decimal_digits: 0,
                     y_integer_digits: 0,
                     y_decimal_digits: 0,
+                    zero_omission: ZeroOmission::Leading,
+                    notation: Notation::Absolute,
+                };
+
+                // Zero omission mode, leading when absent
+                if let Some(zero_omission_pair) = arguments.next_if(|p| p.as_rule() == Rule::zero_omission) {
+                    let zero_omission_str = zero_omission_pair.as_span().as_str();
+                    format_spec.zero_omission = match zero_omission_str.to_uppercase().as_str() {
+                        "L" => ZeroOmission::Leading,
+                        "T" => ZeroOmission::Trailing,
+                        // Deprecated explicit decimal mode, every digit is present so either decoding works
+                        "D" => ZeroOmission::Leading,
+                        _ => {
+                            return Err(GerberError::SemanticError(
+                                format!("Unrecognized zero omission mode: {}", zero_omission_str)
+                            ));
+                        }
+                    };
+                }
+
+                // Coordinate notation
+                let notation_pair = arguments.next()
+                    .ok_or_else(|| GerberError::SemanticError(
+                        "Missing coordinate notation in FS command.".to_string()
+                    ))?;
+                let notation_str = notation_pair.as_span().as_str();
+                format_spec.notation = match notation_str.to_uppercase().as_str() {
+                    "A" => Notation::Absolute,
+                    "I" => Notation::Incremental,
+                    _ => {
+                        return Err(GerberError::SemanticError(
+                            format!("Unrecognized coordinate notation: {}", notation_str)
+                        ));
+                    }
                 };
 
                 // X integer digits
@@ -149,6 +436,21 @@ impl Gerber {
                     ).into());
                 }
 
+                // Coordinates hold at most 6 digits on either side of the decimal point
+                let digit_counts = [
+                    ("X integer", format_spec.x_integer_digits, x_int_digits),
+                    ("X decimal", format_spec.x_decimal_digits, x_dec_digits),
+                    ("Y integer", format_spec.y_integer_digits, y_int_digits),
+                    ("Y decimal", format_spec.y_decimal_digits, y_dec_digits),
+                ];
+                for (name, digits, digits_pair) in digit_counts {
+                    if !(1..=6).contains(&digits) {
+                        return Err(locate_error(GerberError::SemanticError(
+                            format!("{} digits in FS command must be between 1 and 6, got {}.", name, digits)
+                        ), digits_pair.as_span()));
+                    }
+                }
+
                 commands.push(Command::FS(format_spec));
             },
             Rule::ad => {
@@ -165,165 +467,56 @@ impl Gerber {
                         "Missing aperture code in AD command.".to_string()
                     ))?;
 
-                let ap_str = ap_pair.as_span().as_str();
-                aperture_definition.code = ap_str.trim_start_matches('D').parse::<u32>()
-                    .map_err(|_| GerberError::SemanticError(
-                        format!("Aperture code '{}' could not be parsed as an integer.", ap_str)
-                    ))?;
+                aperture_definition.code = parse_aperture_code(&ap_pair, "AD")?;
 
                 // Parse template
-                if let Some(template_pair) = arguments.next() {
-                    let pair_str = format!("{:?}", template_pair.as_rule());
-                    if pair_str == "template_circle" {
-                        let mut diameter = 0.0;
-                        let mut optional_hole: Option<f64> = None;
-                        let mut circle_arguments = template_pair.clone().into_inner();
-
-                        // Parse diameter
-                        if let Some(diameter_pair) = circle_arguments.next() {
-                            diameter = diameter_pair.as_span().as_str().parse()
-                                .map_err(|_| GerberError::SemanticError(
-                                    "Circle diameter could not be parsed as a number.".to_string()
-                                ))?;
-                        }
-
-                        // Parse optional hole
-                        if let Some(option_pair) = circle_arguments.next() {
-                            optional_hole = Some(option_pair.as_span().as_str().parse()
-                                .map_err(|_| GerberError::SemanticError(
-                                    "Circle hole diameter could not be parsed as a number.".to_string()
-                                ))?);
-                        }
-
-                        aperture_definition.template = ApertureTemplate::Circle(diameter, optional_hole);
-                    } else if pair_str == "template_rectangle" {
-                        let mut arguments = template_pair.clone().into_inner();
-                        let mut x = 0.0;
-                        let mut y = 0.0;
-                        let mut hole_diameter = None;
-
-                        // Parse diameter
-                        if let Some(x_pair) = arguments.next() {
-                            x = x_pair.as_span().as_str().parse()
-                                .map_err(|_| GerberError::SemanticError(
-                                    "Rectangle x could not be parsed.".to_string()
-                                ))?;
-                        }
-
-                        if let Some(y_pair) = arguments.next() {
-                            y = y_pair.as_span().as_str().parse()
-                                .map_err(|_| GerberError::SemanticError(
-                                    "Rectangle y could not be parsed.".to_string()
-                                ))?;
-                        }
-
-                        // Parse optional hole
-                        if let Some(hole_pair) = arguments.next() {
-                            hole_diameter = Some(hole_pair.as_span().as_str().parse()
-                                .map_err(|_| GerberError::SemanticError(
-                                    "Rectangle y could not be parsed.".to_string()
-                                ))?);
-                        }
-
-                        aperture_definition.template = ApertureTemplate::Rectangle(x, y, hole_diameter);
-                    } else if pair_str == "template_obround" {
-                        let mut arguments = template_pair.clone().into_inner();
-                        let mut x = 0.0;
-                        let mut y = 0.0;
-                        let mut hole_diameter = None;
-
-                        // Parse diameter
-                        if let Some(x_pair) = arguments.next() {
-                            x = x_pair.as_span().as_str().parse()
-                                .map_err(|_| GerberError::SemanticError(
-                                    "Rectangle x could not be parsed.".to_string()
-                                ))?;
-                        }
-
-                        if let Some(y_pair) = arguments.next() {
-                            y = y_pair.as_span().as_str().parse()
-                                .map_err(|_| GerberError::SemanticError(
-                                    "Rectangle y could not be parsed.".to_string()
-                                ))?;
-                        }
-
-                        // Parse optional hole
-                        if let Some(hole_pair) = arguments.next() {
-                            hole_diameter = Some(hole_pair.as_span().as_str().parse()
-                                .map_err(|_| GerberError::SemanticError(
-                                    "Rectangle y could not be parsed.".to_string()
-                                ))?);
-                        }
-
-                        aperture_definition.template = ApertureTemplate::Obround(x, y, hole_diameter);
-                    } else if pair_str == "template_polygon" {
-                        let mut arguments = template_pair.clone().into_inner();
-                        let mut outer_diameter = 0.0;
-                        let mut vertices = 0;
-                        let mut rotation = None;
-                        let mut hole_diameter = None;
-
-                        // Parse diameter
-                        if let Some(outer_diam_pair) = arguments.next() {
-                            outer_diameter = outer_diam_pair.as_span().as_str().parse()
-                                .map_err(|_| GerberError::SemanticError(
-                                    "Rectangle x could not be parsed.".to_string()
-                                ))?;
-                        }
+                let template_pair = arguments.next()
+                    .ok_or_else(|| GerberError::SemanticError(
+                        "Missing aperture template in AD command.".to_string()
+                    ))?;
 
-                        if let Some(vertices_pair) = arguments.next() {
-                            vertices = vertices_pair.as_span().as_str().parse()
-                                .map_err(|_| GerberError::SemanticError(
-                                    "Rectangle y could not be parsed.".to_string()
-                                ))?;
+                aperture_definition.template = match template_pair.as_rule() {
+                    Rule::template_circle => {
+                        let parameters = parse_template_parameters(template_pair, "Circle", &["diameter", "hole diameter"])?;
+                        ApertureTemplate::Circle(parameters[0], parameters.get(1).copied())
+                    },
+                    Rule::template_rectangle => {
+                        let parameters = parse_template_parameters(template_pair, "Rectangle", &["x size", "y size", "hole diameter"])?;
+                        ApertureTemplate::Rectangle(parameters[0], parameters[1], parameters.get(2).copied())
+                    },
+                    Rule::template_obround => {
+                        let parameters = parse_template_parameters(template_pair, "Obround", &["x size", "y size", "hole diameter"])?;
+                        ApertureTemplate::Obround(parameters[0], parameters[1], parameters.get(2).copied())
+                    },
+                    Rule::template_polygon => {
+                        // The rotation comes before the hole, so a lone third parameter is the rotation
+                        let parameters = parse_template_parameters(template_pair, "Polygon", &["outer diameter", "vertices", "rotation", "hole diameter"])?;
+                        let vertices = parameters[1];
+                        if vertices.fract() != 0.0 || !(3.0..=12.0).contains(&vertices) {
+                            return Err(GerberError::SemanticError(
+                                format!("Polygon vertices must be a whole number from 3 to 12, found {}.", vertices)
+                            ));
                         }
 
-                        // Parse optional hole
-                        if let Some(rotation_pair) = arguments.next() {
-                            rotation = Some(rotation_pair.as_span().as_str().parse()
-                                .map_err(|_| GerberError::SemanticError(
-                                    "Rectangle y could not be parsed.".to_string()
-                                ))?);
-                        }
+                        ApertureTemplate::Polygon(parameters[0], vertices as u32, parameters.get(2).copied(), parameters.get(3).copied())
+                    },
+                    Rule::template_name => {
+                        let mut arguments = template_pair.into_inner();
+                        let name = arguments.next().map_or(String::new(), |name_pair| name_pair.as_str().to_string());
 
-                        if let Some(hole_pair) = arguments.next() {
-                            hole_diameter = Some(hole_pair.as_span().as_str().parse()
+                        let parameters = arguments
+                            .map(|parameter_pair| parameter_pair.as_str().parse()
                                 .map_err(|_| GerberError::SemanticError(
-                                    "Rectangle y could not be parsed.".to_string()
-                                ))?);
-                        }
-
-                        aperture_definition.template = ApertureTemplate::Polygon(outer_diameter, vertices, rotation, hole_diameter);
-                    } else if pair_str == "template_name" {
-                        let mut arguments = template_pair.clone().into_inner();
-
-                        let mut name = "".to_string();
-                        let mut parameters = vec![];
-
-                        if let Some(name_pair) = arguments.next() {
-                            name = name_pair.as_span().as_str().to_string();
-                        }
-
-                        while let Some(parameter_pair) = arguments.next() {
-                            parameters.push(
-                                parameter_pair.as_span().as_str().parse()
-                                    .map_err(|_| GerberError::SemanticError(
-                                        "Rectangle y could not be parsed.".to_string()
-                                    ))?
-                            );
-                        }
-
-                        aperture_definition.template = ApertureTemplate::Macro(name, parameters);
-                    } else {
-                        return Err(GerberError::SemanticError(
-                            format!("Unsupported aperture template: {}", pair_str)
-                        ).into());
-                    }
-                } else {
-                    return Err(GerberError::SemanticError(
-                        "Missing aperture template in AD command.".to_string()
-                    ).into());
-                }
+                                    format!("Macro parameter '{}' could not be parsed as a number.", parameter_pair.as_str())
+                                )))
+                            .collect::<Result<Vec<f64>, GerberError>>()?;
+
+                        ApertureTemplate::Macro(name, parameters)
+                    },
+                    rule => return Err(GerberError::SemanticError(
+                        format!("Unsupported aperture template: {:?}", rule)
+                    )),
+                };
 
                 commands.push(Command::AD(aperture_definition));
             },
@@ -337,6 +530,17 @@ impl Gerber {
                 }
 
                 while let Some(macro_body_pair) = arguments.next() {
+                    // Primitives using variables are evaluated when the macro is instantiated
+                    if let Some(code) = aperture_macro::primitive_code(macro_body_pair.as_rule()) {
+                        let expressions: Vec<String> = macro_body_pair.clone().into_inner()
+                            .map(|expr| expr.as_span().as_str().trim_end().to_string())
+                            .collect();
+                        if expressions.iter().any(|expr| expr.parse::<f64>().is_err()) {
+                            primitives.push(command::AMPrimitive::Parametric(code, expressions));
+                            continue;
+                        }
+                    }
+
                     let macro_str = format!("{:?}", macro_body_pair.as_rule());
                     if macro_str == "primitive_comment" {
                         let mut inner = macro_body_pair.into_inner();
@@ -378,23 +582,19 @@ impl Gerber {
                     } else if macro_str == "primitive_outline" {
                         let mut inner = macro_body_pair.into_inner();
                         let exposure = parse_bool(inner.next());
-                        let mut points = Vec::new();
-
-                        // First point
-                        let x = parse_f64(inner.next());
-                        let y = parse_f64(inner.next());
-                        points.push((x, y));
-
-                        // Remaining points
-                        while let (Some(x_opt), Some(y_opt)) = (inner.next(), inner.next()) {
-                            if inner.clone().count() <= 1 {
-                                // Last parameter is rotation
-                                break;
-                            }
-                            let x = parse_f64_value(x_opt);
-                            let y = parse_f64_value(y_opt);
-                            points.push((x, y));
+                        let vertices = parse_u32(inner.next());
+
+                        // The start point is repeated at the end, so there is one more point than vertices
+                        let coordinates: Vec<f64> = inner.by_ref()
+                            .take(2 * (vertices as usize + 1))
+                            .map(parse_f64_value)
+                            .collect();
+                        if coordinates.len() != 2 * (vertices as usize + 1) || inner.clone().count() != 1 {
+                            return Err(GerberError::SemanticError(
+                                format!("Outline primitive with {} vertices must have {} points.", vertices, vertices + 1)
+                            ));
                         }
+                        let points = coordinates.chunks(2).map(|point| (point[0], point[1])).collect();
 
                         let rotation = parse_f64(inner.next());
                         primitives.push(command::AMPrimitive::Outline(exposure, points, rotation));
@@ -406,6 +606,7 @@ impl Gerber {
                         let center_y = parse_f64(inner.next());
                         let diameter = parse_f64(inner.next());
                         let rotation = parse_f64(inner.next());
+                        aperture_macro::check_polygon_vertices(vertices)?;
                         primitives.push(command::AMPrimitive::Polygon(exposure, vertices, center_x, center_y, diameter, rotation));
                     } else if macro_str == "primitive_thermal" {
                         let mut inner = macro_body_pair.into_inner();
@@ -418,8 +619,9 @@ impl Gerber {
                         primitives.push(command::AMPrimitive::Thermal(center_x, center_y, outer_diameter, inner_diameter, gap, rotation));
                     } else if macro_str == "variable_definition" {
                         let mut inner = macro_body_pair.into_inner();
-                        let var_num = parse_u32(inner.next());
-                        let expression = inner.next().map_or(String::new(), |expr| expr.as_span().as_str().to_string());
+                        let var_num = inner.next()
+                            .map_or(0, |var| var.as_span().as_str().trim_start_matches('$').parse::<u32>().unwrap_or(0));
+                        let expression = inner.next().map_or(String::new(), |expr| expr.as_span().as_str().trim_end().to_string());
                         primitives.push(command::AMPrimitive::VariableDefinition(var_num, expression));
                     }
                 }
@@ -435,11 +637,7 @@ impl Gerber {
                         "Missing aperture code in Dnn command.".to_string()
                     ))?;
 
-                let ap_str = ap_pair.as_span().as_str();
-                let aperture_command = ap_str.trim_start_matches('D').parse::<u32>()
-                    .map_err(|_| GerberError::SemanticError(
-                        format!("Aperture code '{}' could not be parsed as an integer.", ap_str)
-                    ))?;
+                let aperture_command = parse_aperture_code(&ap_pair, "Dnn")?;
 
                 commands.push(Command::Dnn(aperture_command));
             },
@@ -455,8 +653,18 @@ impl Gerber {
             Rule::g75 => {
                 commands.push(Command::G75);
             },
+            Rule::g74 => {
+                commands.push(Command::G74);
+            },
+            Rule::g90 => {
+                commands.push(Command::G90);
+            },
+            Rule::g91 => {
+                commands.push(Command::G91);
+            },
             Rule::d01 => {
                 let mut arguments = pair.clone().into_inner();
+                let format_spec = active_format(commands);
                 let mut op = D01Operation {
                     x: None,
                     y: None,
@@ -465,6 +673,16 @@ impl Gerber {
                 };
 
                 while let Some(new_pair) = arguments.next() {
+                    // The plot mode comes first, so it is in effect for the draw
+                    if new_pair.as_rule() == Rule::plot_mode {
+                        commands.push(match new_pair.as_str() {
+                            "G02" => Command::G02,
+                            "G03" => Command::G03,
+                            _ => Command::G01,
+                        });
+                        continue;
+                    }
+
                     let pair_str = format!("{:?}", new_pair.as_rule());
                     let mut coord_args = new_pair.clone().into_inner();
 
@@ -472,26 +690,19 @@ impl Gerber {
                         let coord_str = coord_pair.as_span().as_str();
 
                         if pair_str == "x_coord" {
-                            op.x = Some(coord_str.parse()
-                                .map_err(|_| GerberError::SemanticError(
-                                    format!("X coordinate '{}' could not be parsed as a number.", coord_str)
-                                ))?);
+                            op.x = Some(decode_x(coord_str, &format_spec)
+                                .map_err(|e| coordinate_error("X", &coord_pair, e))?);
                         } else if pair_str == "y_coord" {
-                            op.y = Some(coord_str.parse()
-                                .map_err(|_| GerberError::SemanticError(
-                                    format!("Y coordinate '{}' could not be parsed as a number.", coord_str)
-                                ))?);
+                            op.y = Some(decode_y(coord_str, &format_spec)
+                                .map_err(|e| coordinate_error("Y", &coord_pair, e))?);
                         } else if pair_str == "ij_coords" {
-                            op.i = Some(coord_str.parse()
-                                .map_err(|_| GerberError::SemanticError(
-                                    format!("Y coordinate '{}' could not be parsed as a number.", coord_str)
-                                ))?);
+                            op.i = Some(decode_x(coord_str, &format_spec)
+                                .map_err(|e| coordinate_error("I", &coord_pair, e))?);
 
                             if let Some(j_pair) = coord_args.next() {
-                                op.j = Some(j_pair.as_span().as_str().parse()
-                                    .map_err(|_| GerberError::SemanticError(
-                                        format!("Y coordinate '{}' could not be parsed as a number.", coord_str)
-                                    ))?);
+                                let j_str = j_pair.as_span().as_str();
+                                op.j = Some(decode_y(j_str, &format_spec)
+                                    .map_err(|e| coordinate_error("J", &j_pair, e))?);
                             } else {
                                 return Err(GerberError::SemanticError(
                                     "Missing J parameter.".to_string()
@@ -506,6 +717,7 @@ impl Gerber {
             },
             Rule::d02 => {
                 let mut arguments = pair.clone().into_inner();
+                let format_spec = active_format(commands);
                 let mut op = D02Operation {
                     x: None,
                     y: None,
@@ -519,15 +731,11 @@ impl Gerber {
                         let coord_str = coord_pair.as_span().as_str();
 
                         if pair_str == "x_coord" {
-                            op.x = Some(coord_str.parse()
-                                .map_err(|_| GerberError::SemanticError(
-                                    format!("X coordinate '{}' could not be parsed as a number.", coord_str)
-                                ))?);
+                            op.x = Some(decode_x(coord_str, &format_spec)
+                                .map_err(|e| coordinate_error("X", &coord_pair, e))?);
                         } else if pair_str == "y_coord" {
-                            op.y = Some(coord_str.parse()
-                                .map_err(|_| GerberError::SemanticError(
-                                    format!("Y coordinate '{}' could not be parsed as a number.", coord_str)
-                                ))?);
+                            op.y = Some(decode_y(coord_str, &format_spec)
+                                .map_err(|e| coordinate_error("Y", &coord_pair, e))?);
                         }
                     }
                 }
@@ -536,6 +744,7 @@ impl Gerber {
             },
             Rule::d03 => {
                 let mut arguments = pair.clone().into_inner();
+                let format_spec = active_format(commands);
                 let mut op = D03Operation {
                     x: None,
                     y: None,
@@ -549,15 +758,11 @@ impl Gerber {
                         let coord_str = coord_pair.as_span().as_str();
 
                         if pair_str == "x_coord" {
-                            op.x = Some(coord_str.parse()
-                                .map_err(|_| GerberError::SemanticError(
-                                    format!("X coordinate '{}' could not be parsed as a number.", coord_str)
-                                ))?);
+                            op.x = Some(decode_x(coord_str, &format_spec)
+                                .map_err(|e| coordinate_error("X", &coord_pair, e))?);
                         } else if pair_str == "y_coord" {
-                            op.y = Some(coord_str.parse()
-                                .map_err(|_| GerberError::SemanticError(
-                                    format!("Y coordinate '{}' could not be parsed as a number.", coord_str)
-                                ))?);
+                            op.y = Some(decode_y(coord_str, &format_spec)
+                                .map_err(|e| coordinate_error("Y", &coord_pair, e))?);
                         }
                     }
                 }
@@ -662,8 +867,78 @@ impl Gerber {
 
                 commands.push(Command::G37);
             },
-            Rule::ab_statement => {},
-            Rule::sr_statement => {},
+            Rule::ab_statement => {
+                let mut arguments = pair.clone().into_inner();
+
+                let open_pair = arguments.next()
+                    .ok_or_else(|| GerberError::SemanticError(
+                        "Missing opening AB command.".to_string()
+                    ))?;
+
+                let ap_pair = open_pair.into_inner().next()
+                    .ok_or_else(|| GerberError::SemanticError(
+                        "Missing aperture code in AB command.".to_string()
+                    ))?;
+
+                let aperture_code = parse_aperture_code(&ap_pair, "AB")?;
+
+                commands.push(Command::AB(Some(aperture_code)));
+
+                let block_pair = arguments.next()
+                    .ok_or_else(|| GerberError::SemanticError(
+                        "Expected block".to_string()
+                    ))?;
+
+                for statement in block_pair.into_inner() {
+                    Self::parse_pair(statement, commands)?;
+                }
+
+                commands.push(Command::AB(None));
+            },
+            Rule::sr_statement => {
+                let mut arguments = pair.clone().into_inner();
+
+                let open_pair = arguments.next()
+                    .ok_or_else(|| GerberError::SemanticError(
+                        "Missing opening SR command.".to_string()
+                    ))?;
+
+                let mut parameters = open_pair.into_inner();
+                let x_repeats = parameters.next().map(|p| p.as_str())
+                    .ok_or_else(|| GerberError::SemanticError(
+                        "Missing X repeats in SR command.".to_string()
+                    ))?;
+                let y_repeats = parameters.next().map(|p| p.as_str())
+                    .ok_or_else(|| GerberError::SemanticError(
+                        "Missing Y repeats in SR command.".to_string()
+                    ))?;
+
+                let step_and_repeat = command::StepAndRepeat {
+                    x_repeats: x_repeats.parse()
+                        .map_err(|_| GerberError::SemanticError(
+                            format!("X repeats '{}' in SR command could not be parsed as an integer.", x_repeats)
+                        ))?,
+                    y_repeats: y_repeats.parse()
+                        .map_err(|_| GerberError::SemanticError(
+                            format!("Y repeats '{}' in SR command could not be parsed as an integer.", y_repeats)
+                        ))?,
+                    x_step: parse_f64(parameters.next()),
+                    y_step: parse_f64(parameters.next()),
+                };
+
+                commands.push(Command::SR(Some(step_and_repeat)));
+
+                let block_pair = arguments.next()
+                    .ok_or_else(|| GerberError::SemanticError(
+                        "Expected block".to_string()
+                    ))?;
+
+                for statement in block_pair.into_inner() {
+                    Self::parse_pair(statement, commands)?;
+                }
+
+                commands.push(Command::SR(None));
+            },
             Rule::tf => {
                 let mut arguments = pair.clone().into_inner();
                 let mut attribute_value: Vec<String> = vec![];
@@ -681,20 +956,261 @@ impl Gerber {
 
                 commands.push(Command::TF(attribute_name, attribute_value));
             },
-            Rule::ta => {},
-            Rule::to => {},
-            Rule::td => {},
+            Rule::ta => {
+                let mut arguments = pair.clone().into_inner();
+                let mut attribute_value: Vec<String> = vec![];
+
+                let attribute_name_pair = arguments.next()
+                    .ok_or_else(|| GerberError::SemanticError(
+                        "Missing attribute name in TA command.".to_string()
+                    ))?;
+
+                let attribute_name = attribute_name_pair.as_span().as_str().to_string();
+
+                for new_value_pair in arguments {
+                    attribute_value.push(new_value_pair.as_span().as_str().to_string());
+                }
+
+                commands.push(Command::TA(attribute_name, attribute_value));
+            },
+            Rule::to => {
+                let mut arguments = pair.clone().into_inner();
+                let mut attribute_value: Vec<String> = vec![];
+
+                let attribute_name_pair = arguments.next()
+                    .ok_or_else(|| GerberError::SemanticError(
+                        "Missing attribute name in TO command.".to_string()
+                    ))?;
+
+                let attribute_name = attribute_name_pair.as_span().as_str().to_string();
+
+                for new_value_pair in arguments {
+                    attribute_value.push(new_value_pair.as_span().as_str().to_string());
+                }
+
+                commands.push(Command::TO(attribute_name, attribute_value));
+            },
+            Rule::td => {
+                let mut arguments = pair.clone().into_inner();
+
+                let attribute_name = arguments.next()
+                    .map(|name_pair| name_pair.as_span().as_str().to_string());
+
+                commands.push(Command::TD(attribute_name));
+            },
+            Rule::image_name | Rule::load_name => {
+                let name = pair.clone().into_inner().next()
+                    .map_or(String::new(), |name_pair| name_pair.as_span().as_str().to_string());
+
+                commands.push(match pair.as_rule() {
+                    Rule::image_name => Command::IN(name),
+                    _ => Command::LN(name),
+                });
+            },
             Rule::m02 => {
                 commands.push(Command::M02);
             },
-            _ => {}
+            Rule::m00 => {
+                commands.push(Command::M00);
+            },
+            Rule::m01 => {
+                commands.push(Command::M01);
+            },
+            Rule::EOI => {},
+            _ => commands.push(Command::Unknown(pair.as_str().to_string())),
         }
         Ok(())
     }
 }
 
+/// Finds the format specification in effect, which the spec requires to be
+/// set once near the start of the file
+fn active_format(commands: &[Command]) -> Option<FormatSpecification> {
+    commands.iter().find_map(|command| match command {
+        Command::FS(format_spec) => Some(format_spec.clone()),
+        _ => None,
+    })
+}
+
+fn decode_x(coord_str: &str, format_spec: &Option<FormatSpecification>) -> Result<i64, std::num::ParseIntError> {
+    match format_spec {
+        Some(spec) => spec.decode_x(coord_str),
+        None => coord_str.parse(),
+    }
+}
+
+fn decode_y(coord_str: &str, format_spec: &Option<FormatSpecification>) -> Result<i64, std::num::ParseIntError> {
+    match format_spec {
+        Some(spec) => spec.decode_y(coord_str),
+        None => coord_str.parse(),
+    }
+}
+
+/// Describes a coordinate that failed to decode, telling overflow apart from bad digits
+fn coordinate_error(axis: &str, coord_pair: &Pair<Rule>, error: std::num::ParseIntError) -> GerberError {
+    let coord_str = coord_pair.as_str();
+    let error = match error.kind() {
+        IntErrorKind::PosOverflow | IntErrorKind::NegOverflow => GerberError::SemanticError(
+            format!("{} coordinate '{}' is out of range, it must fit in a 64-bit integer.", axis, coord_str)
+        ),
+        _ => GerberError::SemanticError(
+            format!("{} coordinate '{}' could not be parsed as a number.", axis, coord_str)
+        ),
+    };
+
+    locate_error(error, coord_pair.as_span())
+}
+
+/// Turns a semantic error into a parse error pointing at the start of a span
+///
+/// # Arguments
+///
+/// * `error` - The error to locate, other kinds of errors are returned as they are
+/// * `span` - The part of the statement the error is about
+fn locate_error(error: GerberError, span: pest::Span) -> GerberError {
+    let GerberError::SemanticError(message) = error else {
+        return error;
+    };
+
+    let (line, column) = span.start_pos().line_col();
+    GerberError::ParseError {
+        line,
+        column,
+        source_line: span.start_pos().line_of().trim_end_matches(['\r', '\n']).to_string(),
+        message,
+    }
+}
+
+impl FromStr for Gerber {
+    type Err = GerberError;
+
+    /// Parses Gerber data held in memory
+    fn from_str(content: &str) -> Result<Self, Self::Err> {
+        let mut commands = Vec::new();
+        let mut source_lines = Vec::new();
+        let parse_warnings = Self::parse_content(content, |command, line| {
+            commands.push(command);
+            source_lines.push(line);
+        })?;
+
+        Ok(Gerber { commands, parse_warnings, source_lines })
+    }
+}
+
+impl From<Vec<Command>> for Gerber {
+    /// Wraps commands built or edited elsewhere
+    fn from(commands: Vec<Command>) -> Self {
+        Gerber { commands, parse_warnings: Vec::new(), source_lines: Vec::new() }
+    }
+}
+
+/// Finds the lines of positions in the parsed text, which come in increasing order
+///
+/// Only the text since the previous position is scanned, where the spans of
+/// pest would scan from the start of the file every time.
+struct LineCounter<'a> {
+    content: &'a str,
+    position: usize,
+    line: usize,
+}
+
+impl<'a> LineCounter<'a> {
+    fn new(content: &'a str) -> Self {
+        LineCounter { content, position: 0, line: 1 }
+    }
+
+    /// Line of a byte position, starting from 1
+    fn line_at(&mut self, position: usize) -> usize {
+        self.line += self.content[self.position..position].matches('\n').count();
+        self.position = position;
+        self.line
+    }
+}
+
+/// Locates an error of a statement parsed on its own in the whole text
+///
+/// # Arguments
+///
+/// * `error` - The error, with its line and column counted from the start of the statement
+/// * `content` - The whole text
+/// * `start` - Byte position of the statement in `content`
+fn relocate_error(error: GerberError, content: &str, start: usize) -> GerberError {
+    let GerberError::ParseError { line, column, source_line, message } = error else {
+        return error;
+    };
+
+    let line_start = content[..start].rfind('\n').map_or(0, |index| index + 1);
+    let start_line = content[..start].matches('\n').count() + 1;
+
+    if line > 1 {
+        return GerberError::ParseError { line: start_line + line - 1, column, source_line, message };
+    }
+
+    // The first line of the statement may start after other text of its line
+    GerberError::ParseError {
+        line: start_line,
+        column: content[line_start..start].chars().count() + column,
+        source_line: content[line_start..].lines().next().unwrap_or_default().to_string(),
+        message,
+    }
+}
+
+/// Removes the UTF-8 byte order mark some editors save files with, which the grammar doesn't expect
+fn strip_bom(content: &str) -> &str {
+    content.strip_prefix('\u{feff}').unwrap_or(content)
+}
+
+/// Warning for the deprecated codes of an FS statement, if it has any
+///
+/// # Arguments
+///
+/// * `index` - Index of the command the statement produces
+/// * `pair` - The statement
+fn format_warning(index: usize, pair: &Pair<Rule>) -> Option<GerberWarning> {
+    if pair.as_rule() != Rule::fs {
+        return None;
+    }
+
+    let codes: Vec<String> = pair.clone().into_inner()
+        .filter(|p| p.as_rule() == Rule::deprecated_code
+            || (p.as_rule() == Rule::zero_omission && p.as_str().eq_ignore_ascii_case("D")))
+        .map(|p| p.as_str().to_string())
+        .collect();
+
+    (!codes.is_empty()).then_some(GerberWarning::FormatCodesIgnored { index, codes })
+}
+
+/// Parses the code of an aperture, rejecting the codes below 10 that the spec reserves
+fn parse_aperture_code(ap_pair: &Pair<Rule>, command: &str) -> Result<u32, GerberError> {
+    let ap_str = ap_pair.as_str();
+    let code = ap_str.trim_start_matches('D').parse::<u32>()
+        .map_err(|_| locate_error(GerberError::SemanticError(
+            format!("Aperture code '{}' could not be parsed as an integer.", ap_str)
+        ), ap_pair.as_span()))?;
+
+    if code < 10 {
+        return Err(locate_error(GerberError::SemanticError(
+            format!("Aperture code '{}' in {} command is reserved, codes start at D10.", ap_str, command)
+        ), ap_pair.as_span()));
+    }
+
+    Ok(code)
+}
+
+/// Parses the parameters of a standard aperture template, naming the field in errors
+fn parse_template_parameters(pair: Pair<Rule>, template: &str, fields: &[&str]) -> Result<Vec<f64>, GerberError> {
+    pair.into_inner()
+        .zip(fields)
+        .map(|(parameter_pair, field)| parameter_pair.as_str().parse()
+            .map_err(|_| GerberError::SemanticError(
+                format!("{} {} could not be parsed as a number.", template, field)
+            )))
+        .collect()
+}
+
+// Expressions may end with the whitespace before their terminator, hence the trimming
 fn parse_bool(opt: Option<Pair<Rule>>) -> bool {
-    opt.map_or(false, |p| p.as_span().as_str().parse::<i32>().unwrap_or(0) != 0)
+    opt.map_or(false, |p| p.as_span().as_str().trim_end().parse::<i32>().unwrap_or(0) != 0)
 }
 
 fn parse_f64(opt: Option<Pair<Rule>>) -> f64 {
@@ -702,17 +1218,18 @@ fn parse_f64(opt: Option<Pair<Rule>>) -> f64 {
 }
 
 fn parse_f64_value(pair: Pair<Rule>) -> f64 {
-    pair.as_span().as_str().parse::<f64>().unwrap_or(0.0)
+    pair.as_span().as_str().trim_end().parse::<f64>().unwrap_or(0.0)
 }
 
 fn parse_u32(opt: Option<Pair<Rule>>) -> u32 {
-    opt.map_or(0, |p| p.as_span().as_str().parse::<u32>().unwrap_or(0))
+    opt.map_or(0, |p| p.as_span().as_str().trim_end().parse::<u32>().unwrap_or(0))
 }
 
 /// Core error types used throughout the library
 pub mod error {
     use std::fmt;
     pub(crate) use std::error::Error;
+    use pest::error::LineColLocation;
 
     /// Errors that can occur when parsing Gerber files
     #[derive(Debug)]
@@ -724,6 +1241,10 @@ pub mod error {
         ParseError {
             /// Line number where the error occurred
             line: usize,
+            /// Column where the error occurred, starting at 1
+            column: usize,
+            /// Text of the line where the error occurred, shown under the message
+            source_line: String,
             /// Description of the error
             message: String,
         },
@@ -736,14 +1257,73 @@ pub mod error {
         fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
             match self {
                 GerberError::IoError(err) => write!(f, "I/O error: {}", err),
-                GerberError::ParseError { line, message } => {
-                    write!(f, "Parse error at line {}: {}", line, message)
+                GerberError::ParseError { line, column, source_line, message } => {
+                    // Snippet of the line with a caret under the column, like rustc
+                    let gutter = " ".repeat(line.to_string().len());
+                    let indent: String = source_line.chars()
+                        .take(column.saturating_sub(1))
+                        .map(|c| if c == '\t' { '\t' } else { ' ' })
+                        .collect();
+
+                    writeln!(f, "Parse error at line {}: {}", line, message)?;
+                    write!(f, "{} |\n{} | {}\n{} | {}^", gutter, line, source_line, gutter, indent)
                 },
                 GerberError::SemanticError(msg) => write!(f, "Semantic error: {}", msg),
             }
         }
     }
 
+    /// Problems that don't make a Gerber file invalid but likely are mistakes
+    #[derive(Debug, PartialEq, Clone)]
+    pub enum GerberWarning {
+        /// Two dark regions overlap, identified by the index of their G36 commands
+        OverlappingRegions {
+            first: usize,
+            second: usize,
+        },
+        /// A flash kept its orientation when rotating the file, since its aperture can't be rotated
+        UnrotatedFlash {
+            index: usize,
+            aperture: u32,
+        },
+        /// Deprecated codes of an FS command were accepted without affecting the format
+        FormatCodesIgnored {
+            index: usize,
+            codes: Vec<String>,
+        },
+        /// Coordinates of an operation had more decimals than the format they were converted to holds
+        RoundedCoordinates {
+            index: usize,
+        },
+        /// A macro aperture kept the unit it was defined in when converting the file to another unit
+        UnconvertedMacro {
+            index: usize,
+            aperture: u32,
+        },
+    }
+
+    impl fmt::Display for GerberWarning {
+        fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+            match self {
+                GerberWarning::OverlappingRegions { first, second } => {
+                    write!(f, "Commands {} and {}: dark regions overlap.", first, second)
+                },
+                GerberWarning::UnrotatedFlash { index, aperture } => {
+                    write!(f, "Command {}: aperture D{} can't be rotated, the flash keeps its orientation.", index, aperture)
+                },
+                GerberWarning::FormatCodesIgnored { index, codes } => {
+                    write!(f, "Command {}: deprecated FS codes {} were ignored.", index, codes.join(", "))
+                },
+                GerberWarning::RoundedCoordinates { index } => {
+                    write!(f, "Command {}: coordinates were rounded to fit the new format.", index)
+                },
+                GerberWarning::UnconvertedMacro { index, aperture } => {
+                    write!(f, "Command {}: macro aperture D{} was left in the original unit.", index, aperture)
+                },
+            }
+        }
+    }
+
     impl Error for GerberError {
         fn source(&self) -> Option<&(dyn Error + 'static)> {
             match self {
@@ -758,4 +1338,20 @@ pub mod error {
             GerberError::IoError(err)
         }
     }
+
+    impl From<pest::error::Error<crate::Rule>> for GerberError {
+        fn from(err: pest::error::Error<crate::Rule>) -> Self {
+            let (line, column) = match err.line_col {
+                LineColLocation::Pos(position) => position,
+                LineColLocation::Span(start, _) => start,
+            };
+
+            GerberError::ParseError {
+                line,
+                column,
+                source_line: err.line().trim_end_matches(['\r', '\n']).to_string(),
+                message: err.variant.message().to_string(),
+            }
+        }
+    }
 }
\ No newline at end of file
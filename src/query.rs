@@ -0,0 +1,314 @@
+//! # Queries
+//!
+//! This module answers common questions about a parsed Gerber file, so
+//! consumers don't have to match on the commands themselves.
+
+use std::collections::{HashMap, HashSet};
+use crate::{Command, Gerber};
+use crate::command::{ApertureDefinition, ApertureTemplate, D03Operation, FormatSpecification, Unit};
+use crate::error::GerberError;
+use crate::geometry::{BoundsTracker, PlotState};
+use crate::transform::scale_template;
+
+/// Summary of a Gerber file, computed by [`Gerber::stats`]
+#[derive(Debug, PartialEq, Clone)]
+pub struct GerberStats {
+    /// Number of AD commands
+    pub apertures: usize,
+    /// Number of D03 operations
+    pub flashes: usize,
+    /// Number of straight D01 draws, outside regions
+    pub draws: usize,
+    /// Number of circular D01 draws, outside regions
+    pub arcs: usize,
+    /// Number of G36/G37 regions
+    pub regions: usize,
+    /// Unit set by MO, if any
+    pub unit: Option<Unit>,
+    /// Coordinate format set by FS, if any
+    pub format: Option<FormatSpecification>,
+    /// `(min_x, min_y, max_x, max_y)` as given by [`Gerber::bounding_box`]
+    pub bounding_box: Option<(f64, f64, f64, f64)>,
+}
+
+impl Gerber {
+    /// Builds the aperture dictionary, mapping each D code to its template
+    ///
+    /// Redefining an aperture is not allowed by the spec; if it happens the
+    /// last definition wins.
+    ///
+    /// # Returns
+    ///
+    /// * `HashMap<u32, ApertureTemplate>` - Template of every defined aperture, by code
+    pub fn aperture_map(&self) -> HashMap<u32, ApertureTemplate> {
+        self.commands.iter()
+            .filter_map(|command| match command {
+                Command::AD(aperture_def) => Some((aperture_def.code, aperture_def.template.clone())),
+                _ => None,
+            })
+            .collect()
+    }
+
+    /// Looks up the template of an aperture
+    ///
+    /// As with [`aperture_map`](Self::aperture_map), the last definition wins.
+    ///
+    /// # Arguments
+    ///
+    /// * `code` - The D code of the aperture
+    ///
+    /// # Returns
+    ///
+    /// * `Option<&ApertureTemplate>` - The template, or None if the aperture is not defined
+    pub fn aperture(&self, code: u32) -> Option<&ApertureTemplate> {
+        self.commands.iter().rev().find_map(|command| match command {
+            Command::AD(aperture_def) if aperture_def.code == code => Some(&aperture_def.template),
+            _ => None,
+        })
+    }
+
+    /// Looks up the coordinate format of the file
+    ///
+    /// The spec allows a single FS command; repeating it with the same format
+    /// is tolerated.
+    ///
+    /// # Returns
+    ///
+    /// * `Result<Option<&FormatSpecification>, GerberError>` - The format, None if the file has no FS command, or an error if FS commands disagree
+    pub fn format(&self) -> Result<Option<&FormatSpecification>, GerberError> {
+        let mut format = None;
+
+        for (index, command) in self.commands.iter().enumerate() {
+            let Command::FS(spec) = command else {
+                continue;
+            };
+
+            match format {
+                Some(first) if first != spec => {
+                    return Err(GerberError::SemanticError(
+                        format!("Command {}: FS command conflicts with the format set before.", index)
+                    ));
+                },
+                Some(_) => {},
+                None => format = Some(spec),
+            }
+        }
+
+        Ok(format)
+    }
+
+    /// Looks up the unit of the file
+    ///
+    /// As with [`format`](Self::format), repeating the MO command with the
+    /// same unit is tolerated.
+    ///
+    /// # Returns
+    ///
+    /// * `Result<Option<Unit>, GerberError>` - The unit, None if the file has no MO command, or an error if MO commands disagree
+    pub fn unit(&self) -> Result<Option<Unit>, GerberError> {
+        let mut unit = None;
+
+        for (index, command) in self.commands.iter().enumerate() {
+            let Command::MO(command_unit) = command else {
+                continue;
+            };
+
+            match unit {
+                Some(first) if first != *command_unit => {
+                    return Err(GerberError::SemanticError(
+                        format!("Command {}: MO command conflicts with the unit set before.", index)
+                    ));
+                },
+                Some(_) => {},
+                None => unit = Some(*command_unit),
+            }
+        }
+
+        Ok(unit)
+    }
+
+    /// Finds the line of the parsed text a command comes from
+    ///
+    /// Statements producing several commands, such as regions and blocks,
+    /// give all of them the line the statement starts on. Lines are only known
+    /// for commands that were parsed, and stay valid as long as no command is
+    /// inserted or removed before them.
+    ///
+    /// # Arguments
+    ///
+    /// * `command_index` - Index of the command in `commands`
+    ///
+    /// # Returns
+    ///
+    /// * `Option<usize>` - The line, starting from 1, or None if the command wasn't parsed
+    pub fn source_line(&self, command_index: usize) -> Option<usize> {
+        self.source_lines.get(command_index).copied()
+    }
+
+    /// Counts the flashes of each aperture
+    ///
+    /// Flashes before any aperture is selected are skipped; [`validate`](Self::validate)
+    /// reports them.
+    ///
+    /// # Returns
+    ///
+    /// * `HashMap<u32, usize>` - Number of D03 operations, by aperture code
+    pub fn flash_counts(&self) -> HashMap<u32, usize> {
+        let mut current_aperture = None;
+        let mut counts = HashMap::new();
+
+        for command in &self.commands {
+            match (command, current_aperture) {
+                (Command::Dnn(code), _) => current_aperture = Some(*code),
+                (Command::D03(_), Some(code)) => *counts.entry(code).or_insert(0) += 1,
+                _ => {}
+            }
+        }
+
+        counts
+    }
+
+    /// Iterates over the aperture definitions, in file order
+    ///
+    /// # Returns
+    ///
+    /// * `impl Iterator<Item = &ApertureDefinition>` - Every AD command
+    pub fn apertures(&self) -> impl Iterator<Item = &ApertureDefinition> {
+        self.commands.iter().filter_map(|command| match command {
+            Command::AD(aperture_def) => Some(aperture_def),
+            _ => None,
+        })
+    }
+
+    /// Lists the aperture definitions with their dimensions in millimeters
+    ///
+    /// Dimensions of files in inches are converted, those of files in millimeters
+    /// or without an MO command are kept. Macro parameters are left as they are,
+    /// since those that are lengths can't be told apart from the others.
+    ///
+    /// # Returns
+    ///
+    /// * `Vec<(u32, ApertureTemplate)>` - The code and converted template of every AD command, in file order
+    pub fn apertures_mm(&self) -> Vec<(u32, ApertureTemplate)> {
+        let inches = self.commands.iter().find_map(|command| match command {
+            Command::MO(unit) => Some(*unit),
+            _ => None,
+        }) == Some(Unit::Inches);
+
+        self.apertures()
+            .map(|aperture_def| {
+                let mut template = aperture_def.template.clone();
+                if inches {
+                    scale_template(&mut template, Unit::Inches.mm_per_unit());
+                }
+                (aperture_def.code, template)
+            })
+            .collect()
+    }
+
+    /// Finds the apertures that are defined but never selected
+    ///
+    /// A Dnn command only counts if it comes after the AD command. Apertures
+    /// selected without being defined are reported by [`validate`](Self::validate).
+    ///
+    /// # Returns
+    ///
+    /// * `Vec<u32>` - Codes of the unused apertures, in definition order
+    pub fn unused_apertures(&self) -> Vec<u32> {
+        let mut defined = Vec::new();
+        let mut selected = HashSet::new();
+
+        for command in &self.commands {
+            match command {
+                Command::AD(aperture_def) if !defined.contains(&aperture_def.code) => defined.push(aperture_def.code),
+                Command::Dnn(code) if defined.contains(code) => { selected.insert(*code); },
+                _ => {}
+            }
+        }
+
+        defined.retain(|code| !selected.contains(code));
+        defined
+    }
+
+    /// Iterates over the text of the comments, in file order
+    ///
+    /// Comments carrying attributes (`G04 #@! ...`) are parsed as attributes
+    /// and are not included.
+    ///
+    /// # Returns
+    ///
+    /// * `impl Iterator<Item = &str>` - The text of every G04 command
+    pub fn comments(&self) -> impl Iterator<Item = &str> {
+        self.commands.iter().filter_map(|command| match command {
+            Command::G04(comment) => Some(comment.as_str()),
+            _ => None,
+        })
+    }
+
+    /// Iterates over the flash operations, in file order
+    ///
+    /// # Returns
+    ///
+    /// * `impl Iterator<Item = &D03Operation>` - Every D03 command
+    pub fn flashes(&self) -> impl Iterator<Item = &D03Operation> {
+        self.commands.iter().filter_map(|command| match command {
+            Command::D03(operation) => Some(operation),
+            _ => None,
+        })
+    }
+
+    /// Summarizes the file in a single pass over the commands
+    ///
+    /// # Returns
+    ///
+    /// * `GerberStats` - Counts of the apertures and operations, with the unit, format and extents
+    pub fn stats(&self) -> GerberStats {
+        let mut stats = GerberStats {
+            apertures: 0,
+            flashes: 0,
+            draws: 0,
+            arcs: 0,
+            regions: 0,
+            unit: None,
+            format: None,
+            bounding_box: None,
+        };
+        let mut plot_state = PlotState::default();
+        let mut tracker = BoundsTracker::default();
+        let mut resolved = true;
+        let mut in_region = false;
+
+        for command in &self.commands {
+            let start = plot_state.current_point();
+            let end = match plot_state.apply(command) {
+                Ok(end) => end,
+                Err(_) => {
+                    resolved = false;
+                    None
+                },
+            };
+            tracker.update(command, &plot_state, start, end);
+
+            match command {
+                Command::AD(_) => stats.apertures += 1,
+                Command::MO(unit) => stats.unit = Some(*unit),
+                Command::FS(spec) => stats.format = Some(spec.clone()),
+                Command::G36 => {
+                    stats.regions += 1;
+                    in_region = true;
+                },
+                Command::G37 => in_region = false,
+                Command::D01(_) if !in_region => match plot_state.arc(command, start) {
+                    Some(_) => stats.arcs += 1,
+                    None => stats.draws += 1,
+                },
+                Command::D03(_) => stats.flashes += 1,
+                _ => {}
+            }
+        }
+
+        // Like the bounding box, extents are unknown if an operation can't be resolved
+        stats.bounding_box = tracker.bounds().filter(|_| resolved);
+        stats
+    }
+}
@@ -0,0 +1,431 @@
+//! # Rasterization
+//!
+//! This module fills the image described by a Gerber file into a grid of
+//! pixels, applying clear polarity by erasing what was drawn before.
+
+use std::f64::consts::{PI, TAU};
+use crate::{Command, Gerber};
+use crate::aperture_macro::thermal_polygons;
+use crate::command::{convert, AMPrimitive, ApertureTemplate, Mirroring, Polarity, Unit};
+use crate::geometry::{in_polygon, Arc, DrawPrimitive};
+use crate::state::GraphicsState;
+
+/// A coverage image of a Gerber file
+///
+/// Pixel `(0, 0)` is at the bottom left, so rows go up along the Y axis like
+/// Gerber coordinates do.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Raster {
+    /// Number of pixel columns
+    pub width: usize,
+    /// Number of pixel rows
+    pub height: usize,
+    /// Position of the bottom left corner of pixel `(0, 0)`, in the file unit
+    pub origin: (f64, f64),
+    /// Width and height of a pixel, in the file unit
+    pub pixel_size: f64,
+    pixels: Vec<bool>,
+}
+
+impl Raster {
+    /// Whether a pixel is covered
+    ///
+    /// # Returns
+    ///
+    /// * `bool` - True if the pixel is dark, false if it is clear or out of the image
+    pub fn pixel(&self, x: usize, y: usize) -> bool {
+        x < self.width && y < self.height && self.pixels[y * self.width + x]
+    }
+
+    /// Whether the pixel containing a point is covered
+    ///
+    /// # Arguments
+    ///
+    /// * `x`, `y` - The point, in the file unit
+    pub fn covers(&self, x: f64, y: f64) -> bool {
+        let column = ((x - self.origin.0) / self.pixel_size).floor();
+        let row = ((y - self.origin.1) / self.pixel_size).floor();
+
+        column >= 0.0 && row >= 0.0 && self.pixel(column as usize, row as usize)
+    }
+
+    /// Sets every pixel whose center lies in the shape to the given value
+    fn fill(&mut self, shape: &Shape, dark: bool) {
+        let (min_x, min_y, max_x, max_y) = shape.bounds();
+        let first_column = ((min_x - self.origin.0) / self.pixel_size).floor().max(0.0) as usize;
+        let first_row = ((min_y - self.origin.1) / self.pixel_size).floor().max(0.0) as usize;
+        let last_column = (((max_x - self.origin.0) / self.pixel_size).ceil().max(0.0) as usize).min(self.width);
+        let last_row = (((max_y - self.origin.1) / self.pixel_size).ceil().max(0.0) as usize).min(self.height);
+
+        for row in first_row..last_row {
+            for column in first_column..last_column {
+                let center = (
+                    self.origin.0 + (column as f64 + 0.5) * self.pixel_size,
+                    self.origin.1 + (row as f64 + 0.5) * self.pixel_size,
+                );
+                if shape.contains(center) {
+                    self.pixels[row * self.width + column] = dark;
+                }
+            }
+        }
+    }
+}
+
+impl Gerber {
+    /// Fills the image into a grid of pixels
+    ///
+    /// Draws are filled with the width of their aperture, flashes with the
+    /// shape of their aperture, including holes and LM, LR and LS
+    /// transformations, and regions with the even-odd rule. Objects with clear
+    /// polarity erase the pixels under them.
+    ///
+    /// # Arguments
+    ///
+    /// * `dpi` - Number of pixels per inch
+    ///
+    /// # Returns
+    ///
+    /// * `Raster` - The image, covering the bounding box of the file, or empty if nothing is drawn
+    pub fn rasterize(&self, dpi: f64) -> Raster {
+        let unit = self.commands.iter().find_map(|command| match command {
+            Command::MO(unit) => Some(*unit),
+            _ => None,
+        });
+        let pixel_size = convert(1.0 / dpi, Unit::Inches, unit.unwrap_or(Unit::Millimeters));
+
+        let (min_x, min_y, max_x, max_y) = self.bounding_box().unwrap_or((0.0, 0.0, 0.0, 0.0));
+        let width = ((max_x - min_x) / pixel_size).ceil() as usize;
+        let height = ((max_y - min_y) / pixel_size).ceil() as usize;
+
+        let mut raster = Raster {
+            width,
+            height,
+            origin: (min_x, min_y),
+            pixel_size,
+            pixels: vec![false; width * height],
+        };
+
+        let apertures = self.aperture_map();
+        let mut primitives = self.draw_primitives();
+
+        while let Some(primitive) = primitives.next() {
+            let state = primitives.state();
+            let dark = state.polarity == Polarity::Dark;
+
+            match primitive {
+                DrawPrimitive::Line { from, to, aperture } => {
+                    if let Some(template) = apertures.get(&aperture) {
+                        raster.fill(&line_shape(from, to, template), dark);
+                    }
+                },
+                DrawPrimitive::Arc { from, to, center, clockwise, aperture } => {
+                    let Some(template) = apertures.get(&aperture) else {
+                        continue;
+                    };
+                    let radius = draw_width(template) / 2.0;
+                    let arc = Gerber::arc_segment(from, to, center.0 - from.0, center.1 - from.1, clockwise, false);
+
+                    raster.fill(&Shape::ArcBand { arc, half_width: radius }, dark);
+                    raster.fill(&Shape::Circle { center: from, radius }, dark);
+                    raster.fill(&Shape::Circle { center: to, radius }, dark);
+                },
+                DrawPrimitive::Flash { at, aperture } => {
+                    if let Some(template) = apertures.get(&aperture) {
+                        let shapes = self.aperture_shapes(template);
+                        raster.fill(&Shape::Flash { at, shapes, hole: template.hole_diameter(), transform: Transform::from(state) }, dark);
+                    }
+                },
+                DrawPrimitive::RegionFill { contours, .. } => {
+                    raster.fill(&Shape::Polygons(contours), dark);
+                },
+            }
+        }
+
+        raster
+    }
+
+    /// Shapes of an aperture around its origin, each one darkening or clearing
+    fn aperture_shapes(&self, template: &ApertureTemplate) -> Vec<(Shape, bool)> {
+        match template {
+            ApertureTemplate::Circle(diameter, _) => vec![(Shape::Circle { center: (0.0, 0.0), radius: diameter / 2.0 }, true)],
+            ApertureTemplate::Rectangle(x, y, _) => vec![(Shape::Polygons(vec![rectangle((0.0, 0.0), *x, *y, 0.0)]), true)],
+            ApertureTemplate::Obround(x, y, _) => {
+                // A stadium: a line along the long side with round ends
+                let radius = x.min(*y) / 2.0;
+                let (half_x, half_y) = (x / 2.0 - radius, y / 2.0 - radius);
+                vec![(Shape::Capsule { from: (-half_x, -half_y), to: (half_x, half_y), radius }, true)]
+            },
+            ApertureTemplate::Polygon(diameter, vertices, rotation, _) => {
+                let points = regular_polygon((0.0, 0.0), diameter / 2.0, *vertices, rotation.unwrap_or(0.0));
+                vec![(Shape::Polygons(vec![points]), true)]
+            },
+            ApertureTemplate::Macro(name, params) => self.expand_macro(name, params)
+                .unwrap_or_default()
+                .iter()
+                .filter_map(macro_shape)
+                .collect(),
+        }
+    }
+}
+
+/// Mirroring, rotation and scaling of a flashed aperture
+#[derive(Debug, Clone, Copy)]
+struct Transform {
+    mirror_x: bool,
+    mirror_y: bool,
+    rotation: f64,
+    scaling: f64,
+}
+
+impl From<&GraphicsState> for Transform {
+    fn from(state: &GraphicsState) -> Self {
+        Transform {
+            mirror_x: matches!(state.mirroring, Mirroring::X | Mirroring::XY),
+            mirror_y: matches!(state.mirroring, Mirroring::Y | Mirroring::XY),
+            rotation: state.rotation.to_radians(),
+            scaling: state.scaling,
+        }
+    }
+}
+
+impl Transform {
+    /// Maps a point of the image back into the aperture, undoing the scaling,
+    /// rotation and mirroring applied to it in that order
+    fn inverse(&self, (x, y): (f64, f64)) -> (f64, f64) {
+        let (x, y) = (x / self.scaling, y / self.scaling);
+        let (sin, cos) = (-self.rotation).sin_cos();
+        let (x, y) = (x * cos - y * sin, x * sin + y * cos);
+
+        (if self.mirror_x { -x } else { x }, if self.mirror_y { -y } else { y })
+    }
+}
+
+/// Area filled by a drawing primitive
+#[derive(Debug, Clone)]
+enum Shape {
+    Circle { center: (f64, f64), radius: f64 },
+    /// A line with round ends
+    Capsule { from: (f64, f64), to: (f64, f64), radius: f64 },
+    /// The band covered by an arc drawn with the given half width, without its ends
+    ArcBand { arc: Arc, half_width: f64 },
+    /// Polygons combined with the even-odd rule
+    Polygons(Vec<Vec<(f64, f64)>>),
+    /// A flashed aperture, made of layered shapes around its origin
+    Flash { at: (f64, f64), shapes: Vec<(Shape, bool)>, hole: Option<f64>, transform: Transform },
+}
+
+impl Shape {
+    fn contains(&self, (x, y): (f64, f64)) -> bool {
+        match self {
+            Shape::Circle { center, radius } => (x - center.0).hypot(y - center.1) <= *radius,
+            Shape::Capsule { from, to, radius } => segment_distance((x, y), *from, *to) <= *radius,
+            Shape::ArcBand { arc, half_width } => {
+                let distance = (x - arc.center.0).hypot(y - arc.center.1);
+                let angle = (y - arc.center.1).atan2(x - arc.center.0);
+                let along = if arc.sweep() >= 0.0 {
+                    (angle - arc.start_angle).rem_euclid(TAU)
+                } else {
+                    (arc.start_angle - angle).rem_euclid(TAU)
+                };
+
+                (distance - arc.radius).abs() <= *half_width && along <= arc.sweep().abs()
+            },
+            Shape::Polygons(polygons) => polygons.iter()
+                .filter(|points| in_polygon((x, y), points))
+                .count() % 2 == 1,
+            Shape::Flash { at, shapes, hole, transform } => {
+                let local = transform.inverse((x - at.0, y - at.1));
+
+                layers_cover(shapes, local) && !hole.is_some_and(|diameter| local.0.hypot(local.1) <= diameter / 2.0)
+            },
+        }
+    }
+
+    /// `(min_x, min_y, max_x, max_y)` of the area the shape may cover
+    fn bounds(&self) -> (f64, f64, f64, f64) {
+        match self {
+            Shape::Circle { center, radius } => (center.0 - radius, center.1 - radius, center.0 + radius, center.1 + radius),
+            Shape::Capsule { from, to, radius } => (
+                from.0.min(to.0) - radius, from.1.min(to.1) - radius,
+                from.0.max(to.0) + radius, from.1.max(to.1) + radius,
+            ),
+            Shape::ArcBand { arc, half_width } => {
+                let reach = arc.radius + half_width;
+                (arc.center.0 - reach, arc.center.1 - reach, arc.center.0 + reach, arc.center.1 + reach)
+            },
+            Shape::Polygons(polygons) => polygons.iter().flatten().fold(
+                (f64::INFINITY, f64::INFINITY, f64::NEG_INFINITY, f64::NEG_INFINITY),
+                |(min_x, min_y, max_x, max_y), (x, y)| (min_x.min(*x), min_y.min(*y), max_x.max(*x), max_y.max(*y)),
+            ),
+            Shape::Flash { at, shapes, transform, .. } => {
+                let reach = layers_reach(shapes) * transform.scaling.abs();
+                (at.0 - reach, at.1 - reach, at.0 + reach, at.1 + reach)
+            },
+        }
+    }
+}
+
+/// Coverage of a macro aperture around its origin
+///
+/// The primitives are layered in order, so one with exposure off clears what
+/// the ones before it exposed, without affecting anything outside the aperture.
+#[cfg(feature = "visualizer")]
+pub(crate) struct MacroCoverage {
+    shapes: Vec<(Shape, bool)>,
+}
+
+#[cfg(feature = "visualizer")]
+impl MacroCoverage {
+    /// Builds the coverage of concrete macro primitives, as expanded from the macro body
+    pub(crate) fn new(primitives: &[AMPrimitive]) -> Self {
+        MacroCoverage { shapes: primitives.iter().filter_map(macro_shape).collect() }
+    }
+
+    /// Whether the aperture is exposed at a point, relative to its origin
+    pub(crate) fn covers(&self, point: (f64, f64)) -> bool {
+        layers_cover(&self.shapes, point)
+    }
+
+    /// Distance from the origin that the aperture may cover, whatever its rotation
+    pub(crate) fn reach(&self) -> f64 {
+        layers_reach(&self.shapes)
+    }
+}
+
+/// Whether the last of the layered shapes containing the point is exposed
+fn layers_cover(shapes: &[(Shape, bool)], point: (f64, f64)) -> bool {
+    shapes.iter().fold(false, |covered, (shape, exposure)| {
+        if shape.contains(point) { *exposure } else { covered }
+    })
+}
+
+/// Distance from the origin that layered shapes may cover once rotated
+fn layers_reach(shapes: &[(Shape, bool)]) -> f64 {
+    // Rotation may swing any corner of the aperture round its origin
+    shapes.iter()
+        .map(|(shape, _)| {
+            let (min_x, min_y, max_x, max_y) = shape.bounds();
+            min_x.abs().max(min_y.abs()).max(max_x.abs()).max(max_y.abs()) * std::f64::consts::SQRT_2
+        })
+        .fold(0.0, f64::max)
+}
+
+/// Shape covered by a straight draw
+fn line_shape(from: (f64, f64), to: (f64, f64), template: &ApertureTemplate) -> Shape {
+    match template {
+        ApertureTemplate::Rectangle(x, y, _) => {
+            // The rectangle swept along the line covers the hull of its corners at both ends
+            let corners: Vec<(f64, f64)> = [from, to].iter()
+                .flat_map(|end| rectangle(*end, *x, *y, 0.0))
+                .collect();
+            Shape::Polygons(vec![convex_hull(corners)])
+        },
+        template => Shape::Capsule { from, to, radius: draw_width(template) / 2.0 },
+    }
+}
+
+/// Width of the stroke drawn with an aperture
+///
+/// Only circles are valid for draws; other shapes use their narrowest width.
+fn draw_width(template: &ApertureTemplate) -> f64 {
+    match template {
+        ApertureTemplate::Circle(diameter, _) | ApertureTemplate::Polygon(diameter, ..) => *diameter,
+        ApertureTemplate::Rectangle(x, y, _) | ApertureTemplate::Obround(x, y, _) => x.min(*y),
+        ApertureTemplate::Macro(..) => 0.0,
+    }
+}
+
+/// Shape of a concrete macro primitive, with whether it is exposed
+fn macro_shape(primitive: &AMPrimitive) -> Option<(Shape, bool)> {
+    let shape = match primitive {
+        AMPrimitive::Circle(on, diameter, center_x, center_y, rotation) => (
+            Shape::Circle { center: rotate((*center_x, *center_y), rotation.unwrap_or(0.0)), radius: diameter / 2.0 },
+            *on,
+        ),
+        AMPrimitive::VectorLine(on, width, start_x, start_y, end_x, end_y, rotation) => {
+            let (dx, dy) = (end_x - start_x, end_y - start_y);
+            let length = dx.hypot(dy);
+            let center = ((start_x + end_x) / 2.0, (start_y + end_y) / 2.0);
+            let points = rectangle(center, length, *width, dy.atan2(dx).to_degrees())
+                .into_iter()
+                .map(|point| rotate(point, *rotation))
+                .collect();
+            (Shape::Polygons(vec![points]), *on)
+        },
+        AMPrimitive::CenterLine(on, width, height, center_x, center_y, rotation) => {
+            let points = rectangle((*center_x, *center_y), *width, *height, 0.0)
+                .into_iter()
+                .map(|point| rotate(point, *rotation))
+                .collect();
+            (Shape::Polygons(vec![points]), *on)
+        },
+        AMPrimitive::Outline(on, ..) | AMPrimitive::Polygon(on, ..) => (Shape::Polygons(vec![primitive.as_polygon()?]), *on),
+        AMPrimitive::Thermal(center_x, center_y, outer_diameter, inner_diameter, gap, rotation) => (
+            Shape::Polygons(thermal_polygons((*center_x, *center_y), *outer_diameter, *inner_diameter, *gap, *rotation)),
+            true,
+        ),
+        _ => return None,
+    };
+
+    Some(shape)
+}
+
+/// Rotates a point counter-clockwise about the origin by an angle in degrees
+fn rotate((x, y): (f64, f64), degrees: f64) -> (f64, f64) {
+    let (sin, cos) = degrees.to_radians().sin_cos();
+    (x * cos - y * sin, x * sin + y * cos)
+}
+
+/// Corners of a rectangle rotated about its center, counter-clockwise
+fn rectangle(center: (f64, f64), width: f64, height: f64, degrees: f64) -> Vec<(f64, f64)> {
+    [(-1.0, -1.0), (1.0, -1.0), (1.0, 1.0), (-1.0, 1.0)].iter()
+        .map(|(sign_x, sign_y)| {
+            let (x, y) = rotate((sign_x * width / 2.0, sign_y * height / 2.0), degrees);
+            (center.0 + x, center.1 + y)
+        })
+        .collect()
+}
+
+/// Vertices of a regular polygon, the first one at the given angle in degrees
+fn regular_polygon(center: (f64, f64), radius: f64, vertices: u32, degrees: f64) -> Vec<(f64, f64)> {
+    (0..vertices)
+        .map(|index| {
+            let angle = degrees.to_radians() + 2.0 * PI * index as f64 / vertices as f64;
+            (center.0 + radius * angle.cos(), center.1 + radius * angle.sin())
+        })
+        .collect()
+}
+
+/// Convex hull of points, by the monotone chain algorithm
+fn convex_hull(mut points: Vec<(f64, f64)>) -> Vec<(f64, f64)> {
+    points.sort_by(|a, b| a.0.total_cmp(&b.0).then(a.1.total_cmp(&b.1)));
+    let cross = |o: (f64, f64), a: (f64, f64), b: (f64, f64)| (a.0 - o.0) * (b.1 - o.1) - (a.1 - o.1) * (b.0 - o.0);
+
+    let mut hull: Vec<(f64, f64)> = Vec::new();
+    for pass in 0..2 {
+        let start = hull.len();
+        for point in if pass == 0 { points.clone() } else { points.iter().rev().copied().collect() } {
+            while hull.len() >= start + 2 && cross(hull[hull.len() - 2], hull[hull.len() - 1], point) <= 0.0 {
+                hull.pop();
+            }
+            hull.push(point);
+        }
+        // The last point starts the other half
+        hull.pop();
+    }
+
+    hull
+}
+
+/// Distance from a point to a line segment
+fn segment_distance(point: (f64, f64), from: (f64, f64), to: (f64, f64)) -> f64 {
+    let (dx, dy) = (to.0 - from.0, to.1 - from.1);
+    let length_squared = dx * dx + dy * dy;
+    let t = if length_squared == 0.0 {
+        0.0
+    } else {
+        (((point.0 - from.0) * dx + (point.1 - from.1) * dy) / length_squared).clamp(0.0, 1.0)
+    };
+
+    (point.0 - from.0 - t * dx).hypot(point.1 - from.1 - t * dy)
+}
@@ -0,0 +1,217 @@
+//! # Graphics State
+//!
+//! This module tracks the graphics state and the attribute dictionary that
+//! commands set for the operations following them, so consumers don't have to
+//! scan back through the commands.
+
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+use crate::{Command, Gerber};
+use crate::command::{Mirroring, Operation, Polarity};
+
+/// The graphics state in effect for an operation
+#[derive(Debug, PartialEq, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct GraphicsState {
+    /// Polarity set by LP, dark by default
+    pub polarity: Polarity,
+    /// Mirroring set by LM, none by default
+    pub mirroring: Mirroring,
+    /// Rotation in degrees set by LR, 0 by default
+    pub rotation: f64,
+    /// Scale factor set by LS, 1 by default
+    pub scaling: f64,
+    /// Aperture selected by the last Dnn command, if any
+    pub aperture: Option<u32>,
+}
+
+impl Default for GraphicsState {
+    fn default() -> Self {
+        GraphicsState {
+            polarity: Polarity::Dark,
+            mirroring: Mirroring::None,
+            rotation: 0.0,
+            scaling: 1.0,
+            aperture: None,
+        }
+    }
+}
+
+impl GraphicsState {
+    /// Updates the state with a command
+    ///
+    /// # Arguments
+    ///
+    /// * `command` - The command to apply, commands not setting the state are ignored
+    pub fn apply(&mut self, command: &Command) {
+        match command {
+            Command::LP(polarity) => self.polarity = *polarity,
+            Command::LM(mirroring) => self.mirroring = *mirroring,
+            Command::LR(rotation) => self.rotation = *rotation,
+            Command::LS(scaling) => self.scaling = *scaling,
+            Command::Dnn(code) => self.aperture = Some(*code),
+            _ => {}
+        }
+    }
+}
+
+/// The attributes in effect at a point of the file, by name
+///
+/// Each attribute maps to its values, in the order they were written.
+#[derive(Debug, PartialEq, Clone, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct AttributeDictionary {
+    /// File attributes set by TF
+    pub file: HashMap<String, Vec<String>>,
+    /// Aperture attributes set by TA, attached to the apertures defined next
+    pub aperture: HashMap<String, Vec<String>>,
+    /// Object attributes set by TO, attached to the objects created next
+    pub object: HashMap<String, Vec<String>>,
+}
+
+impl AttributeDictionary {
+    /// Updates the dictionary with a command
+    ///
+    /// TD deletes aperture and object attributes only, file attributes stay
+    /// for the whole file.
+    ///
+    /// # Arguments
+    ///
+    /// * `command` - The command to apply, commands other than TF, TA, TO and TD are ignored
+    pub fn apply(&mut self, command: &Command) {
+        match command {
+            Command::TF(name, values) => { self.file.insert(name.clone(), values.clone()); },
+            Command::TA(name, values) => { self.aperture.insert(name.clone(), values.clone()); },
+            Command::TO(name, values) => { self.object.insert(name.clone(), values.clone()); },
+            Command::TD(Some(name)) => {
+                self.aperture.remove(name);
+                self.object.remove(name);
+            },
+            Command::TD(None) => {
+                self.aperture.clear();
+                self.object.clear();
+            },
+            _ => {}
+        }
+    }
+
+    /// The net of the objects created next, from the `.N` object attribute
+    ///
+    /// # Returns
+    ///
+    /// * `Option<&str>` - The first net name, or None if no net is set
+    pub fn net(&self) -> Option<&str> {
+        self.object.get(".N")?.first().map(String::as_str)
+    }
+}
+
+impl Gerber {
+    /// Replays the attribute commands to find the attributes in effect for a command
+    ///
+    /// # Arguments
+    ///
+    /// * `index` - Index of the command in `commands`; only the commands before it are replayed
+    ///
+    /// # Returns
+    ///
+    /// * `AttributeDictionary` - The file, aperture and object attributes in effect
+    pub fn attribute_state_at(&self, index: usize) -> AttributeDictionary {
+        let mut attributes = AttributeDictionary::default();
+
+        for command in self.commands.iter().take(index) {
+            attributes.apply(command);
+        }
+
+        attributes
+    }
+
+    /// Lists the nets named by `.N` object attributes
+    ///
+    /// Empty names and `N/C`, which mark objects not connected to any net, are
+    /// left out.
+    ///
+    /// # Returns
+    ///
+    /// * `BTreeSet<String>` - The distinct net names, sorted
+    pub fn nets(&self) -> BTreeSet<String> {
+        self.commands.iter()
+            .filter_map(|command| match command {
+                Command::TO(name, values) if name == ".N" => Some(values),
+                _ => None,
+            })
+            .flatten()
+            .filter(|net| is_named_net(net))
+            .cloned()
+            .collect()
+    }
+
+    /// Finds the objects belonging to each net, replaying the attribute commands
+    ///
+    /// Objects are draws and flashes, identified by the index of their D01 or
+    /// D03 command, and regions, identified by the index of their G36 command.
+    /// Objects with several nets are listed under each of them.
+    ///
+    /// # Returns
+    ///
+    /// * `BTreeMap<String, Vec<usize>>` - The command indices of the objects of every net named as in [`nets`](Self::nets)
+    pub fn net_objects(&self) -> BTreeMap<String, Vec<usize>> {
+        let mut attributes = AttributeDictionary::default();
+        let mut in_region = false;
+        let mut objects: BTreeMap<String, Vec<usize>> = BTreeMap::new();
+
+        for (index, command) in self.commands.iter().enumerate() {
+            attributes.apply(command);
+
+            let creates_object = match command {
+                Command::G36 => {
+                    in_region = true;
+                    true
+                },
+                Command::G37 => {
+                    in_region = false;
+                    false
+                },
+                Command::D01(_) | Command::D03(_) => !in_region,
+                _ => false,
+            };
+
+            if let (true, Some(nets)) = (creates_object, attributes.object.get(".N")) {
+                for net in nets.iter().filter(|net| is_named_net(net)) {
+                    objects.entry(net.clone()).or_default().push(index);
+                }
+            }
+        }
+
+        objects
+    }
+
+    /// Pairs every operation with the graphics state it executes in
+    ///
+    /// # Returns
+    ///
+    /// * `Vec<(Operation, GraphicsState)>` - The D01, D02 and D03 operations in order, with their state
+    pub fn operations_with_state(&self) -> Vec<(Operation, GraphicsState)> {
+        let mut state = GraphicsState::default();
+        let mut operations = Vec::new();
+
+        for command in &self.commands {
+            let operation = match command {
+                Command::D01(op) => Operation::D01(*op),
+                Command::D02(op) => Operation::D02(*op),
+                Command::D03(op) => Operation::D03(*op),
+                command => {
+                    state.apply(command);
+                    continue;
+                },
+            };
+
+            operations.push((operation, state.clone()));
+        }
+
+        operations
+    }
+}
+
+/// True for net names other than the empty name and `N/C`, used for unconnected objects
+fn is_named_net(net: &str) -> bool {
+    !net.is_empty() && net != "N/C"
+}
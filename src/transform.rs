@@ -0,0 +1,695 @@
+//! # Transformations
+//!
+//! This module rewrites the commands of a Gerber file, moving or converting
+//! the image they describe, or combining it with the image of another file.
+
+use std::collections::{HashMap, HashSet};
+use crate::{Command, Gerber};
+use crate::command::{convert, ApertureDefinition, ApertureTemplate, FormatSpecification, Mirroring, Notation, Polarity, Unit};
+use crate::error::{GerberError, GerberWarning};
+use crate::geometry::{InterpolationMode, PlotState};
+use crate::state::GraphicsState;
+
+impl Gerber {
+    /// Copies the commands with both coordinates of every operation written out
+    ///
+    /// Coordinates left out of D01, D02 and D03 operations reuse those of the
+    /// current point, so they are filled in with it. In incremental notation
+    /// they are filled in with 0, which keeps the current point too. The values
+    /// stay integers in the format of the file, so the commands still serialize.
+    ///
+    /// # Returns
+    ///
+    /// * `Vec<Command>` - The commands, with `x` and `y` set on every operation
+    pub fn explicit_operations(&self) -> Vec<Command> {
+        let mut incremental = false;
+        // Current point, as written in the file
+        let (mut current_x, mut current_y) = (0, 0);
+
+        self.commands.iter()
+            .map(|command| {
+                let mut command = command.clone();
+                let (x, y) = match &mut command {
+                    Command::FS(spec) => {
+                        incremental = spec.notation == Notation::Incremental;
+                        return command;
+                    },
+                    Command::G90 | Command::G91 => {
+                        incremental = command == Command::G91;
+                        return command;
+                    },
+                    Command::D01(op) => (&mut op.x, &mut op.y),
+                    Command::D02(op) => (&mut op.x, &mut op.y),
+                    Command::D03(op) => (&mut op.x, &mut op.y),
+                    _ => return command,
+                };
+
+                if incremental {
+                    x.get_or_insert(0);
+                    y.get_or_insert(0);
+                } else {
+                    current_x = *x.get_or_insert(current_x);
+                    current_y = *y.get_or_insert(current_y);
+                }
+
+                command
+            })
+            .collect()
+    }
+
+    /// Converts the file to another unit
+    ///
+    /// The MO command is rewritten and coordinates, standard aperture dimensions
+    /// and step and repeat distances are scaled. The coordinate format gains the
+    /// digits needed to hold the converted values: two integer digits when going
+    /// to millimeters, two decimal digits when going to inches.
+    ///
+    /// Macro apertures are left as they are, since their parameters can't be told
+    /// apart from ones that are not lengths. Nothing is done if the file doesn't
+    /// declare its unit.
+    ///
+    /// # Arguments
+    ///
+    /// * `target` - The unit to convert to
+    ///
+    /// # Returns
+    ///
+    /// * `Vec<GerberWarning>` - The macro apertures that were left in the original unit
+    pub fn to_units(&mut self, target: Unit) -> Vec<GerberWarning> {
+        let source = self.commands.iter().find_map(|command| match command {
+            Command::MO(unit) => Some(*unit),
+            _ => None,
+        });
+        let factor = match source {
+            Some(source) if source != target => convert(1.0, source, target),
+            _ => return Vec::new(),
+        };
+
+        // Original and converted format, to rescale the integer coordinates
+        let mut formats: Option<(FormatSpecification, FormatSpecification)> = None;
+        let mut warnings = Vec::new();
+
+        for (index, command) in self.commands.iter_mut().enumerate() {
+            match command {
+                Command::MO(unit) => *unit = target,
+                Command::FS(spec) => {
+                    let original = spec.clone();
+                    match target {
+                        Unit::Millimeters => {
+                            spec.x_integer_digits = (spec.x_integer_digits + 2).min(6);
+                            spec.y_integer_digits = (spec.y_integer_digits + 2).min(6);
+                        },
+                        Unit::Inches => {
+                            spec.x_integer_digits = spec.x_integer_digits.saturating_sub(1).max(1);
+                            spec.y_integer_digits = spec.y_integer_digits.saturating_sub(1).max(1);
+                            spec.x_decimal_digits = (spec.x_decimal_digits + 2).min(6);
+                            spec.y_decimal_digits = (spec.y_decimal_digits + 2).min(6);
+                        },
+                    }
+                    formats = Some((original, spec.clone()));
+                },
+                Command::AD(aperture_def) => {
+                    if matches!(aperture_def.template, ApertureTemplate::Macro(..)) {
+                        warnings.push(GerberWarning::UnconvertedMacro { index, aperture: aperture_def.code });
+                    }
+                    scale_template(&mut aperture_def.template, factor);
+                },
+                Command::SR(Some(step_and_repeat)) => {
+                    step_and_repeat.x_step = scale(step_and_repeat.x_step, factor);
+                    step_and_repeat.y_step = scale(step_and_repeat.y_step, factor);
+                },
+                Command::D01(op) => {
+                    if let Some((from, to)) = &formats {
+                        op.x = op.x.map(|x| scale_coordinate(x, from.x_decimal_digits, to.x_decimal_digits, factor));
+                        op.y = op.y.map(|y| scale_coordinate(y, from.y_decimal_digits, to.y_decimal_digits, factor));
+                        op.i = op.i.map(|i| scale_coordinate(i, from.x_decimal_digits, to.x_decimal_digits, factor));
+                        op.j = op.j.map(|j| scale_coordinate(j, from.y_decimal_digits, to.y_decimal_digits, factor));
+                    }
+                },
+                Command::D02(op) => {
+                    if let Some((from, to)) = &formats {
+                        op.x = op.x.map(|x| scale_coordinate(x, from.x_decimal_digits, to.x_decimal_digits, factor));
+                        op.y = op.y.map(|y| scale_coordinate(y, from.y_decimal_digits, to.y_decimal_digits, factor));
+                    }
+                },
+                Command::D03(op) => {
+                    if let Some((from, to)) = &formats {
+                        op.x = op.x.map(|x| scale_coordinate(x, from.x_decimal_digits, to.x_decimal_digits, factor));
+                        op.y = op.y.map(|y| scale_coordinate(y, from.y_decimal_digits, to.y_decimal_digits, factor));
+                    }
+                },
+                _ => {}
+            }
+        }
+
+        warnings
+    }
+
+    /// Re-expresses every coordinate in another coordinate format
+    ///
+    /// The FS commands are replaced and the integer values of the coordinates
+    /// and arc offsets are rescaled to the new decimal digits, so the image
+    /// stays the same. Coordinates keep the notation they are written in, so
+    /// the notation of the new format is not used.
+    ///
+    /// # Arguments
+    ///
+    /// * `new_format` - The format to write coordinates in
+    ///
+    /// # Returns
+    ///
+    /// * `Vec<GerberWarning>` - The operations whose coordinates had to be rounded, having more decimals than the new format holds
+    pub fn reformat(&mut self, new_format: FormatSpecification) -> Vec<GerberWarning> {
+        let mut warnings = Vec::new();
+        // Original format of the coordinates that follow
+        let mut original: Option<FormatSpecification> = None;
+
+        for (index, command) in self.commands.iter_mut().enumerate() {
+            let (coordinates, offsets) = match command {
+                Command::FS(spec) => {
+                    original = Some(spec.clone());
+                    *spec = FormatSpecification { notation: spec.notation.clone(), ..new_format.clone() };
+                    continue;
+                },
+                Command::D01(op) => ([&mut op.x, &mut op.y], Some([&mut op.i, &mut op.j])),
+                Command::D02(op) => ([&mut op.x, &mut op.y], None),
+                Command::D03(op) => ([&mut op.x, &mut op.y], None),
+                _ => continue,
+            };
+            let Some(from) = &original else {
+                continue;
+            };
+
+            let digits = [
+                (from.x_decimal_digits, new_format.x_decimal_digits),
+                (from.y_decimal_digits, new_format.y_decimal_digits),
+            ];
+            let mut rounded = false;
+
+            for values in std::iter::once(coordinates).chain(offsets) {
+                for (value, (from_digits, to_digits)) in values.into_iter().zip(digits) {
+                    if let Some(value) = value {
+                        let rescaled = scale_coordinate(*value, from_digits, to_digits, 1.0);
+                        rounded |= scale_coordinate(rescaled, to_digits, from_digits, 1.0) != *value;
+                        *value = rescaled;
+                    }
+                }
+            }
+
+            if rounded {
+                warnings.push(GerberWarning::RoundedCoordinates { index });
+            }
+        }
+
+        warnings
+    }
+
+    /// Moves the whole image by an offset
+    ///
+    /// Every coordinate of the D01, D02 and D03 operations is moved, so flashes,
+    /// draws, arcs and region contours all move together. The I and J offsets of
+    /// arcs are relative to their start point and are left as they are, and so
+    /// are step and repeat blocks, whose copies follow the operations inside them.
+    ///
+    /// In incremental notation only the first coordinate of each axis is moved,
+    /// since every later one is relative to it. A coordinate left out before the
+    /// first one given is the origin, so it is written out with the offset.
+    /// Nothing is done if the file doesn't declare its format.
+    ///
+    /// # Arguments
+    ///
+    /// * `dx` - Offset along X, in the file unit
+    /// * `dy` - Offset along Y, in the file unit
+    pub fn translate(&mut self, dx: f64, dy: f64) {
+        // Offsets in the integer coordinates of the file, once its format is known
+        let mut offset: Option<(i64, i64)> = None;
+        let mut incremental = false;
+        let mut first = true;
+
+        for command in &mut self.commands {
+            let (x, y) = match command {
+                Command::FS(spec) => {
+                    incremental = spec.notation == Notation::Incremental;
+                    offset = Some((
+                        (dx * 10f64.powi(spec.x_decimal_digits as i32)).round() as i64,
+                        (dy * 10f64.powi(spec.y_decimal_digits as i32)).round() as i64,
+                    ));
+                    continue;
+                },
+                Command::G90 | Command::G91 => {
+                    incremental = *command == Command::G91;
+                    continue;
+                },
+                Command::D01(op) => (&mut op.x, &mut op.y),
+                Command::D02(op) => (&mut op.x, &mut op.y),
+                Command::D03(op) => (&mut op.x, &mut op.y),
+                _ => continue,
+            };
+            let Some((offset_x, offset_y)) = offset else { continue };
+
+            if first || !incremental {
+                for (value, offset) in [(x, offset_x), (y, offset_y)] {
+                    match value {
+                        Some(coordinate) => *coordinate += offset,
+                        None if first => *value = Some(offset),
+                        None => {},
+                    }
+                }
+            }
+
+            first = false;
+        }
+    }
+
+    /// Rotates the whole image about a point
+    ///
+    /// Unlike the LR command, which only rotates apertures, this moves the
+    /// coordinates themselves. Every operation gets both coordinates written out,
+    /// and the I and J offsets of arcs are rotated with them. The coordinates
+    /// stay in the notation of the file.
+    ///
+    /// Circles need no change and polygons get their rotation parameter updated.
+    /// Rectangles and obrounds can only be turned by swapping their sides, so for
+    /// angles other than quarter turns their flashes keep their orientation, as
+    /// do flashes of macro apertures, and a warning is returned for each of them.
+    ///
+    /// # Arguments
+    ///
+    /// * `degrees` - The angle, counter-clockwise
+    /// * `about` - The center of rotation, in the file unit
+    ///
+    /// # Returns
+    ///
+    /// * `Vec<GerberWarning>` - The flashes whose aperture could not be rotated
+    pub fn rotate(&mut self, degrees: f64, about: (f64, f64)) -> Vec<GerberWarning> {
+        let (sin, cos) = degrees.to_radians().sin_cos();
+        let turn = |(x, y): (f64, f64)| (x * cos - y * sin, x * sin + y * cos);
+        let quarter_turns = degrees / 90.0;
+        let swap_sides = quarter_turns.fract() == 0.0 && quarter_turns.rem_euclid(2.0) == 1.0;
+        let rotates_apertures = quarter_turns.fract() == 0.0;
+
+        let mut plot_state = PlotState::default();
+        let mut format_spec: Option<FormatSpecification> = None;
+        let mut incremental = false;
+        let mut single_quadrant = false;
+        // Rotated current point, as written in the file, to compute incremental coordinates
+        let mut written = (0, 0);
+        let mut unrotated_apertures = HashSet::new();
+        let mut aperture = None;
+        let mut warnings = Vec::new();
+
+        for (index, command) in self.commands.iter_mut().enumerate() {
+            let start = plot_state.current_point();
+            let end = match plot_state.apply(command) {
+                Ok(end) => end,
+                Err(_) => continue,
+            };
+            let arc = plot_state.arc(command, start);
+
+            match command {
+                Command::FS(spec) => {
+                    incremental = spec.notation == Notation::Incremental;
+                    format_spec = Some(spec.clone());
+                },
+                Command::G90 | Command::G91 => incremental = *command == Command::G91,
+                Command::G74 | Command::G75 => single_quadrant = *command == Command::G74,
+                Command::Dnn(code) => aperture = Some(*code),
+                Command::AD(aperture_def) => match &mut aperture_def.template {
+                    ApertureTemplate::Circle(_, _) => {},
+                    ApertureTemplate::Polygon(_, _, rotation, _) => {
+                        *rotation = Some((rotation.unwrap_or(0.0) + degrees).rem_euclid(360.0));
+                    },
+                    ApertureTemplate::Rectangle(x, y, _) | ApertureTemplate::Obround(x, y, _) if rotates_apertures => {
+                        if swap_sides {
+                            std::mem::swap(x, y);
+                        }
+                    },
+                    _ if degrees.rem_euclid(360.0) == 0.0 => {},
+                    _ => { unrotated_apertures.insert(aperture_def.code); },
+                },
+                _ => {},
+            }
+
+            let (Some(spec), Some(end)) = (&format_spec, end) else {
+                continue;
+            };
+            let x_scale = 10f64.powi(spec.x_decimal_digits as i32);
+            let y_scale = 10f64.powi(spec.y_decimal_digits as i32);
+            let (x, y) = turn((end.0 - about.0, end.1 - about.1));
+            let rotated = (((x + about.0) * x_scale).round() as i64, ((y + about.1) * y_scale).round() as i64);
+            let coordinates = if incremental {
+                (rotated.0 - written.0, rotated.1 - written.1)
+            } else {
+                rotated
+            };
+            written = rotated;
+
+            match command {
+                Command::D01(op) => {
+                    (op.x, op.y) = (Some(coordinates.0), Some(coordinates.1));
+
+                    if let Some(arc) = arc {
+                        let (i, j) = turn((arc.center.0 - start.0, arc.center.1 - start.1));
+                        let (i, j) = if single_quadrant { (i.abs(), j.abs()) } else { (i, j) };
+                        (op.i, op.j) = (Some((i * x_scale).round() as i64), Some((j * y_scale).round() as i64));
+                    }
+                },
+                Command::D02(op) => (op.x, op.y) = (Some(coordinates.0), Some(coordinates.1)),
+                Command::D03(op) => {
+                    (op.x, op.y) = (Some(coordinates.0), Some(coordinates.1));
+
+                    if let Some(code) = aperture.filter(|code| unrotated_apertures.contains(code)) {
+                        warnings.push(GerberWarning::UnrotatedFlash { index, aperture: code });
+                    }
+                },
+                _ => {},
+            }
+        }
+
+        warnings
+    }
+
+    /// Appends the commands of another file, drawing its image over this one
+    ///
+    /// The apertures of `other` get codes unused in this file, and its aperture
+    /// selections are renumbered to match. Its MO and FS commands are dropped when
+    /// this file has them, and the end of this file is dropped so the commands of
+    /// `other` are kept. The graphics state, notation, quadrant and interpolation
+    /// modes are reset in between, so `other` is drawn as it was. Macros of
+    /// `other` named like one of this file are renamed, with their apertures.
+    ///
+    /// # Arguments
+    ///
+    /// * `other` - The file to append
+    ///
+    /// # Returns
+    ///
+    /// * `Result<(), GerberError>` - An error if the files use different units or coordinate formats, in which case nothing is changed
+    pub fn merge(&mut self, other: &Gerber) -> Result<(), GerberError> {
+        let own_unit = self.unit()?;
+        if let (Some(own), Some(other)) = (own_unit, other.unit()?) {
+            if own != other {
+                return Err(GerberError::SemanticError(
+                    format!("Cannot merge a file in {:?} into a file in {:?}.", other, own)
+                ));
+            }
+        }
+
+        let own_format = self.format()?.cloned();
+        if let (Some(own), Some(other)) = (&own_format, other.format()?) {
+            if own != other {
+                return Err(GerberError::SemanticError(
+                    "Cannot merge files with different coordinate formats.".to_string()
+                ));
+            }
+        }
+
+        let mut next_code = self.commands.iter()
+            .filter_map(|command| match command {
+                Command::AD(aperture_def) => Some(aperture_def.code),
+                Command::AB(Some(code)) => Some(*code),
+                _ => None,
+            })
+            .max()
+            .map_or(10, |code| code + 1);
+        let mut codes = HashMap::new();
+        for command in &other.commands {
+            if let Command::AD(ApertureDefinition { code, .. }) | Command::AB(Some(code)) = command {
+                if !codes.contains_key(code) {
+                    codes.insert(*code, next_code);
+                    next_code += 1;
+                }
+            }
+        }
+
+        if self.commands.last().is_some_and(Command::is_end_of_file) {
+            self.commands.pop();
+        }
+        // The merged commands don't come from the parsed text
+        self.source_lines.truncate(self.commands.len());
+
+        // Macros of other named like one of this file get a name used by neither
+        let macro_names = |gerber: &Gerber| -> HashSet<String> {
+            gerber.commands.iter()
+                .filter_map(|command| match command {
+                    Command::AM(name, _) => Some(name.clone()),
+                    _ => None,
+                })
+                .collect()
+        };
+        let (own_macros, other_macros) = (macro_names(self), macro_names(other));
+        let mut taken: HashSet<String> = own_macros.union(&other_macros).cloned().collect();
+        let mut renamed_macros = HashMap::new();
+        for name in own_macros.intersection(&other_macros) {
+            let renamed = (1..)
+                .map(|suffix| format!("{}_{}", name, suffix))
+                .find(|candidate| !taken.contains(candidate))
+                .unwrap_or_default();
+            taken.insert(renamed.clone());
+            renamed_macros.insert(name.clone(), renamed);
+        }
+
+        let mut state = GraphicsState::default();
+        let mut plot_state = PlotState::default();
+        for command in &self.commands {
+            state.apply(command);
+            let _ = plot_state.apply(command);
+        }
+        if state.polarity != Polarity::Dark {
+            self.commands.push(Command::LP(Polarity::Dark));
+        }
+        if state.mirroring != Mirroring::None {
+            self.commands.push(Command::LM(Mirroring::None));
+        }
+        if state.rotation != 0.0 {
+            self.commands.push(Command::LR(0.0));
+        }
+        if state.scaling != 1.0 {
+            self.commands.push(Command::LS(1.0));
+        }
+
+        // Coordinates of other are read in the notation of its own FS command
+        let other_notation = other.format()?.or(own_format.as_ref())
+            .map_or(Notation::Absolute, |spec| spec.notation.clone());
+        match plot_state.notation() {
+            Some(notation) if notation != other_notation => self.commands.push(match other_notation {
+                Notation::Absolute => Command::G90,
+                Notation::Incremental => Command::G91,
+            }),
+            _ => {},
+        }
+        if plot_state.single_quadrant() {
+            self.commands.push(Command::G75);
+        }
+        if plot_state.interpolation_mode() != InterpolationMode::Linear {
+            self.commands.push(Command::G01);
+        }
+
+        for command in &other.commands {
+            let mut command = command.clone();
+            match &mut command {
+                Command::MO(_) if own_unit.is_some() => continue,
+                Command::FS(_) if own_format.is_some() => continue,
+                Command::AD(ApertureDefinition { code, template }) => {
+                    *code = codes.get(code).copied().unwrap_or(*code);
+                    if let ApertureTemplate::Macro(name, _) = template {
+                        if let Some(renamed) = renamed_macros.get(name) {
+                            *name = renamed.clone();
+                        }
+                    }
+                },
+                Command::AB(Some(code)) | Command::Dnn(code) => {
+                    *code = codes.get(code).copied().unwrap_or(*code);
+                },
+                Command::AM(name, _) => {
+                    if let Some(renamed) = renamed_macros.get(name) {
+                        *name = renamed.clone();
+                    }
+                },
+                _ => {},
+            }
+            self.commands.push(command);
+        }
+
+        Ok(())
+    }
+
+    /// Copies the file with every step and repeat block replaced by its copies
+    ///
+    /// The commands of each block are repeated `x_repeats` × `y_repeats` times,
+    /// row by row from the bottom left copy, with their coordinates moved by the
+    /// step distances. Coordinates are written out as by
+    /// [`explicit_operations`](Self::explicit_operations), and in incremental
+    /// notation the first operation of each copy, and the first one after the
+    /// block, are moved to account for where the previous copy ended.
+    ///
+    /// The spec doesn't allow nesting step and repeat blocks and the parser
+    /// rejects them. If commands built otherwise nest them, the inner block is
+    /// expanded first and its copies are repeated by the outer one. Blocks
+    /// before the FS command can't be placed, so their content is kept once.
+    ///
+    /// # Returns
+    ///
+    /// * `Gerber` - The expanded file, without SR commands
+    pub fn expand_step_and_repeat(&self) -> Gerber {
+        let mut format = None;
+        let mut incremental = false;
+
+        Gerber::from(expand_blocks(&self.explicit_operations(), &mut format, &mut incremental))
+    }
+}
+
+/// Expands the step and repeat blocks of explicit operations
+///
+/// # Arguments
+///
+/// * `commands` - The commands, with both coordinates set on every operation
+/// * `format` - The format set by the last FS command seen
+/// * `incremental` - Whether the coordinates seen last are incremental
+fn expand_blocks(commands: &[Command], format: &mut Option<FormatSpecification>, incremental: &mut bool) -> Vec<Command> {
+    let mut expanded = Vec::new();
+    // Incremental offset left for the next operation by the last block
+    let mut pending = (0, 0);
+    let mut index = 0;
+
+    while index < commands.len() {
+        let command = &commands[index];
+        index += 1;
+
+        match command {
+            Command::FS(spec) => {
+                *incremental = spec.notation == Notation::Incremental;
+                *format = Some(spec.clone());
+            },
+            Command::G90 | Command::G91 => *incremental = *command == Command::G91,
+            Command::SR(Some(step_and_repeat)) => {
+                let mut depth = 1;
+                let end = commands[index..].iter()
+                    .position(|command| {
+                        match command {
+                            Command::SR(Some(_)) => depth += 1,
+                            Command::SR(None) => depth -= 1,
+                            _ => {},
+                        }
+                        depth == 0
+                    })
+                    .map_or(commands.len(), |position| index + position);
+
+                let block = expand_blocks(&commands[index..end], format, incremental);
+                index = end + 1;
+
+                let Some(spec) = format.as_ref() else {
+                    expanded.extend(block);
+                    continue;
+                };
+                let offset = |repeat: u32, step: f64, digits: u8| (repeat as f64 * step * 10f64.powi(digits as i32)).round() as i64;
+
+                // Displacement of the whole block, in incremental notation
+                let displacement = block.iter().fold((0, 0), |(x, y), command| match operation_coordinates(command) {
+                    Some((op_x, op_y)) => (x + op_x.unwrap_or(0), y + op_y.unwrap_or(0)),
+                    None => (x, y),
+                });
+                let mut previous = (0, 0);
+
+                for row in 0..step_and_repeat.y_repeats {
+                    for column in 0..step_and_repeat.x_repeats {
+                        let copy_offset = (
+                            offset(column, step_and_repeat.x_step, spec.x_decimal_digits),
+                            offset(row, step_and_repeat.y_step, spec.y_decimal_digits),
+                        );
+
+                        if *incremental {
+                            if (row, column) != (0, 0) {
+                                pending.0 += copy_offset.0 - previous.0 - displacement.0;
+                                pending.1 += copy_offset.1 - previous.1 - displacement.1;
+                            }
+                            previous = copy_offset;
+                            expanded.extend(block.iter().map(|command| shift_pending(command, &mut pending)));
+                        } else {
+                            expanded.extend(block.iter().map(|command| shift_operation(command.clone(), copy_offset)));
+                        }
+                    }
+                }
+
+                // The file continues from where the block was first drawn
+                if *incremental {
+                    pending.0 -= previous.0;
+                    pending.1 -= previous.1;
+                }
+                continue;
+            },
+            Command::SR(None) => continue,
+            _ => {},
+        }
+
+        expanded.push(shift_pending(command, &mut pending));
+    }
+
+    expanded
+}
+
+/// Coordinates of a D01, D02 or D03 operation
+fn operation_coordinates(command: &Command) -> Option<(Option<i64>, Option<i64>)> {
+    match command {
+        Command::D01(op) => Some((op.x, op.y)),
+        Command::D02(op) => Some((op.x, op.y)),
+        Command::D03(op) => Some((op.x, op.y)),
+        _ => None,
+    }
+}
+
+/// Moves the coordinates of an operation by an offset, other commands are returned as they are
+fn shift_operation(mut command: Command, offset: (i64, i64)) -> Command {
+    let (x, y) = match &mut command {
+        Command::D01(op) => (&mut op.x, &mut op.y),
+        Command::D02(op) => (&mut op.x, &mut op.y),
+        Command::D03(op) => (&mut op.x, &mut op.y),
+        _ => return command,
+    };
+
+    *x = x.map(|x| x + offset.0);
+    *y = y.map(|y| y + offset.1);
+
+    command
+}
+
+/// Moves an operation by a pending incremental offset, which it uses up
+fn shift_pending(command: &Command, pending: &mut (i64, i64)) -> Command {
+    if operation_coordinates(command).is_none() {
+        return command.clone();
+    }
+
+    shift_operation(command.clone(), std::mem::take(pending))
+}
+
+/// Scales a dimension, rounding away floating point noise below a nanometer
+fn scale(value: f64, factor: f64) -> f64 {
+    (value * factor * 1e9).round() / 1e9
+}
+
+/// Scales a raw coordinate, moving it from one number of decimal digits to another
+fn scale_coordinate(value: i64, from_digits: u8, to_digits: u8, factor: f64) -> i64 {
+    (value as f64 * factor * 10f64.powi(to_digits as i32 - from_digits as i32)).round() as i64
+}
+
+/// Scales the dimensions of a standard aperture template, leaving macro parameters as they are
+pub(crate) fn scale_template(template: &mut ApertureTemplate, factor: f64) {
+    let scale_hole = |hole: &mut Option<f64>| *hole = hole.map(|diameter| scale(diameter, factor));
+
+    match template {
+        ApertureTemplate::Circle(diameter, hole) => {
+            *diameter = scale(*diameter, factor);
+            scale_hole(hole);
+        },
+        ApertureTemplate::Rectangle(x, y, hole) | ApertureTemplate::Obround(x, y, hole) => {
+            *x = scale(*x, factor);
+            *y = scale(*y, factor);
+            scale_hole(hole);
+        },
+        ApertureTemplate::Polygon(diameter, _, _, hole) => {
+            *diameter = scale(*diameter, factor);
+            scale_hole(hole);
+        },
+        ApertureTemplate::Macro(_, _) => {},
+    }
+}
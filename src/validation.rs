@@ -0,0 +1,278 @@
+//! # Validation
+//!
+//! This module checks parsed commands for problems that the grammar accepts
+//! but that make the resulting image meaningless.
+
+use std::collections::HashSet;
+use crate::{Command, Gerber};
+use crate::command::Polarity;
+use crate::error::{GerberError, GerberWarning};
+use crate::geometry::{contours_overlap, PlotState};
+
+/// Index of the G36 command and contours of a region
+type Region = (usize, Vec<Vec<(f64, f64)>>);
+
+impl Gerber {
+    /// Checks the commands for semantic problems
+    ///
+    /// Checking does not stop at the first problem, so everything wrong with
+    /// the file is reported at once.
+    ///
+    /// # Returns
+    ///
+    /// * `Vec<GerberError>` - Every problem found, empty for a clean file
+    pub fn validate(&self) -> Vec<GerberError> {
+        let mut errors = Vec::new();
+
+        self.check_header(&mut errors);
+        self.check_aperture_selection(&mut errors);
+        self.check_duplicate_apertures(&mut errors);
+        self.check_region_closure(&mut errors);
+        self.check_integer_digits(&mut errors);
+
+        errors
+    }
+
+    /// Checks the commands for likely design mistakes that are still valid Gerber
+    ///
+    /// # Returns
+    ///
+    /// * `Vec<GerberWarning>` - Every suspicious construct found, empty if there is none
+    pub fn warnings(&self) -> Vec<GerberWarning> {
+        let mut warnings = self.parse_warnings.clone();
+
+        self.check_region_overlaps(&mut warnings);
+
+        warnings
+    }
+
+    /// Reports the first operation made before both the MO and FS commands
+    ///
+    /// Only the first one is reported, as every later operation of a file
+    /// missing its header would be too.
+    fn check_header(&self, errors: &mut Vec<GerberError>) {
+        let mut unit_set = false;
+        let mut format_set = false;
+
+        for (index, command) in self.commands.iter().enumerate() {
+            match command {
+                Command::MO(_) => unit_set = true,
+                Command::FS(_) => format_set = true,
+                Command::D01(_) | Command::D02(_) | Command::D03(_) if !(unit_set && format_set) => {
+                    let missing = match (unit_set, format_set) {
+                        (false, false) => "MO and FS commands",
+                        (false, true) => "MO command",
+                        _ => "FS command",
+                    };
+                    errors.push(GerberError::SemanticError(
+                        format!("Command {}: coordinate operation before the {}.", index, missing)
+                    ));
+                    return;
+                },
+                _ => {}
+            }
+        }
+    }
+
+    /// Reports aperture codes defined more than once, by AD or AB commands
+    fn check_duplicate_apertures(&self, errors: &mut Vec<GerberError>) {
+        let mut defined = HashSet::new();
+
+        for (index, command) in self.commands.iter().enumerate() {
+            let code = match command {
+                Command::AD(aperture_def) => aperture_def.code,
+                Command::AB(Some(code)) => *code,
+                _ => continue,
+            };
+
+            if !defined.insert(code) {
+                errors.push(GerberError::SemanticError(
+                    format!("Command {}: aperture D{} is already defined.", index, code)
+                ));
+            }
+        }
+    }
+
+    /// Reports apertures selected before being defined, and draws or flashes
+    /// made without any aperture selected
+    fn check_aperture_selection(&self, errors: &mut Vec<GerberError>) {
+        let mut defined = HashSet::new();
+        let mut current_aperture = None;
+        let mut in_region = false;
+
+        for (index, command) in self.commands.iter().enumerate() {
+            match command {
+                Command::AD(aperture_def) => {
+                    defined.insert(aperture_def.code);
+                },
+                Command::AB(Some(code)) => {
+                    defined.insert(*code);
+                },
+                Command::Dnn(code) => {
+                    if !defined.contains(code) {
+                        errors.push(GerberError::SemanticError(
+                            format!("Command {}: aperture D{} was selected but never defined.", index, code)
+                        ));
+                    }
+                    current_aperture = Some(*code);
+                },
+                Command::G36 => in_region = true,
+                Command::G37 => in_region = false,
+                // Region contours are drawn without an aperture
+                Command::D01(_) if !in_region && current_aperture.is_none() => {
+                    errors.push(GerberError::SemanticError(
+                        format!("Command {}: D01 operation with no aperture selected.", index)
+                    ));
+                },
+                Command::D03(_) if current_aperture.is_none() => {
+                    errors.push(GerberError::SemanticError(
+                        format!("Command {}: D03 operation with no aperture selected.", index)
+                    ));
+                },
+                _ => {}
+            }
+        }
+    }
+
+    /// Reports operations with coordinates too large for the integer digits of the FS command
+    ///
+    /// Such values can't have been written in the declared format, so the FS
+    /// command doesn't match the data and the coordinates can't be trusted.
+    fn check_integer_digits(&self, errors: &mut Vec<GerberError>) {
+        let mut format = None;
+
+        for (index, command) in self.commands.iter().enumerate() {
+            let (x, y, i, j) = match command {
+                Command::FS(spec) => {
+                    format = Some(spec);
+                    continue;
+                },
+                Command::D01(op) => (op.x, op.y, op.i, op.j),
+                Command::D02(op) => (op.x, op.y, None, None),
+                Command::D03(op) => (op.x, op.y, None, None),
+                _ => continue,
+            };
+            // Operations before the FS command are reported by check_header
+            let Some(spec) = format else {
+                continue;
+            };
+
+            let x_overflows = [x, i].into_iter().flatten()
+                .any(|value| spec.resolve_x(value).abs() >= 10f64.powi(spec.x_integer_digits as i32));
+            let y_overflows = [y, j].into_iter().flatten()
+                .any(|value| spec.resolve_y(value).abs() >= 10f64.powi(spec.y_integer_digits as i32));
+
+            let (axis, digits) = match (x_overflows, y_overflows) {
+                (true, _) => ("X", spec.x_integer_digits),
+                (false, true) => ("Y", spec.y_integer_digits),
+                (false, false) => continue,
+            };
+            errors.push(GerberError::SemanticError(format!(
+                "Command {}: {} coordinate needs more than the {} integer digits set by the FS command.", index, axis, digits
+            )));
+        }
+    }
+
+    /// Reports region contours that don't end where they started
+    fn check_region_closure(&self, errors: &mut Vec<GerberError>) {
+        let mut plot_state = PlotState::default();
+        let mut in_region = false;
+        // Start and current end of the contour being built
+        let mut contour: Option<((f64, f64), (f64, f64))> = None;
+
+        for (index, command) in self.commands.iter().enumerate() {
+            let start = plot_state.current_point();
+            let Ok(point) = plot_state.apply(command) else {
+                // Operations before the FS command are reported by the header check
+                continue;
+            };
+
+            match (command, point) {
+                (Command::G36, _) => in_region = true,
+                (Command::G37, _) => {
+                    if let Some((contour_start, end)) = contour.take() {
+                        errors.extend(unclosed_contour(index, contour_start, end));
+                    }
+                    in_region = false;
+                },
+                // A move starts a new contour
+                (Command::D02(_), Some(_)) if in_region => {
+                    if let Some((contour_start, end)) = contour.take() {
+                        errors.extend(unclosed_contour(index, contour_start, end));
+                    }
+                },
+                (Command::D01(_), Some(end)) if in_region => {
+                    let (contour_start, _) = contour.unwrap_or((start, start));
+                    contour = Some((contour_start, end));
+                },
+                _ => {}
+            }
+        }
+    }
+
+    /// Reports dark regions overlapping each other
+    ///
+    /// Candidates are the pairs of regions whose bounding boxes overlap, then
+    /// their contours are intersected.
+    fn check_region_overlaps(&self, warnings: &mut Vec<GerberWarning>) {
+        let mut plot_state = PlotState::default();
+        let mut dark = true;
+        let mut regions: Vec<Region> = Vec::new();
+        let mut region: Option<Region> = None;
+
+        for (index, command) in self.commands.iter().enumerate() {
+            let start = plot_state.current_point();
+            let Ok(point) = plot_state.apply(command) else {
+                continue;
+            };
+
+            match (command, point, region.as_mut()) {
+                (Command::LP(polarity), _, _) => dark = *polarity == Polarity::Dark,
+                (Command::G36, _, _) => region = Some((index, Vec::new())),
+                (Command::G37, _, _) => {
+                    if let Some(finished) = region.take().filter(|_| dark) {
+                        regions.push(finished);
+                    }
+                },
+                (Command::D02(_), Some(point), Some((_, contours))) => contours.push(vec![point]),
+                (Command::D01(_), Some(end), Some((_, contours))) => match contours.last_mut() {
+                    Some(contour) => contour.push(end),
+                    None => contours.push(vec![start, end]),
+                },
+                _ => {}
+            }
+        }
+
+        let bounds: Vec<(f64, f64, f64, f64)> = regions.iter()
+            .map(|(_, contours)| contours.iter().flatten().fold(
+                (f64::MAX, f64::MAX, f64::MIN, f64::MIN),
+                |(min_x, min_y, max_x, max_y), &(x, y)| (min_x.min(x), min_y.min(y), max_x.max(x), max_y.max(y)),
+            ))
+            .collect();
+
+        for (first, (first_index, first_contours)) in regions.iter().enumerate() {
+            for (second, (second_index, second_contours)) in regions.iter().enumerate().skip(first + 1) {
+                let (a, b) = (bounds[first], bounds[second]);
+                if a.2 <= b.0 || b.2 <= a.0 || a.3 <= b.1 || b.3 <= a.1 {
+                    continue;
+                }
+
+                if contours_overlap(first_contours, second_contours) {
+                    warnings.push(GerberWarning::OverlappingRegions { first: *first_index, second: *second_index });
+                }
+            }
+        }
+    }
+}
+
+/// Error for a contour whose end differs from its start, if it does
+fn unclosed_contour(index: usize, start: (f64, f64), end: (f64, f64)) -> Option<GerberError> {
+    if (start.0 - end.0).abs() <= 1e-9 && (start.1 - end.1).abs() <= 1e-9 {
+        return None;
+    }
+
+    Some(GerberError::SemanticError(format!(
+        "Command {}: region contour starting at ({}, {}) ends at ({}, {}) and is not closed.",
+        index, start.0, start.1, end.0, end.1
+    )))
+}
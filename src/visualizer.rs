@@ -1,26 +1,42 @@
+use std::path::Path;
 use raylib::prelude::*;
 use crate::{Command, command::Unit, command::AMPrimitive, ApertureTemplate, D01Operation, D02Operation, D03Operation};
+use crate::aperture_macro::{expand_primitives, thermal_polygons};
 use crate::command::{Mirroring, Polarity};
+use crate::error::GerberError;
+use crate::geometry::{triangulate, InterpolationMode, PlotState};
+use crate::raster::MacroCoverage;
+
+/// Marks the pixels erased by clear polarity in the rendered layer
+///
+/// Layers are rendered on this color so it can be swapped for the background
+/// once every object is drawn, letting clear objects cut through dark ones.
+const CLEAR_COLOR: Color = Color { r: 255, g: 0, b: 255, a: 255 };
 
 /// Represents the state of the Gerber visualization
 pub struct GerberVisualizer {
-    // Current position
-    current_x: f32,
-    current_y: f32,
+    // Current point and aperture, in the file unit
+    plot_state: PlotState,
 
-    // Current aperture
-    current_aperture: Option<u32>,
     aperture_definitions: std::collections::HashMap<u32, ApertureTemplate>,
     aperture_macros: std::collections::HashMap<String, Vec<AMPrimitive>>,
+    // Commands of each block aperture, drawn again wherever it is flashed
+    block_apertures: std::collections::HashMap<u32, Vec<Command>>,
 
     // Scale factors for converting coordinates to pixels
     scale_factor: f64,
 
+    // Pan of the view, in pixels
+    offset_x: f32,
+    offset_y: f32,
+
     // Display settings
     width: i32,
     height: i32,
     background_color: Color,
     drawing_color: Color,
+    // Background and drawing colors switched to by the space key
+    alternate_colors: (Color, Color),
 
     // Transformation settings
     rotation: f32,
@@ -37,24 +53,38 @@ pub struct GerberVisualizer {
     // Current unit
     unit: Unit,
 
-    // Current polarity
-    dark_polarity: bool,
+    // Layers drawn by render, bottom first
+    layers: Vec<Layer>,
+}
+
+/// Commands drawn together, with the apertures they define
+///
+/// Every file numbers its apertures from D10, so each layer keeps its own.
+struct Layer {
+    commands: Vec<Command>,
+    // Color of the layer, or None to use the drawing color
+    color: Option<Color>,
+    aperture_definitions: std::collections::HashMap<u32, ApertureTemplate>,
+    aperture_macros: std::collections::HashMap<String, Vec<AMPrimitive>>,
+    block_apertures: std::collections::HashMap<u32, Vec<Command>>,
 }
 
 impl GerberVisualizer {
     /// Create a new GerberVisualizer with default settings
     pub fn new(width: i32, height: i32) -> Self {
         GerberVisualizer {
-            current_x: 0.0,
-            current_y: 0.0,
-            current_aperture: None,
+            plot_state: PlotState::default(),
             aperture_definitions: std::collections::HashMap::new(),
             aperture_macros: std::collections::HashMap::new(),
+            block_apertures: std::collections::HashMap::new(),
             scale_factor: 1.0,
+            offset_x: 0.0,
+            offset_y: 0.0,
             width,
             height,
             background_color: Color::BLACK,
             drawing_color: Color::GREEN,
+            alternate_colors: (Color::WHITE, Color::BLACK),
             rotation: 0.0,
             mirror_x: false,
             mirror_y: false,
@@ -64,25 +94,229 @@ impl GerberVisualizer {
             min_y: std::f32::MAX,
             max_y: std::f32::MIN,
             unit: Unit::Millimeters,
-            dark_polarity: true,
+            layers: Vec::new(),
         }
     }
 
+    /// Create a new GerberVisualizer drawing with the given colors
+    ///
+    /// # Arguments
+    ///
+    /// * `width` - Width of the view, in pixels
+    /// * `height` - Height of the view, in pixels
+    /// * `background` - Color of the empty image
+    /// * `drawing` - Color of the dark objects
+    pub fn with_colors(width: i32, height: i32, background: Color, drawing: Color) -> Self {
+        let mut visualizer = Self::new(width, height);
+        visualizer.set_colors(background, drawing);
+        visualizer
+    }
+
+    /// Set the colors the image is drawn with
+    ///
+    /// # Arguments
+    ///
+    /// * `background` - Color of the empty image
+    /// * `drawing` - Color of the dark objects
+    pub fn set_colors(&mut self, background: Color, drawing: Color) {
+        self.background_color = background;
+        self.drawing_color = drawing;
+    }
+
+    /// Set the colors the space key switches to, and back from
+    ///
+    /// # Arguments
+    ///
+    /// * `background` - Color of the empty image
+    /// * `drawing` - Color of the dark objects
+    pub fn set_alternate_colors(&mut self, background: Color, drawing: Color) {
+        self.alternate_colors = (background, drawing);
+    }
+
+    /// Colors the image is drawn with
+    ///
+    /// # Returns
+    ///
+    /// * `(Color, Color)` - The background and drawing colors
+    pub fn colors(&self) -> (Color, Color) {
+        (self.background_color, self.drawing_color)
+    }
+
+    /// Swap the colors the image is drawn with for the alternate ones
+    pub fn toggle_colors(&mut self) {
+        let current = self.colors();
+        let (background, drawing) = self.alternate_colors;
+        self.set_colors(background, drawing);
+        self.alternate_colors = current;
+    }
+
+    /// Set the color of the empty image
+    ///
+    /// Returns the visualizer, so settings can be chained before [`run`](Self::run).
+    pub fn background(&mut self, color: Color) -> &mut Self {
+        self.background_color = color;
+        self
+    }
+
+    /// Set the color of the dark objects
+    pub fn drawing(&mut self, color: Color) -> &mut Self {
+        self.drawing_color = color;
+        self
+    }
+
+    /// Set the scale applied to objects, as an LS command does
+    ///
+    /// LS commands in the processed file replace it.
+    pub fn scale(&mut self, scale: f32) -> &mut Self {
+        self.scale = scale;
+        self
+    }
+
+    /// Set the counterclockwise rotation applied to objects, in degrees, as an LR command does
+    ///
+    /// LR commands in the processed file replace it.
+    pub fn rotation(&mut self, degrees: f32) -> &mut Self {
+        self.rotation = degrees;
+        self
+    }
+
+    /// Set the mirroring applied to objects, as an LM command does
+    ///
+    /// LM commands in the processed file replace it.
+    ///
+    /// # Arguments
+    ///
+    /// * `x` - Whether X coordinates are negated
+    /// * `y` - Whether Y coordinates are negated
+    pub fn mirror(&mut self, x: bool, y: bool) -> &mut Self {
+        self.mirror_x = x;
+        self.mirror_y = y;
+        self
+    }
+
+    /// Add a layer drawn over the ones added before it
+    ///
+    /// The alpha of the color lets the layers below show through, so overlaps
+    /// blend. Bounds and scaling take every layer into account.
+    ///
+    /// # Arguments
+    ///
+    /// * `commands` - The commands of the layer, usually a whole file
+    /// * `color` - Color of the dark objects of the layer
+    pub fn add_layer(&mut self, commands: Vec<Command>, color: Color) {
+        self.push_layer(commands, Some(color));
+    }
+
+    fn push_layer(&mut self, commands: Vec<Command>, color: Option<Color>) {
+        // Each layer is a file of its own, starting from a fresh state
+        self.plot_state = PlotState::default();
+        self.process_commands(&commands);
+
+        self.layers.push(Layer {
+            commands,
+            color,
+            aperture_definitions: std::mem::take(&mut self.aperture_definitions),
+            aperture_macros: std::mem::take(&mut self.aperture_macros),
+            block_apertures: std::mem::take(&mut self.block_apertures),
+        });
+    }
+
+    /// Exchange the apertures of the visualizer with those of a layer
+    fn swap_apertures(&mut self, layer: &mut Layer) {
+        std::mem::swap(&mut self.aperture_definitions, &mut layer.aperture_definitions);
+        std::mem::swap(&mut self.aperture_macros, &mut layer.aperture_macros);
+        std::mem::swap(&mut self.block_apertures, &mut layer.block_apertures);
+    }
+
     /// Process a list of Gerber commands and prepare for visualization
+    ///
+    /// Problems in the command stream are ignored; use `try_process_commands`
+    /// to have them reported.
     pub fn process_commands(&mut self, commands: &[Command]) {
+        let _ = self.try_process_commands(commands);
+    }
+
+    /// Process a list of Gerber commands and prepare for visualization,
+    /// collecting every problem encountered along the way
+    ///
+    /// Processing does not stop at the first problem, so everything that can
+    /// be rendered still is.
+    ///
+    /// # Returns
+    ///
+    /// * `Result<(), Vec<GerberError>>` - Ok if the commands were consistent, or all detected issues
+    pub fn try_process_commands(&mut self, commands: &[Command]) -> Result<(), Vec<GerberError>> {
+        let mut errors = Vec::new();
+        // Code, commands and plot state of the blocks being defined, innermost last
+        let mut open_blocks: Vec<(u32, Vec<Command>, PlotState)> = Vec::new();
+
         for cmd in commands {
-            self.process_command(cmd);
+            // Block contents aren't plotted yet, but their modes still apply to what follows in the block
+            let plot_state = open_blocks.last().map_or(&self.plot_state, |(_, _, state)| state);
+
+            match cmd {
+                Command::AD(aperture_def) => {
+                    if let ApertureTemplate::Macro(name, _) = &aperture_def.template {
+                        if !self.aperture_macros.contains_key(name) {
+                            errors.push(GerberError::SemanticError(
+                                format!("Aperture D{} uses undefined macro '{}'.", aperture_def.code, name)
+                            ));
+                        }
+                    }
+                },
+                Command::Dnn(code) if !self.aperture_definitions.contains_key(code) && !self.block_apertures.contains_key(code) => {
+                    errors.push(GerberError::SemanticError(
+                        format!("Aperture D{} was selected but never defined.", code)
+                    ));
+                },
+                Command::D01(op) if plot_state.interpolation_mode() != InterpolationMode::Linear && (op.i.is_none() || op.j.is_none()) => {
+                    errors.push(GerberError::SemanticError(
+                        "Arc D01 operation is missing its I/J offsets.".to_string()
+                    ));
+                },
+                _ => {}
+            }
+
+            match (cmd, open_blocks.last_mut()) {
+                (Command::AB(Some(code)), _) => {
+                    // Nested blocks start from the state of the block around them
+                    let state = open_blocks.last().map_or(&self.plot_state, |(_, _, state)| state).clone();
+                    open_blocks.push((*code, Vec::new(), state));
+                },
+                (Command::AB(None), _) => {
+                    if let Some((code, block, _)) = open_blocks.pop() {
+                        self.block_apertures.insert(code, block);
+                    }
+                },
+                (_, Some((_, block, state))) => {
+                    // Definitions made inside a block are still global
+                    if matches!(cmd, Command::AD(_) | Command::AM(_, _)) {
+                        self.process_command(cmd);
+                    }
+                    let _ = state.apply(cmd);
+                    block.push(cmd.clone());
+                },
+                (_, None) => self.process_command(cmd),
+            }
         }
 
         // After processing all commands, calculate appropriate scaling
         self.calculate_scale_factor();
+
+        if errors.is_empty() {
+            Ok(())
+        } else {
+            Err(errors)
+        }
     }
 
     /// Process a single Gerber command
     fn process_command(&mut self, command: &Command) {
+        let end = self.plot_state.apply(command).unwrap_or(None);
+
         match command {
             Command::MO(unit) => {
-                self.unit = unit.clone();
+                self.unit = *unit;
             },
             Command::AD(aperture_def) => {
                 self.aperture_definitions.insert(aperture_def.code, aperture_def.template.clone());
@@ -90,43 +324,11 @@ impl GerberVisualizer {
             Command::AM(name, primitives) => {
                 self.aperture_macros.insert(name.clone(), primitives.clone());
             },
-            Command::Dnn(code) => {
-                self.current_aperture = Some(*code);
-            },
-            Command::D01(op) => {
-                if let Some(x) = op.x {
-                    self.update_bounds(x as f32, self.current_y);
-                    self.current_x = x as f32;
-                }
-                if let Some(y) = op.y {
-                    self.update_bounds(self.current_x, y as f32);
-                    self.current_y = y as f32;
-                }
-            },
-            Command::D02(op) => {
-                if let Some(x) = op.x {
-                    self.current_x = x as f32;
-                }
-                if let Some(y) = op.y {
-                    self.current_y = y as f32;
+            Command::D01(_) | Command::D03(_) => {
+                if let Some((x, y)) = end {
+                    self.update_bounds(x as f32, y as f32);
                 }
             },
-            Command::D03(op) => {
-                if let Some(x) = op.x {
-                    self.update_bounds(x as f32, self.current_y);
-                    self.current_x = x as f32;
-                }
-                if let Some(y) = op.y {
-                    self.update_bounds(self.current_x, y as f32);
-                    self.current_y = y as f32;
-                }
-            },
-            Command::LP(polarity) => {
-                self.dark_polarity = match polarity {
-                    Polarity::Dark => true,
-                    Polarity::Clear => false,
-                };
-            },
             Command::LM(mirroring) => {
                 match mirroring {
                     Mirroring::None => {
@@ -184,43 +386,216 @@ impl GerberVisualizer {
 
     /// Convert gerber coordinates to screen coordinates
     fn to_screen_coords(&self, x: f32, y: f32) -> (i32, i32) {
+        let (screen_x, screen_y) = self.screen_position(x, y);
+        (screen_x as i32, screen_y as i32)
+    }
+
+    /// Convert gerber coordinates to a position on the screen, in pixels
+    ///
+    /// # Arguments
+    ///
+    /// * `x` - The X coordinate, in the file unit
+    /// * `y` - The Y coordinate, in the file unit
+    ///
+    /// # Returns
+    ///
+    /// * `(f32, f32)` - The position on the screen, including zoom and pan
+    pub fn screen_position(&self, x: f32, y: f32) -> (f32, f32) {
+        let (x_scaled, y_scaled) = self.transform_point(x as f64, y as f64);
+        let (x_scaled, y_scaled) = (x_scaled as f32, y_scaled as f32);
+
+        // Apply translation to center the drawing
+        let center_x = self.width as f32 / 2.0;
+        let center_y = self.height as f32 / 2.0;
+
+        let x_centered = center_x + (x_scaled - (self.min_x + self.max_x) / 2.0) * self.scale_factor as f32;
+        let y_centered = center_y + (y_scaled - (self.min_y + self.max_y) / 2.0) * self.scale_factor as f32;
+
+        (x_centered + self.offset_x, y_centered + self.offset_y)
+    }
+
+    /// Zoom the view, keeping the point under the cursor where it is
+    ///
+    /// # Arguments
+    ///
+    /// * `factor` - How much to multiply the scale by, above 1 to zoom in
+    /// * `cursor_x` - The X position of the cursor on the screen, in pixels
+    /// * `cursor_y` - The Y position of the cursor on the screen, in pixels
+    pub fn zoom_at(&mut self, factor: f64, cursor_x: f32, cursor_y: f32) {
+        // Distance from the cursor to where the drawing is centered
+        let center_x = self.width as f32 / 2.0 + self.offset_x;
+        let center_y = self.height as f32 / 2.0 + self.offset_y;
+
+        self.scale_factor *= factor;
+        self.offset_x += (center_x - cursor_x) * (factor as f32 - 1.0);
+        self.offset_y += (center_y - cursor_y) * (factor as f32 - 1.0);
+    }
+
+    /// Move the view by a number of pixels
+    pub fn pan(&mut self, dx: f32, dy: f32) {
+        self.offset_x += dx;
+        self.offset_y += dy;
+    }
+
+    /// Apply the current transformation (mirroring, then rotation, then
+    /// scaling) to a point, without mapping it onto the screen
+    ///
+    /// This is the transform shared by every output, the screen translation
+    /// is added by `screen_position`.
+    ///
+    /// # Arguments
+    ///
+    /// * `x` - The X coordinate, in the file unit
+    /// * `y` - The Y coordinate, in the file unit
+    ///
+    /// # Returns
+    ///
+    /// * `(f64, f64)` - The transformed point, in the file unit
+    pub fn transform_point(&self, x: f64, y: f64) -> (f64, f64) {
         // Apply mirroring
         let x_mirrored = if self.mirror_x { -x } else { x };
         let y_mirrored = if self.mirror_y { -y } else { y };
 
         // Apply rotation
-        let angle_rad = self.rotation.to_radians();
-        let sin_angle = angle_rad.sin();
-        let cos_angle = angle_rad.cos();
+        let (sin_angle, cos_angle) = (self.rotation as f64).to_radians().sin_cos();
 
         let x_rotated = x_mirrored * cos_angle - y_mirrored * sin_angle;
         let y_rotated = x_mirrored * sin_angle + y_mirrored * cos_angle;
 
         // Apply scaling
-        let x_scaled = x_rotated * self.scale;
-        let y_scaled = y_rotated * self.scale;
+        (x_rotated * self.scale as f64, y_rotated * self.scale as f64)
+    }
 
-        // Apply translation to center the drawing
-        let center_x = self.width as f32 / 2.0;
-        let center_y = self.height as f32 / 2.0;
+    /// Apply the aperture transformation (mirroring, rotation, scaling) to an
+    /// offset relative to the flash point
+    fn transform_offset(&self, dx: f32, dy: f32) -> (f32, f32) {
+        let (dx_transformed, dy_transformed) = self.transform_point(dx as f64, dy as f64);
+        (dx_transformed as f32, dy_transformed as f32)
+    }
 
-        let x_centered = center_x + (x_scaled - (self.min_x + self.max_x) / 2.0) * self.scale_factor as f32;
-        let y_centered = center_y + (y_scaled - (self.min_y + self.max_y) / 2.0) * self.scale_factor as f32;
+    /// Compute the screen coordinates of the four corners of a rectangle
+    /// flashed at the given location, with the aperture transformation applied
+    ///
+    /// # Arguments
+    ///
+    /// * `x`, `y` - Flash location, in gerber coordinates
+    /// * `width`, `height` - Size of the rectangle before rotation and scaling
+    pub fn rectangle_corners(&self, x: f32, y: f32, width: f32, height: f32) -> [Vector2; 4] {
+        let (screen_x, screen_y) = self.to_screen_coords(x, y);
+        let half_width = width / 2.0;
+        let half_height = height / 2.0;
+
+        let offsets = [
+            (-half_width, -half_height),
+            (half_width, -half_height),
+            (half_width, half_height),
+            (-half_width, half_height),
+        ];
 
-        (x_centered as i32, y_centered as i32)
+        offsets.map(|(dx, dy)| {
+            let (tx, ty) = self.transform_offset(dx, dy);
+            Vector2::new(
+                screen_x as f32 + tx * self.scale_factor as f32,
+                screen_y as f32 + ty * self.scale_factor as f32,
+            )
+        })
+    }
+
+    /// Split an obround flashed at the given location into a rectangle and two
+    /// half circle caps, with the aperture transformation applied
+    ///
+    /// The caps have the diameter of the shorter side and sit on the ends of the
+    /// longer one, so together with the rectangle between them they make a stadium.
+    ///
+    /// # Arguments
+    ///
+    /// * `x`, `y` - Flash location, in gerber coordinates
+    /// * `width`, `height` - Size of the obround before rotation and scaling
+    ///
+    /// # Returns
+    ///
+    /// * `([Vector2; 4], [Vector2; 2], f32)` - Corners of the rectangle and centers of the caps on screen, and the cap radius in pixels
+    pub fn obround_parts(&self, x: f32, y: f32, width: f32, height: f32) -> ([Vector2; 4], [Vector2; 2], f32) {
+        let (screen_x, screen_y) = self.to_screen_coords(x, y);
+        let (straight_width, straight_height, end_offset) = if width > height {
+            (width - height, height, ((width - height) / 2.0, 0.0))
+        } else {
+            (width, height - width, (0.0, (height - width) / 2.0))
+        };
+
+        let corners = self.rectangle_corners(x, y, straight_width, straight_height);
+        let ends = [-1.0, 1.0].map(|sign| {
+            let (tx, ty) = self.transform_offset(sign * end_offset.0, sign * end_offset.1);
+            Vector2::new(
+                screen_x as f32 + tx * self.scale_factor as f32,
+                screen_y as f32 + ty * self.scale_factor as f32,
+            )
+        });
+        let radius = width.min(height) / 2.0 * self.scale * self.scale_factor as f32;
+
+        (corners, ends, radius)
+    }
+
+    /// Compute the screen coordinates of the vertices of a regular polygon
+    /// flashed at the given location, with the aperture transformation applied
+    ///
+    /// # Arguments
+    ///
+    /// * `x`, `y` - Flash location, in gerber coordinates
+    /// * `diameter` - Diameter of the circumscribed circle
+    /// * `vertices` - Number of vertices
+    /// * `rotation` - Rotation in degrees; with no rotation the first vertex lies on the positive X axis
+    ///
+    /// # Returns
+    ///
+    /// * `Vec<Vector2>` - The vertices, in counter-clockwise order before transformation
+    pub fn polygon_vertices(&self, x: f32, y: f32, diameter: f32, vertices: u32, rotation: f32) -> Vec<Vector2> {
+        let (screen_x, screen_y) = self.to_screen_coords(x, y);
+        let radius = diameter / 2.0;
+
+        (0..vertices)
+            .map(|index| {
+                let angle = (rotation + 360.0 * index as f32 / vertices as f32).to_radians();
+                let (tx, ty) = self.transform_offset(radius * angle.cos(), radius * angle.sin());
+                Vector2::new(
+                    screen_x as f32 + tx * self.scale_factor as f32,
+                    screen_y as f32 + ty * self.scale_factor as f32,
+                )
+            })
+            .collect()
+    }
+
+    /// Draw a filled triangle regardless of the winding order of its vertices
+    ///
+    /// Raylib only fills triangles given in counter-clockwise order, which
+    /// mirroring flips.
+    fn draw_triangle_ccw(d: &mut impl RaylibDraw, v1: Vector2, v2: Vector2, v3: Vector2, color: Color) {
+        let cross = (v2.x - v1.x) * (v3.y - v1.y) - (v2.y - v1.y) * (v3.x - v1.x);
+
+        if cross > 0.0 {
+            d.draw_triangle(v1, v3, v2, color);
+        } else {
+            d.draw_triangle(v1, v2, v3, color);
+        }
     }
 
     /// Render the gerber file
-    pub fn render(&self, d: &mut RaylibDrawHandle) {
+    ///
+    /// # Arguments
+    ///
+    /// * `d` - The draw handle of the window
+    /// * `layer` - The layer rendered from the commands, with cleared pixels transparent
+    pub fn render(&self, d: &mut RaylibDrawHandle, layer: &Texture2D) {
         // Clear the background
         d.clear_background(self.background_color);
 
         // Draw the parsed gerber commands
         self.draw_commands(d);
+        d.draw_texture(layer, 0, 0, Color::WHITE);
 
         // Draw scale info
         let scale_text = format!("Scale: {:.2}", self.scale_factor);
-        d.draw_text(&scale_text, 20, 20, 20, Color::WHITE);
+        d.draw_text(&scale_text, 20, 20, 20, self.drawing_color);
     }
 
     /// Draw the gerber commands
@@ -237,69 +612,211 @@ impl GerberVisualizer {
     }
 
     /// Draw aperture at a specific location
-    fn draw_aperture(&self, d: &mut RaylibDrawHandle, aperture_code: u32, x: f32, y: f32) {
+    fn draw_aperture(&self, d: &mut impl RaylibDraw, aperture_code: u32, x: f32, y: f32, color: Color) {
         if let Some(aperture) = self.aperture_definitions.get(&aperture_code) {
             let (screen_x, screen_y) = self.to_screen_coords(x, y);
 
             match aperture {
                 ApertureTemplate::Circle(diameter, _) => {
-                    let radius = (diameter * self.scale_factor / 2.0) as f32;
-                    let color = if self.dark_polarity { self.drawing_color } else { self.background_color };
+                    let radius = (diameter * self.scale_factor / 2.0) as f32 * self.scale;
                     d.draw_circle(screen_x, screen_y, radius, color);
                 },
                 ApertureTemplate::Rectangle(width, height, _) => {
-                    let half_width = (width * self.scale_factor / 2.0) as i32;
-                    let half_height = (height * self.scale_factor / 2.0) as i32;
-                    let color = if self.dark_polarity { self.drawing_color } else { self.background_color };
-                    d.draw_rectangle(
-                        screen_x - half_width,
-                        screen_y - half_height,
-                        half_width * 2,
-                        half_height * 2,
-                        color
-                    );
+                    let corners = self.rectangle_corners(x, y, *width as f32, *height as f32);
+
+                    // Split the (possibly rotated) quad into two triangles
+                    Self::draw_triangle_ccw(d, corners[0], corners[1], corners[2], color);
+                    Self::draw_triangle_ccw(d, corners[0], corners[2], corners[3], color);
                 },
                 ApertureTemplate::Obround(width, height, _) => {
-                    // Simplified obround as rectangle with rounded corners
-                    let half_width = (width * self.scale_factor / 2.0) as i32;
-                    let half_height = (height * self.scale_factor / 2.0) as i32;
-                    let color = if self.dark_polarity { self.drawing_color } else { self.background_color };
-
-                    d.draw_rectangle_rounded(
-                        Rectangle::new(
-                            (screen_x - half_width) as f32,
-                            (screen_y - half_height) as f32,
-                            (half_width * 2) as f32,
-                            (half_height * 2) as f32
-                        ),
-                        0.5,
-                        10,
-                        color
-                    );
+                    let (corners, ends, radius) = self.obround_parts(x, y, *width as f32, *height as f32);
+
+                    Self::draw_triangle_ccw(d, corners[0], corners[1], corners[2], color);
+                    Self::draw_triangle_ccw(d, corners[0], corners[2], corners[3], color);
+                    for end in ends {
+                        d.draw_circle_v(end, radius, color);
+                    }
                 },
                 ApertureTemplate::Polygon(diameter, vertices, rotation, _) => {
-                    let radius = (diameter * self.scale_factor / 2.0) as f32;
-                    let rot = rotation.unwrap_or(0.0) as f32;
-
-                    // Draw polygon (simplified)
-                    let color = if self.dark_polarity { self.drawing_color } else { self.background_color };
-                    let vert_count = *vertices as i32;
+                    let corners = self.polygon_vertices(x, y, *diameter as f32, *vertices, rotation.unwrap_or(0.0) as f32);
+                    let center = Vector2::new(screen_x as f32, screen_y as f32);
 
-                    // Draw as circle for now (full polygon implementation would be more complex)
-                    d.draw_circle(screen_x, screen_y, radius, color);
+                    // Fill as a fan of triangles around the center
+                    for (index, corner) in corners.iter().enumerate() {
+                        let next = corners[(index + 1) % corners.len()];
+                        Self::draw_triangle_ccw(d, center, *corner, next, color);
+                    }
                 },
-                ApertureTemplate::Macro(name, params) => {
-                    // Drawing macro apertures requires more complex implementation
-                    // Not implemented in this basic version
+                ApertureTemplate::Macro(_, _) => {
+                    let center = Vector2::new(screen_x as f32, screen_y as f32);
+                    let primitives = self.macro_primitives(aperture_code);
+
+                    // Erasing with the clear color would cut through whatever is under the
+                    // flash too, so apertures with cutouts are filled pixel by pixel
+                    if primitives.iter().any(|primitive| !primitive_exposure(primitive)) {
+                        self.draw_macro_coverage(d, center, &primitives, color);
+                    } else {
+                        for primitive in &primitives {
+                            self.draw_macro_primitive(d, center, primitive, color != CLEAR_COLOR);
+                        }
+                    }
                 },
             }
+
+            // The hole is drilled through, so it shows the background whatever the polarity
+            if let Some(hole_diameter) = self.aperture_hole(aperture_code) {
+                let radius = (hole_diameter * self.scale_factor / 2.0) as f32 * self.scale;
+                d.draw_circle(screen_x, screen_y, radius, CLEAR_COLOR);
+            }
         }
     }
 
+    /// Diameter of the hole cut out of a flashed aperture
+    ///
+    /// # Arguments
+    ///
+    /// * `aperture_code` - The D code of the aperture
+    ///
+    /// # Returns
+    ///
+    /// * `Option<f64>` - The hole diameter in the file unit, or None if the aperture has no hole
+    pub fn aperture_hole(&self, aperture_code: u32) -> Option<f64> {
+        self.aperture_definitions.get(&aperture_code)?.hole_diameter()
+    }
+
+    /// Enumerate the primitives drawn when flashing a macro aperture
+    ///
+    /// The macro is expanded with the parameters of the aperture definition.
+    ///
+    /// # Returns
+    ///
+    /// * `Vec<AMPrimitive>` - The concrete primitives, empty if the aperture is not a macro or the macro can't be expanded
+    pub fn macro_primitives(&self, aperture_code: u32) -> Vec<AMPrimitive> {
+        match self.aperture_definitions.get(&aperture_code) {
+            Some(ApertureTemplate::Macro(name, params)) => self.aperture_macros.get(name)
+                .and_then(|body| expand_primitives(body, params).ok())
+                .unwrap_or_default(),
+            _ => Vec::new(),
+        }
+    }
+
+    /// Draw a single macro primitive of an aperture flashed at the given screen location
+    fn draw_macro_primitive(&self, d: &mut impl RaylibDraw, center: Vector2, primitive: &AMPrimitive, dark: bool) {
+        // Exposure off erases within the aperture, which only matters when drawing dark
+        let color = match (dark, primitive_exposure(primitive)) {
+            (true, true) => self.drawing_color,
+            (true, false) | (false, true) => CLEAR_COLOR,
+            (false, false) => return,
+        };
+
+        match primitive {
+            AMPrimitive::Circle(_, diameter, center_x, center_y, rotation) => {
+                let position = self.macro_point(center, *center_x, *center_y, rotation.unwrap_or(0.0));
+                let radius = (diameter / 2.0 * self.scale_factor) as f32 * self.scale;
+                d.draw_circle_v(position, radius, color);
+            },
+            AMPrimitive::VectorLine(_, width, start_x, start_y, end_x, end_y, rotation) => {
+                let length = (end_x - start_x).hypot(end_y - start_y);
+                if length == 0.0 {
+                    return;
+                }
+
+                // Offset perpendicular to the line by half the width
+                let normal_x = -(end_y - start_y) / length * width / 2.0;
+                let normal_y = (end_x - start_x) / length * width / 2.0;
+                let corners = [
+                    (start_x + normal_x, start_y + normal_y),
+                    (end_x + normal_x, end_y + normal_y),
+                    (end_x - normal_x, end_y - normal_y),
+                    (start_x - normal_x, start_y - normal_y),
+                ];
+                self.fill_macro_polygon(d, center, &corners, *rotation, color);
+            },
+            AMPrimitive::CenterLine(_, width, height, center_x, center_y, rotation) => {
+                let corners = [
+                    (center_x - width / 2.0, center_y - height / 2.0),
+                    (center_x + width / 2.0, center_y - height / 2.0),
+                    (center_x + width / 2.0, center_y + height / 2.0),
+                    (center_x - width / 2.0, center_y + height / 2.0),
+                ];
+                self.fill_macro_polygon(d, center, &corners, *rotation, color);
+            },
+            AMPrimitive::Outline(_, points, rotation) => {
+                self.fill_macro_polygon(d, center, points, *rotation, color);
+            },
+            AMPrimitive::Polygon(..) => {
+                if let Some(corners) = primitive.as_polygon() {
+                    self.fill_macro_polygon(d, center, &corners, 0.0, color);
+                }
+            },
+            AMPrimitive::Thermal(center_x, center_y, outer_diameter, inner_diameter, gap, rotation) => {
+                for polygon in thermal_polygons((*center_x, *center_y), *outer_diameter, *inner_diameter, *gap, *rotation) {
+                    self.fill_macro_polygon(d, center, &polygon, 0.0, color);
+                }
+            },
+            _ => {},
+        }
+    }
+
+    /// Fill a convex polygon given in macro coordinates
+    fn fill_macro_polygon(&self, d: &mut impl RaylibDraw, center: Vector2, points: &[(f64, f64)], rotation: f64, color: Color) {
+        // The transformation is affine, so triangles of the macro outline stay triangles on screen
+        for [a, b, c] in triangulate(points) {
+            let corner = |(x, y): (f64, f64)| self.macro_point(center, x, y, rotation);
+            Self::draw_triangle_ccw(d, corner(a), corner(b), corner(c), color);
+        }
+    }
+
+    /// Fill the pixels exposed by a macro aperture flashed at the given screen location
+    ///
+    /// Primitives with exposure off clear what the ones before them exposed,
+    /// leaving the pixels under the flash as they were.
+    fn draw_macro_coverage(&self, d: &mut impl RaylibDraw, center: Vector2, primitives: &[AMPrimitive], color: Color) {
+        let coverage = MacroCoverage::new(primitives);
+        let pixels_per_unit = self.scale_factor * self.scale as f64;
+        let reach = (coverage.reach() * pixels_per_unit.abs()).ceil() as i32;
+        let (sin_angle, cos_angle) = (-self.rotation as f64).to_radians().sin_cos();
+
+        // Only the pixels on the screen are tested
+        let (center_x, center_y) = (center.x.round() as i32, center.y.round() as i32);
+        for pixel_y in (center_y - reach).max(0)..=(center_y + reach).min(self.height - 1) {
+            for pixel_x in (center_x - reach).max(0)..=(center_x + reach).min(self.width - 1) {
+                // Undo the scaling, rotation and mirroring applied by macro_point
+                let x = (pixel_x - center_x) as f64 / pixels_per_unit;
+                let y = (pixel_y - center_y) as f64 / pixels_per_unit;
+                let (x, y) = (x * cos_angle - y * sin_angle, x * sin_angle + y * cos_angle);
+                let local = (if self.mirror_x { -x } else { x }, if self.mirror_y { -y } else { y });
+
+                if coverage.covers(local) {
+                    d.draw_pixel(pixel_x, pixel_y, color);
+                }
+            }
+        }
+    }
+
+    /// Convert a point in macro coordinates to the screen, rotating it about
+    /// the macro origin before applying the aperture transformation
+    fn macro_point(&self, center: Vector2, x: f64, y: f64, rotation: f64) -> Vector2 {
+        let (sin_angle, cos_angle) = rotation.to_radians().sin_cos();
+        let x_rotated = x * cos_angle - y * sin_angle;
+        let y_rotated = x * sin_angle + y * cos_angle;
+
+        let (tx, ty) = self.transform_offset(x_rotated as f32, y_rotated as f32);
+        Vector2::new(
+            center.x + tx * self.scale_factor as f32,
+            center.y + ty * self.scale_factor as f32,
+        )
+    }
+
     /// Start the visualization loop
+    ///
+    /// The commands are drawn as a layer in the drawing color, over the
+    /// layers added with [`add_layer`](Self::add_layer).
     pub fn run(&mut self, commands: &[Command]) {
         // Process the commands to prepare for visualization
-        self.process_commands(commands);
+        if !commands.is_empty() {
+            self.push_layer(commands.to_vec(), None);
+        }
 
         // Initialize Raylib
         let (mut rl, thread) = init()
@@ -310,199 +827,476 @@ impl GerberVisualizer {
         // Set target FPS
         rl.set_target_fps(60);
 
+        // The layer is only rendered again when the colors or the view change
+        let mut layer = None;
+        let mut layer_settings = None;
+
+        // Mouse position at the previous frame of a drag
+        let mut drag_position: Option<Vector2> = None;
+
         while !rl.window_should_close() {
             // Process input
             if rl.is_key_pressed(KeyboardKey::KEY_SPACE) {
-                // Toggle dark/light background
-                if self.background_color == Color::BLACK {
-                    self.background_color = Color::WHITE;
-                    self.drawing_color = Color::BLACK;
-                } else {
-                    self.background_color = Color::BLACK;
-                    self.drawing_color = Color::GREEN;
-                }
+                self.toggle_colors();
             }
 
             // Zoom controls
+            let (window_center_x, window_center_y) = (self.width as f32 / 2.0, self.height as f32 / 2.0);
             if rl.is_key_down(KeyboardKey::KEY_EQUAL) {
-                self.scale_factor *= 1.05;
+                self.zoom_at(1.05, window_center_x, window_center_y);
             }
             if rl.is_key_down(KeyboardKey::KEY_MINUS) {
-                self.scale_factor *= 0.95;
+                self.zoom_at(0.95, window_center_x, window_center_y);
+            }
+
+            let mouse_position = rl.get_mouse_position();
+            let wheel = rl.get_mouse_wheel_move();
+            if wheel != 0.0 {
+                self.zoom_at(1.1f64.powf(wheel as f64), mouse_position.x, mouse_position.y);
+            }
+
+            // Pan controls
+            if rl.is_mouse_button_down(MouseButton::MOUSE_LEFT_BUTTON) || rl.is_mouse_button_down(MouseButton::MOUSE_MIDDLE_BUTTON) {
+                if let Some(previous) = drag_position {
+                    self.pan(mouse_position.x - previous.x, mouse_position.y - previous.y);
+                }
+                drag_position = Some(mouse_position);
+            } else {
+                drag_position = None;
             }
 
             // Begin drawing
             let mut d = rl.begin_drawing(&thread);
 
+            let settings = (self.background_color, self.drawing_color, self.scale_factor, self.offset_x, self.offset_y);
+            if layer_settings != Some(settings) {
+                // Uncovered pixels stay transparent so the extents show through
+                let image = self.render_layers(&mut d, &thread, Color::BLANK).expect("Failed to render the Gerber layers");
+                layer = Some(d.load_texture_from_image(&thread, &image).expect("Failed to load the Gerber layer"));
+                layer_settings = Some(settings);
+            }
+
             // Render the gerber file
-            self.render(&mut d);
+            if let Some(layer) = &layer {
+                self.render(&mut d, layer);
+            }
 
             // Draw instructions
-            d.draw_text("Space: Toggle Color | +/-: Zoom", 20, self.height - 30, 20, Color::WHITE);
+            d.draw_text("Space: Toggle Color | +/-/Wheel: Zoom | Drag: Pan", 20, self.height - 30, 20, self.drawing_color);
+        }
+    }
+
+    /// Render the commands to a PNG file without showing a window
+    ///
+    /// As with [`run`](Self::run), the commands are drawn over the layers added before.
+    ///
+    /// # Arguments
+    ///
+    /// * `commands` - The commands to render
+    /// * `path` - Where to write the image, `width` by `height` pixels
+    ///
+    /// # Returns
+    ///
+    /// * `Result<(), GerberError>` - Ok once the file is written, or an error if rendering or writing failed
+    pub fn render_to_png(&mut self, commands: &[Command], path: &Path) -> Result<(), GerberError> {
+        let filename = path.to_str().ok_or_else(|| GerberError::SemanticError(
+            format!("Output path '{}' is not valid UTF-8.", path.display())
+        ))?;
+
+        if !commands.is_empty() {
+            self.push_layer(commands.to_vec(), None);
+        }
+
+        // Raylib still needs a window for its graphics context, so keep it hidden
+        unsafe {
+            raylib::ffi::SetConfigFlags(raylib::ffi::ConfigFlags::FLAG_WINDOW_HIDDEN as u32);
+        }
+        let (mut rl, thread) = init()
+            .size(self.width, self.height)
+            .title("Gerber Visualizer")
+            .build();
+
+        let image = {
+            let mut d = rl.begin_drawing(&thread);
+            self.render_layers(&mut d, &thread, self.background_color)?
+        };
+
+        image.export_image(filename);
+
+        // Raylib doesn't report export failures, so check the file was written
+        std::fs::metadata(path)?;
+
+        Ok(())
+    }
+
+    /// Render every layer and blend them into an image the size of the window
+    ///
+    /// # Arguments
+    ///
+    /// * `d` - The draw handle of the window
+    /// * `thread` - The raylib thread
+    /// * `background` - Color of the pixels no layer covers
+    ///
+    /// # Returns
+    ///
+    /// * `Result<Image, GerberError>` - The composited image, or an error if a render texture couldn't be used
+    fn render_layers(&mut self, d: &mut RaylibDrawHandle, thread: &RaylibThread, background: Color) -> Result<Image, GerberError> {
+        let mut layers = std::mem::take(&mut self.layers);
+        let drawing_color = self.drawing_color;
+        let mut coverages = Vec::new();
+
+        for layer in &mut layers {
+            let color = layer.color.unwrap_or(drawing_color);
+
+            // Layers are drawn opaque, their alpha is applied when blending
+            self.drawing_color = Color { a: 255, ..color };
+            self.swap_apertures(layer);
+            let rendered = self.render_layer(d, thread, &layer.commands);
+            self.swap_apertures(layer);
+
+            match rendered {
+                Ok(image) => {
+                    let coverage = image.get_image_data().iter().map(|pixel| *pixel != CLEAR_COLOR).collect::<Vec<_>>();
+                    coverages.push((coverage, color));
+                },
+                Err(err) => {
+                    self.drawing_color = drawing_color;
+                    self.layers = layers;
+                    return Err(err);
+                },
+            }
+        }
+        self.drawing_color = drawing_color;
+        self.layers = layers;
+
+        let mut image = Image::gen_image_color(self.width, self.height, background);
+        for (index, pixel) in composite(background, &coverages).into_iter().enumerate() {
+            let (x, y) = (index as i32 % self.width, index as i32 / self.width);
+            image.draw_pixel(x, y, pixel);
         }
+
+        Ok(image)
+    }
+
+    /// Render commands into an image the size of the window
+    ///
+    /// Objects are drawn in order with their polarity, so clear objects erase
+    /// whatever was drawn before them. Erased pixels and pixels never drawn
+    /// are left as `CLEAR_COLOR`.
+    ///
+    /// # Returns
+    ///
+    /// * `Result<Image, GerberError>` - The rendered layer, or an error if the render texture couldn't be used
+    fn render_layer(&self, d: &mut RaylibDrawHandle, thread: &RaylibThread, commands: &[Command]) -> Result<Image, GerberError> {
+        let mut target = d.load_render_texture(thread, self.width as u32, self.height as u32)
+            .map_err(GerberError::SemanticError)?;
+
+        {
+            let mut texture = d.begin_texture_mode(thread, &mut target);
+            texture.clear_background(CLEAR_COLOR);
+            self.visualize_gerber(&mut texture, commands);
+        }
+
+        let mut image = target.texture().get_texture_data().map_err(GerberError::SemanticError)?;
+
+        // Render textures are stored upside down
+        image.flip_vertical();
+        Ok(image)
     }
 }
 
 /// Enhanced version that properly visualizes all gerber commands
 impl GerberVisualizer {
     /// Draw the full gerber visualization
-    pub fn visualize_gerber(&self, d: &mut RaylibDrawHandle, commands: &[Command]) {
-        // First pass: Process aperture definitions and macros
-        // (This is already done in process_commands)
+    ///
+    /// Clear polarity objects are drawn in a marker color, so the target
+    /// should be cleared with it too and the marker replaced afterwards.
+    pub fn visualize_gerber(&self, d: &mut impl RaylibDraw, commands: &[Command]) {
+        for operation in self.draw_operations(commands) {
+            match operation {
+                DrawOperation::Line { start, end, width, color } => {
+                    d.draw_line_ex(start, end, width, color);
+                },
+                DrawOperation::Arc { center, inner_radius, outer_radius, start_angle, end_angle, color } => {
+                    // Note: Raylib's DrawArc doesn't support line thickness, so for thick lines we'd
+                    // need to implement this differently
+                    d.draw_ring_lines(center, inner_radius, outer_radius, start_angle, end_angle, 100, color);
+                },
+                DrawOperation::Flash { aperture, x, y, color } => {
+                    self.draw_aperture(d, aperture, x, y, color);
+                },
+                DrawOperation::Region { contours, color } => {
+                    for contour in contours {
+                        let points: Vec<(f64, f64)> = contour.iter().map(|v| (v.x as f64, v.y as f64)).collect();
+                        for [a, b, c] in triangulate(&points) {
+                            let vertex = |(x, y): (f64, f64)| Vector2::new(x as f32, y as f32);
+                            Self::draw_triangle_ccw(d, vertex(a), vertex(b), vertex(c), color);
+                        }
+                    }
+                },
+            }
+        }
+    }
+
+    /// Compute everything `visualize_gerber` draws, without needing a window
+    ///
+    /// The commands should have been processed first so apertures and scaling are known.
+    ///
+    /// # Returns
+    ///
+    /// * `Vec<DrawOperation>` - The draws and flashes, in drawing order
+    pub fn draw_operations(&self, commands: &[Command]) -> Vec<DrawOperation> {
+        let mut operations = Vec::new();
+        self.push_operations(commands, PlotState::default(), (0.0, 0.0), true, &mut Vec::new(), &mut operations);
 
-        // Second pass: Render all drawing operations
-        let mut current_x = 0.0;
-        let mut current_y = 0.0;
-        let mut current_aperture: Option<u32> = None;
-        let mut interpolation_mode = InterpolationMode::Linear;
+        operations
+    }
+
+    /// Compute the draw operations of commands, flashing block apertures recursively
+    ///
+    /// # Arguments
+    ///
+    /// * `commands` - The commands to draw
+    /// * `plot_state` - State the commands start from
+    /// * `offset` - Translation applied to every coordinate, in the file unit
+    /// * `flash_dark` - Whether the enclosing flash is dark, clear flashes invert the polarity of their block
+    /// * `active_blocks` - Blocks being flashed, which can't flash themselves again
+    /// * `operations` - Where the operations are appended
+    fn push_operations(
+        &self,
+        commands: &[Command],
+        mut plot_state: PlotState,
+        offset: (f64, f64),
+        flash_dark: bool,
+        active_blocks: &mut Vec<u32>,
+        operations: &mut Vec<DrawOperation>,
+    ) {
+        let mut dark = true;
+        // Contours of the region being built, if in region mode
+        let mut region: Option<Vec<Vec<Vector2>>> = None;
+        // Nesting depth of the block definitions being skipped
+        let mut block_depth = 0u32;
+        let screen_vertex = |(x, y): (f64, f64)| {
+            let (screen_x, screen_y) = self.to_screen_coords((x + offset.0) as f32, (y + offset.1) as f32);
+            Vector2::new(screen_x as f32, screen_y as f32)
+        };
 
         for cmd in commands {
+            // Blocks are drawn where they are flashed, not where they are defined
             match cmd {
-                Command::D01(op) => {
-                    // Draw line or arc
-                    if let Some(aperture_code) = current_aperture {
-                        let end_x = op.x.map(|x| x as f32).unwrap_or(current_x);
-                        let end_y = op.y.map(|y| y as f32).unwrap_or(current_y);
-
-                        match interpolation_mode {
-                            InterpolationMode::Linear => {
-                                // Draw line
-                                let (start_x, start_y) = self.to_screen_coords(current_x, current_y);
-                                let (end_x_screen, end_y_screen) = self.to_screen_coords(end_x, end_y);
-
-                                let color = if self.dark_polarity { self.drawing_color } else { self.background_color };
-
-                                // Get line width from aperture if it's a circle
-                                let line_width = if let Some(aperture) = self.aperture_definitions.get(&aperture_code) {
-                                    match aperture {
-                                        ApertureTemplate::Circle(diameter, _) => (*diameter * self.scale_factor) as f32,
-                                        _ => 1.0,
-                                    }
-                                } else {
-                                    1.0
-                                };
-
-                                d.draw_line_ex(
-                                    Vector2::new(start_x as f32, start_y as f32),
-                                    Vector2::new(end_x_screen as f32, end_y_screen as f32),
-                                    line_width,
-                                    color
-                                );
-                            },
-                            InterpolationMode::ClockwiseArc | InterpolationMode::CounterClockwiseArc => {
-                                // Draw arc if I and J are provided
-                                if let (Some(i), Some(j)) = (op.i, op.j) {
-                                    let i_val = i as f32;
-                                    let j_val = j as f32;
-
-                                    // Calculate center point
-                                    let center_x = current_x + i_val;
-                                    let center_y = current_y + j_val;
-
-                                    // Calculate radius
-                                    let radius = (i_val.powi(2) + j_val.powi(2)).sqrt();
-
-                                    // Calculate start and end angles
-                                    let start_angle = (current_y - center_y).atan2(current_x - center_x);
-                                    let end_angle = (end_y - center_y).atan2(end_x - center_x);
-
-                                    // Convert to screen coordinates
-                                    let (center_x_screen, center_y_screen) = self.to_screen_coords(center_x, center_y);
-                                    let radius_screen = radius * self.scale_factor as f32;
-
-                                    let color = if self.dark_polarity { self.drawing_color } else { self.background_color };
-
-                                    // Draw arc
-                                    let start_angle_deg = start_angle.to_degrees();
-                                    let end_angle_deg = end_angle.to_degrees();
-
-                                    // Determine direction based on interpolation mode
-                                    let (start_deg, end_deg) = match interpolation_mode {
-                                        InterpolationMode::ClockwiseArc => (end_angle_deg, start_angle_deg),
-                                        InterpolationMode::CounterClockwiseArc => (start_angle_deg, end_angle_deg),
-                                        _ => unreachable!(),
-                                    };
-
-                                    // Get line width from aperture if it's a circle
-                                    let line_width = if let Some(aperture) = self.aperture_definitions.get(&aperture_code) {
-                                        match aperture {
-                                            ApertureTemplate::Circle(diameter, _) => (*diameter * self.scale_factor) as f32,
-                                            _ => 1.0,
-                                        }
-                                    } else {
-                                        1.0
-                                    };
-
-                                    // Draw the arc
-                                    // Note: Raylib's DrawArc doesn't support line thickness, so for thick lines we'd
-                                    // need to implement this differently
-                                    d.draw_ring_lines(
-                                        Vector2::new(center_x_screen as f32, center_y_screen as f32),
-                                        radius_screen - line_width/2.0,
-                                        radius_screen + line_width/2.0,
-                                        start_deg as f32,
-                                        end_deg as f32,
-                                        100,
-                                        color
-                                    );
-                                }
-                            },
-                        }
+                Command::AB(Some(_)) => {
+                    block_depth += 1;
+                    continue;
+                },
+                Command::AB(None) => {
+                    block_depth = block_depth.saturating_sub(1);
+                    continue;
+                },
+                _ if block_depth > 0 => continue,
+                _ => {},
+            }
+
+            let start = plot_state.current_point();
+            let Ok(end) = plot_state.apply(cmd) else {
+                continue;
+            };
 
-                        // Update current position
-                        current_x = end_x;
-                        current_y = end_y;
+            match (cmd, end) {
+                (Command::D01(_), Some(end)) if region.is_some() => {
+                    // Extend the current contour, starting one at the current point if needed
+                    if let Some(contours) = region.as_mut() {
+                        if contours.is_empty() {
+                            contours.push(vec![screen_vertex(start)]);
+                        }
+                        if let Some(contour) = contours.last_mut() {
+                            match plot_state.arc(cmd, start) {
+                                // Half a pixel is as close as the screen can show
+                                Some(arc) => contour.extend(arc.approximate(0.5 / self.scale_factor).into_iter().map(screen_vertex)),
+                                None => contour.push(screen_vertex(end)),
+                            }
+                        }
                     }
                 },
-                Command::D02(op) => {
-                    // Move without drawing
-                    if let Some(x) = op.x {
-                        current_x = x as f32;
+                (Command::D01(_), Some(end)) => {
+                    // Draw line or arc
+                    let Some(aperture_code) = plot_state.current_aperture() else {
+                        continue;
+                    };
+                    let color = self.polarity_color(dark == flash_dark);
+                    let line_width = self.line_width(aperture_code);
+
+                    match plot_state.arc(cmd, start) {
+                        None => {
+                            operations.push(DrawOperation::Line {
+                                start: screen_vertex(start),
+                                end: screen_vertex(end),
+                                width: line_width,
+                                color,
+                            });
+                        },
+                        Some(arc) => {
+                            let radius_screen = (arc.radius * self.scale_factor) as f32;
+
+                            // Raylib draws from the lower to the higher angle
+                            let start_deg = arc.start_angle.min(arc.end_angle).to_degrees() as f32;
+                            let end_deg = arc.start_angle.max(arc.end_angle).to_degrees() as f32;
+
+                            operations.push(DrawOperation::Arc {
+                                center: screen_vertex(arc.center),
+                                inner_radius: radius_screen - line_width / 2.0,
+                                outer_radius: radius_screen + line_width / 2.0,
+                                start_angle: start_deg,
+                                end_angle: end_deg,
+                                color,
+                            });
+                        },
                     }
-                    if let Some(y) = op.y {
-                        current_y = y as f32;
+                },
+                (Command::D02(_), Some(end)) => {
+                    // A move inside a region starts a new contour
+                    if let Some(contours) = region.as_mut() {
+                        contours.push(vec![screen_vertex(end)]);
                     }
                 },
-                Command::D03(op) => {
-                    // Flash aperture
-                    if let Some(aperture_code) = current_aperture {
-                        let flash_x = op.x.map(|x| x as f32).unwrap_or(current_x);
-                        let flash_y = op.y.map(|y| y as f32).unwrap_or(current_y);
-
-                        self.draw_aperture(d, aperture_code, flash_x, flash_y);
+                (Command::D03(_), Some((x, y))) => {
+                    let Some(aperture_code) = plot_state.current_aperture() else {
+                        continue;
+                    };
+                    let (x, y) = (x + offset.0, y + offset.1);
 
-                        // Update current position
-                        current_x = flash_x;
-                        current_y = flash_y;
+                    match self.block_apertures.get(&aperture_code) {
+                        // Draw the block again, moved to the flash point
+                        Some(block) if !active_blocks.contains(&aperture_code) => {
+                            active_blocks.push(aperture_code);
+                            self.push_operations(block, plot_state.clone(), (x, y), dark == flash_dark, active_blocks, operations);
+                            active_blocks.pop();
+                        },
+                        Some(_) => {},
+                        // Flash aperture
+                        None => {
+                            operations.push(DrawOperation::Flash { aperture: aperture_code, x: x as f32, y: y as f32, color: self.polarity_color(dark == flash_dark) });
+                        },
                     }
                 },
-                Command::Dnn(code) => {
-                    // Set current aperture
-                    current_aperture = Some(*code);
+                (Command::G36, _) => {
+                    region = Some(Vec::new());
                 },
-                Command::G01 => {
-                    // Set linear interpolation
-                    interpolation_mode = InterpolationMode::Linear;
+                (Command::LP(polarity), _) => {
+                    dark = *polarity == Polarity::Dark;
                 },
-                Command::G02 => {
-                    // Set clockwise circular interpolation
-                    interpolation_mode = InterpolationMode::ClockwiseArc;
-                },
-                Command::G03 => {
-                    // Set counterclockwise circular interpolation
-                    interpolation_mode = InterpolationMode::CounterClockwiseArc;
+                (Command::G37, _) => {
+                    if let Some(contours) = region.take() {
+                        let color = self.polarity_color(dark == flash_dark);
+                        let contours = contours.into_iter().filter(|contour| contour.len() >= 3).collect();
+                        operations.push(DrawOperation::Region { contours, color });
+                    }
                 },
                 // Handle other commands as needed
                 _ => {},
             }
         }
     }
+
+    /// Color of objects drawn with the given polarity
+    fn polarity_color(&self, dark: bool) -> Color {
+        if dark { self.drawing_color } else { CLEAR_COLOR }
+    }
+
+    /// Stroke width of draws made with an aperture, in pixels
+    ///
+    /// Only circular apertures have a meaningful width, others draw hairlines.
+    fn line_width(&self, aperture_code: u32) -> f32 {
+        match self.aperture_definitions.get(&aperture_code) {
+            Some(ApertureTemplate::Circle(diameter, _)) => (*diameter * self.scale_factor) as f32,
+            _ => 1.0,
+        }
+    }
+}
+
+/// Blend layers over a background, each one drawn in its color where it covers
+///
+/// Colors are blended with their alpha, so where translucent layers overlap
+/// the result differs from every one of them alone.
+///
+/// # Arguments
+///
+/// * `background` - Color under every layer
+/// * `layers` - For each layer from the bottom, the pixels it covers and its color
+///
+/// # Returns
+///
+/// * `Vec<Color>` - The blended pixels, as many as the shortest layer has
+pub fn composite(background: Color, layers: &[(Vec<bool>, Color)]) -> Vec<Color> {
+    let pixel_count = layers.iter().map(|(coverage, _)| coverage.len()).min().unwrap_or(0);
+
+    (0..pixel_count)
+        .map(|index| {
+            layers.iter()
+                .filter(|(coverage, _)| coverage[index])
+                .fold(background, |under, (_, over)| blend(under, *over))
+        })
+        .collect()
+}
+
+/// Draw one color over another with its alpha
+fn blend(under: Color, over: Color) -> Color {
+    let over_alpha = over.a as f32 / 255.0;
+    let under_alpha = under.a as f32 / 255.0 * (1.0 - over_alpha);
+    let alpha = over_alpha + under_alpha;
+    if alpha == 0.0 {
+        return Color { r: 0, g: 0, b: 0, a: 0 };
+    }
+
+    let channel = |over: u8, under: u8| ((over as f32 * over_alpha + under as f32 * under_alpha) / alpha).round() as u8;
+    Color {
+        r: channel(over.r, under.r),
+        g: channel(over.g, under.g),
+        b: channel(over.b, under.b),
+        a: (alpha * 255.0).round() as u8,
+    }
 }
 
-/// Interpolation modes for drawing
-#[derive(Debug, Clone, Copy, PartialEq)]
-enum InterpolationMode {
-    Linear,
-    ClockwiseArc,
-    CounterClockwiseArc,
-}
\ No newline at end of file
+/// A single shape drawn by the visualizer, in screen coordinates
+#[derive(Debug, Clone, PartialEq)]
+pub enum DrawOperation {
+    /// A straight draw
+    Line {
+        start: Vector2,
+        end: Vector2,
+        width: f32,
+        color: Color,
+    },
+    /// A circular draw, drawn as a ring segment between the two angles in degrees
+    Arc {
+        center: Vector2,
+        inner_radius: f32,
+        outer_radius: f32,
+        start_angle: f32,
+        end_angle: f32,
+        color: Color,
+    },
+    /// A flash of an aperture, at gerber coordinates
+    Flash {
+        aperture: u32,
+        x: f32,
+        y: f32,
+        color: Color,
+    },
+    /// A filled region, with one vertex list per contour
+    Region {
+        contours: Vec<Vec<Vector2>>,
+        color: Color,
+    },
+}
+
+/// Whether a macro primitive exposes the area it covers, as thermals always do
+fn primitive_exposure(primitive: &AMPrimitive) -> bool {
+    match primitive {
+        AMPrimitive::Circle(on, ..) |
+        AMPrimitive::VectorLine(on, ..) |
+        AMPrimitive::CenterLine(on, ..) |
+        AMPrimitive::Outline(on, ..) |
+        AMPrimitive::Polygon(on, ..) => *on,
+        _ => true,
+    }
+}
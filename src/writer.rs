@@ -0,0 +1,194 @@
+//! # Gerber Writer
+//!
+//! This module serializes commands back into Gerber text, so parsed files can
+//! be modified and written out again.
+
+use crate::{Command, Gerber};
+use crate::command::{AMPrimitive, ApertureTemplate, FormatSpecification, Mirroring, Notation, Polarity, Unit, ZeroOmission};
+
+impl Gerber {
+    /// Serializes the commands into Gerber text, one command per line
+    ///
+    /// Coordinates are written with the digits of the active format specification.
+    ///
+    /// # Returns
+    ///
+    /// * `String` - The Gerber text, which parses back into the same commands
+    pub fn to_gerber_string(&self) -> String {
+        let mut format_spec = None;
+        let mut output = String::new();
+
+        for command in &self.commands {
+            if let Command::FS(spec) = command {
+                format_spec = Some(spec.clone());
+            }
+
+            output.push_str(&write_command(command, &format_spec));
+            output.push('\n');
+        }
+
+        output
+    }
+}
+
+/// Serializes a single command into Gerber text
+///
+/// # Arguments
+///
+/// * `command` - The command to serialize
+/// * `format_spec` - Format used for coordinates, or None to write their integer values as they are
+///
+/// # Returns
+///
+/// * `String` - The command in Gerber syntax
+pub fn write_command(command: &Command, format_spec: &Option<FormatSpecification>) -> String {
+    let x = |x: i64| format_spec.as_ref().map_or(x.to_string(), |spec| spec.encode_x(x));
+    let y = |y: i64| format_spec.as_ref().map_or(y.to_string(), |spec| spec.encode_y(y));
+    let coordinates = |x_opt: Option<i64>, y_opt: Option<i64>| {
+        format!(
+            "{}{}",
+            x_opt.map_or(String::new(), |value| format!("X{}", x(value))),
+            y_opt.map_or(String::new(), |value| format!("Y{}", y(value))),
+        )
+    };
+
+    match command {
+        Command::G04(comment) => format!("G04 {}*", comment),
+        Command::MO(unit) => match unit {
+            Unit::Millimeters => "%MOMM*%".to_string(),
+            Unit::Inches => "%MOIN*%".to_string(),
+        },
+        Command::FS(spec) => format!(
+            "%FS{}{}X{}{}Y{}{}*%",
+            match spec.zero_omission {
+                ZeroOmission::Leading => "L",
+                ZeroOmission::Trailing => "T",
+            },
+            match spec.notation {
+                Notation::Absolute => "A",
+                Notation::Incremental => "I",
+            },
+            spec.x_integer_digits, spec.x_decimal_digits,
+            spec.y_integer_digits, spec.y_decimal_digits,
+        ),
+        Command::AD(aperture_def) => format!("%ADD{}{}*%", aperture_def.code, write_template(&aperture_def.template)),
+        Command::AM(name, primitives) => {
+            let mut output = format!("%AM{}*", name);
+            for primitive in primitives {
+                output.push('\n');
+                output.push_str(&write_primitive(primitive));
+            }
+            output.push('%');
+            output
+        },
+        Command::Dnn(code) => format!("D{}*", code),
+        Command::G01 => "G01*".to_string(),
+        Command::G02 => "G02*".to_string(),
+        Command::G03 => "G03*".to_string(),
+        Command::G75 => "G75*".to_string(),
+        Command::G74 => "G74*".to_string(),
+        Command::G90 => "G90*".to_string(),
+        Command::G91 => "G91*".to_string(),
+        Command::IN(name) => format!("%IN{}*%", name),
+        Command::LN(name) => format!("%LN{}*%", name),
+        Command::D01(op) => {
+            let offsets = if op.i.is_some() || op.j.is_some() {
+                format!("I{}J{}", x(op.i.unwrap_or(0)), y(op.j.unwrap_or(0)))
+            } else {
+                String::new()
+            };
+            format!("{}{}D01*", coordinates(op.x, op.y), offsets)
+        },
+        Command::D02(op) => format!("{}D02*", coordinates(op.x, op.y)),
+        Command::D03(op) => format!("{}D03*", coordinates(op.x, op.y)),
+        Command::LP(polarity) => match polarity {
+            Polarity::Dark => "%LPD*%".to_string(),
+            Polarity::Clear => "%LPC*%".to_string(),
+        },
+        Command::LM(mirroring) => match mirroring {
+            Mirroring::None => "%LMN*%".to_string(),
+            Mirroring::X => "%LMX*%".to_string(),
+            Mirroring::Y => "%LMY*%".to_string(),
+            Mirroring::XY => "%LMXY*%".to_string(),
+        },
+        Command::LR(rotation) => format!("%LR{}*%", rotation),
+        Command::LS(scale) => format!("%LS{}*%", scale),
+        Command::G36 => "G36*".to_string(),
+        Command::G37 => "G37*".to_string(),
+        Command::AB(Some(code)) => format!("%ABD{}*%", code),
+        Command::AB(None) => "%AB*%".to_string(),
+        Command::SR(Some(sr)) => format!("%SRX{}Y{}I{}J{}*%", sr.x_repeats, sr.y_repeats, sr.x_step, sr.y_step),
+        Command::SR(None) => "%SR*%".to_string(),
+        Command::TF(name, fields) => format!("%TF{}*%", write_attribute(name, fields)),
+        Command::TA(name, fields) => format!("%TA{}*%", write_attribute(name, fields)),
+        Command::TO(name, fields) => format!("%TO{}*%", write_attribute(name, fields)),
+        Command::TD(name) => format!("%TD{}*%", name.as_deref().unwrap_or("")),
+        Command::M02 => "M02*".to_string(),
+        Command::M00 => "M00*".to_string(),
+        Command::M01 => "M01*".to_string(),
+        Command::Unknown(text) => text.clone(),
+    }
+}
+
+fn write_template(template: &ApertureTemplate) -> String {
+    let hole = |hole: &Option<f64>| hole.map_or(String::new(), |diameter| format!("X{}", diameter));
+
+    match template {
+        ApertureTemplate::Circle(diameter, hole_diameter) => format!("C,{}{}", diameter, hole(hole_diameter)),
+        ApertureTemplate::Rectangle(x, y, hole_diameter) => format!("R,{}X{}{}", x, y, hole(hole_diameter)),
+        ApertureTemplate::Obround(x, y, hole_diameter) => format!("O,{}X{}{}", x, y, hole(hole_diameter)),
+        ApertureTemplate::Polygon(diameter, vertices, rotation, hole_diameter) => {
+            // The rotation must be written when a hole follows it
+            let rotation = match (rotation, hole_diameter) {
+                (Some(rotation), _) => format!("X{}", rotation),
+                (None, Some(_)) => "X0".to_string(),
+                (None, None) => String::new(),
+            };
+            format!("P,{}X{}{}{}", diameter, vertices, rotation, hole(hole_diameter))
+        },
+        ApertureTemplate::Macro(name, params) if params.is_empty() => name.clone(),
+        ApertureTemplate::Macro(name, params) => {
+            let params: Vec<String> = params.iter().map(|param| param.to_string()).collect();
+            format!("{},{}", name, params.join("X"))
+        },
+    }
+}
+
+fn write_primitive(primitive: &AMPrimitive) -> String {
+    let exposure = |on: &bool| if *on { 1 } else { 0 };
+
+    match primitive {
+        AMPrimitive::Comment(comment) => format!("0 {}*", comment),
+        AMPrimitive::Circle(on, diameter, center_x, center_y, rotation) => format!(
+            "1,{},{},{},{}{}*",
+            exposure(on), diameter, center_x, center_y,
+            rotation.map_or(String::new(), |rotation| format!(",{}", rotation)),
+        ),
+        AMPrimitive::VectorLine(on, width, start_x, start_y, end_x, end_y, rotation) => format!(
+            "20,{},{},{},{},{},{},{}*",
+            exposure(on), width, start_x, start_y, end_x, end_y, rotation,
+        ),
+        AMPrimitive::CenterLine(on, width, height, center_x, center_y, rotation) => format!(
+            "21,{},{},{},{},{},{}*",
+            exposure(on), width, height, center_x, center_y, rotation,
+        ),
+        AMPrimitive::Outline(on, points, rotation) => {
+            let points: Vec<String> = points.iter().map(|(x, y)| format!("{},{}", x, y)).collect();
+            format!("4,{},{},{},{}*", exposure(on), points.len().saturating_sub(1), points.join(","), rotation)
+        },
+        AMPrimitive::Polygon(on, vertices, center_x, center_y, diameter, rotation) => format!(
+            "5,{},{},{},{},{},{}*",
+            exposure(on), vertices, center_x, center_y, diameter, rotation,
+        ),
+        AMPrimitive::Thermal(center_x, center_y, outer_diameter, inner_diameter, gap, rotation) => format!(
+            "7,{},{},{},{},{},{}*",
+            center_x, center_y, outer_diameter, inner_diameter, gap, rotation,
+        ),
+        AMPrimitive::VariableDefinition(number, expression) => format!("${}={}*", number, expression),
+        AMPrimitive::Parametric(code, expressions) => format!("{},{}*", code, expressions.join(",")),
+    }
+}
+
+fn write_attribute(name: &str, fields: &[String]) -> String {
+    fields.iter().fold(name.to_string(), |output, field| format!("{},{}", output, field))
+}
@@ -0,0 +1,77 @@
+use std::path::Path;
+use gerbers::{Gerber, Command};
+use gerbers::command::AMPrimitive;
+use gerbers::aperture_macro::thermal_polygons;
+
+#[test]
+fn test_expand_macro() {
+    let test_file = Path::new("tests/scaled_macro.gbr");
+    let gerber = Gerber::new(test_file).expect("Failed to parse Gerber file");
+
+    let primitives = gerber.expand_macro("SCALED", &[0.5]).expect("Failed to expand macro");
+
+    assert_eq!(primitives, vec![
+        AMPrimitive::Circle(true, 0.5, 0.0, 0.0, None),
+        AMPrimitive::Circle(true, 1.0, 1.5, -0.5, None),
+    ]);
+
+    assert!(gerber.expand_macro("MISSING", &[]).is_err(), "Undefined macros should be reported");
+}
+
+#[test]
+fn test_outline_vertices() {
+    let gerber = "%MOMM*%\n%FSLAX26Y26*%\n%AMPENTAGON*\n4,1,5,0,0,2,0,3,1,1,2,-1,1,0,0,15*%\nM02*\n".parse::<Gerber>()
+        .expect("Failed to parse Gerber data");
+
+    let points = vec![(0.0, 0.0), (2.0, 0.0), (3.0, 1.0), (1.0, 2.0), (-1.0, 1.0), (0.0, 0.0)];
+    assert_eq!(gerber.commands[2], Command::AM("PENTAGON".to_string(), vec![AMPrimitive::Outline(true, points, 15.0)]));
+}
+
+#[test]
+fn test_thermal_polygons() {
+    let polygons = thermal_polygons((1.0, 0.0), 0.8, 0.55, 0.125, 0.0);
+
+    assert_eq!(polygons.len(), 4, "The gaps should split the ring into four segments");
+    for polygon in &polygons {
+        for (x, y) in polygon {
+            let radius = (x - 1.0).hypot(*y);
+            assert!((radius - 0.4).abs() < 1e-9 || (radius - 0.275).abs() < 1e-9, "Point at radius {} is off the ring", radius);
+            assert!((x - 1.0).abs() >= 0.0625 - 1e-9 && y.abs() >= 0.0625 - 1e-9, "Point ({}, {}) is inside a gap", x, y);
+        }
+    }
+}
+
+#[test]
+fn test_outline_as_polygon() {
+    let square = AMPrimitive::Outline(true, vec![(1.0, 0.0), (2.0, 0.0), (2.0, 1.0), (1.0, 1.0), (1.0, 0.0)], 90.0);
+
+    let polygon = square.as_polygon().expect("Outlines have a polygon");
+
+    let expected = [(0.0, 1.0), (0.0, 2.0), (-1.0, 2.0), (-1.0, 1.0), (0.0, 1.0)];
+    assert_eq!(polygon.len(), expected.len());
+    for ((x, y), (expected_x, expected_y)) in polygon.iter().zip(expected) {
+        assert!((x - expected_x).abs() < 1e-9 && (y - expected_y).abs() < 1e-9, "Got ({}, {}), expected ({}, {})", x, y, expected_x, expected_y);
+    }
+
+    assert_eq!(AMPrimitive::Circle(true, 1.0, 0.0, 0.0, None).as_polygon(), None);
+}
+
+#[test]
+fn test_polygon_primitive_vertices() {
+    let hexagon = AMPrimitive::Polygon(true, 6, 0.0, 0.0, 2.0, 30.0);
+
+    let polygon = hexagon.as_polygon().expect("Polygons have vertices");
+
+    // Six vertices, closed by the first one again
+    assert_eq!(polygon.len(), 7);
+    assert_eq!(polygon.first(), polygon.last());
+    for (x, y) in &polygon {
+        assert!((x.hypot(*y) - 1.0).abs() < 1e-9, "Vertex ({}, {}) is off the circumscribed circle", x, y);
+    }
+    // The rotation turns the first vertex off the X axis
+    let (x, y) = polygon[0];
+    assert!((x - 30f64.to_radians().cos()).abs() < 1e-9 && (y - 0.5).abs() < 1e-9, "Got ({}, {})", x, y);
+
+    let too_many = "%MOMM*%\n%FSLAX26Y26*%\n%AMBAD*5,1,13,0,0,1,0*%\nM02*\n".parse::<Gerber>();
+    assert!(too_many.is_err(), "Polygons are limited to 12 vertices");
+}
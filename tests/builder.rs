@@ -0,0 +1,34 @@
+use gerbers::Command;
+use gerbers::builder::GerberBuilder;
+use gerbers::command::{ApertureDefinition, ApertureTemplate, D01Operation, D02Operation, Unit};
+
+#[test]
+fn test_build_square() {
+    let gerber = GerberBuilder::new()
+        .define_aperture(10, ApertureTemplate::Circle(0.1, None))
+        .select(10)
+        .move_to(0.0, 0.0)
+        .line_to(1.0, 0.0)
+        .line_to(1.0, 1.0)
+        .line_to(0.0, 1.0)
+        .line_to(0.0, 0.0)
+        .units(Unit::Millimeters)
+        .build();
+
+    let line = |x, y| Command::D01(D01Operation { x: Some(x), y: Some(y), i: None, j: None });
+
+    assert_eq!(gerber.commands.len(), 11);
+    assert_eq!(gerber.commands[0], Command::MO(Unit::Millimeters));
+    assert!(matches!(gerber.commands[1], Command::FS(_)));
+    assert_eq!(gerber.commands[2..], [
+        Command::AD(ApertureDefinition { code: 10, template: ApertureTemplate::Circle(0.1, None) }),
+        Command::Dnn(10),
+        Command::D02(D02Operation { x: Some(0), y: Some(0) }),
+        Command::G01,
+        line(1000000, 0),
+        line(1000000, 1000000),
+        line(0, 1000000),
+        line(0, 0),
+        Command::M02,
+    ]);
+}
@@ -0,0 +1,361 @@
+use std::fs::File;
+use std::path::Path;
+use gerbers::{Gerber, Command};
+use gerbers::command::{convert, ApertureDefinition, ApertureTemplate, D01Operation, D03Operation, FormatSpecification, Notation, StepAndRepeat, Unit, ZeroOmission};
+use gerbers::geometry::DrawPrimitive;
+
+#[test]
+fn test_aperture_block() {
+    let test_file = Path::new("tests/aperture_block.gbr");
+    let gerber = Gerber::new(test_file).expect("Failed to parse Gerber file");
+
+    let expected_commands = vec![
+        Command::G04("Aperture block with two flashes".to_string()),
+        Command::MO(Unit::Millimeters),
+        Command::FS(FormatSpecification {
+            x_integer_digits: 2,
+            x_decimal_digits: 6,
+            y_integer_digits: 2,
+            y_decimal_digits: 6,
+            zero_omission: ZeroOmission::Leading,
+            notation: Notation::Absolute,
+        }),
+        Command::AD(ApertureDefinition {
+            code: 10,
+            template: ApertureTemplate::Circle(0.5, None),
+        }),
+        Command::AB(Some(12)),
+        Command::Dnn(10),
+        Command::D03(D03Operation { x: Some(0), y: Some(0) }),
+        Command::D03(D03Operation { x: Some(1000000), y: Some(0) }),
+        Command::AB(None),
+        Command::Dnn(12),
+        Command::D03(D03Operation { x: Some(5000000), y: Some(5000000) }),
+        Command::M02,
+    ];
+
+    assert_eq!(gerber.commands, expected_commands, "Command list is not the same.");
+}
+
+#[test]
+fn test_aperture_attributes() {
+    let test_file = Path::new("tests/aperture_attributes.gbr");
+    let gerber = Gerber::new(test_file).expect("Failed to parse Gerber file");
+
+    let attributes: Vec<&Command> = gerber.commands.iter()
+        .filter(|command| matches!(command, Command::TA(..)))
+        .collect();
+
+    assert_eq!(attributes, vec![
+        &Command::TA(".AperFunction".to_string(), vec!["ComponentPad".to_string()]),
+        &Command::TA(".DrillTolerance".to_string(), vec![]),
+    ]);
+}
+
+#[test]
+fn test_delete_attributes() {
+    let test_file = Path::new("tests/delete_attributes.gbr");
+    let gerber = Gerber::new(test_file).expect("Failed to parse Gerber file");
+
+    let deletions: Vec<&Command> = gerber.commands.iter()
+        .filter(|command| matches!(command, Command::TD(..)))
+        .collect();
+
+    assert_eq!(deletions, vec![
+        &Command::TD(Some(".AperFunction".to_string())),
+        &Command::TD(None),
+    ]);
+}
+
+#[test]
+fn test_zero_omission() {
+    let leading = Gerber::new(Path::new("tests/leading_zero_omission.gbr")).expect("Failed to parse Gerber file");
+    let trailing = Gerber::new(Path::new("tests/trailing_zero_omission.gbr")).expect("Failed to parse Gerber file");
+
+    let format_of = |gerber: &Gerber| gerber.commands.iter().find_map(|command| match command {
+        Command::FS(format_spec) => Some(format_spec.zero_omission.clone()),
+        _ => None,
+    });
+
+    assert_eq!(format_of(&leading), Some(ZeroOmission::Leading));
+    assert_eq!(format_of(&trailing), Some(ZeroOmission::Trailing));
+
+    let flash = Command::D03(D03Operation { x: Some(25000000), y: Some(5000000) });
+    assert!(leading.commands.contains(&flash), "Leading zero omission flash was not decoded.");
+    assert!(trailing.commands.contains(&flash), "Trailing zero omission flash was not decoded.");
+}
+
+#[test]
+fn test_absolute_notation() {
+    let gerber = Gerber::new(Path::new("tests/two_square_boxes.gbr")).expect("Failed to parse Gerber file");
+
+    let notation = gerber.commands.iter().find_map(|command| match command {
+        Command::FS(format_spec) => Some(format_spec.notation.clone()),
+        _ => None,
+    });
+
+    assert_eq!(notation, Some(Notation::Absolute));
+}
+
+#[test]
+fn test_clone_command() {
+    let gerber = Gerber::new(Path::new("tests/two_square_boxes.gbr")).expect("Failed to parse Gerber file");
+
+    let original = &gerber.commands[2];
+    let copy = original.clone();
+
+    assert_eq!(&copy, original);
+}
+
+#[test]
+fn test_compare_commands() {
+    let gerber = Gerber::new(Path::new("tests/two_square_boxes.gbr")).expect("Failed to parse Gerber file");
+
+    assert!(gerber.commands[1] == Command::MO(Unit::Millimeters));
+    assert!(gerber.commands[1] != Command::MO(Unit::Inches));
+}
+
+#[test]
+fn test_parse_from_str() {
+    let gerber = "G04 In memory*\n%MOMM*%\n%FSLAX26Y26*%\nM02*\n".parse::<Gerber>()
+        .expect("Failed to parse Gerber data");
+
+    assert_eq!(gerber.commands.last(), Some(&Command::M02));
+}
+
+#[test]
+fn test_parse_streaming() {
+    let test_file = Path::new("tests/two_square_boxes.gbr");
+    let gerber = Gerber::new(test_file).expect("Failed to parse Gerber file");
+
+    let reader = File::open(test_file).expect("Failed to open Gerber file");
+    let mut count = 0;
+    Gerber::parse_streaming(reader, |_| count += 1).expect("Failed to parse Gerber file");
+
+    assert_eq!(count, gerber.commands.len(), "Every command should be handed to the callback.");
+}
+
+#[test]
+fn test_single_quadrant_mode() {
+    let gerber = "%MOMM*%\n%FSLAX26Y26*%\nG74*\nM02*\n".parse::<Gerber>()
+        .expect("Failed to parse Gerber data");
+
+    assert_eq!(gerber.commands[2], Command::G74);
+}
+
+#[test]
+fn test_deprecated_unit_codes() {
+    let gerber = "G70*\n%FSLAX26Y26*%\nG71*\nM02*\n".parse::<Gerber>()
+        .expect("Failed to parse Gerber data");
+
+    assert_eq!(gerber.commands[0], Command::MO(Unit::Inches));
+    assert_eq!(gerber.commands[2], Command::MO(Unit::Millimeters));
+}
+
+#[test]
+fn test_deprecated_notation_codes() {
+    let gerber = "%MOMM*%\n%FSLAX26Y26*%\nG91*\nG90*\nM02*\n".parse::<Gerber>()
+        .expect("Failed to parse Gerber data");
+
+    assert_eq!(gerber.commands[2], Command::G91);
+    assert_eq!(gerber.commands[3], Command::G90);
+}
+
+#[test]
+fn test_attribute_comment() {
+    let gerber = "G04 #@! TF.FileFunction,Copper,L1,Top*\nG04 Just a comment*\n%MOMM*%\n%FSLAX26Y26*%\nM02*\n".parse::<Gerber>()
+        .expect("Failed to parse Gerber data");
+
+    assert_eq!(gerber.commands[0], Command::TF(
+        ".FileFunction".to_string(),
+        vec!["Copper".to_string(), "L1".to_string(), "Top".to_string()],
+    ));
+    assert_eq!(gerber.commands[1], Command::G04("Just a comment".to_string()));
+}
+
+#[test]
+fn test_copy_operation() {
+    let original = D01Operation { x: Some(1), y: Some(2), i: None, j: None };
+
+    let mut copy = original;
+    copy.x = Some(10);
+
+    assert_eq!(original.x, Some(1), "The original must not change with its copy");
+    assert_eq!(copy.x, Some(10));
+    assert_eq!(copy.y, original.y);
+}
+
+#[test]
+fn test_polygon_optional_parameters() {
+    let gerber = "%MOMM*%\n%FSLAX26Y26*%\n%ADD10P,1.5X6X30*%\n%ADD11P,1.5X6X30X0.4*%\nM02*\n".parse::<Gerber>()
+        .expect("Failed to parse Gerber data");
+
+    assert_eq!(gerber.aperture(10), Some(&ApertureTemplate::Polygon(1.5, 6, Some(30.0), None)), "A lone third parameter is the rotation");
+    assert_eq!(gerber.aperture(11), Some(&ApertureTemplate::Polygon(1.5, 6, Some(30.0), Some(0.4))));
+
+    let error = "%MOMM*%\n%FSLAX26Y26*%\n%ADD10P,1.5X2*%\nM02*\n".parse::<Gerber>().err().expect("Expected an invalid vertex count");
+    assert!(error.to_string().contains("Polygon vertices"), "Unexpected error: {}", error);
+}
+
+#[test]
+fn test_macro_named_like_template() {
+    let gerber = "%MOMM*%\n%FSLAX26Y26*%\n%AMC5*1,1,0.5,0,0*%\n%AMR2X3*21,1,2,3,0,0,0*%\n%ADD10C5*%\n%ADD11R2X3*%\nM02*\n".parse::<Gerber>()
+        .expect("Failed to parse Gerber data");
+
+    assert_eq!(gerber.aperture(10), Some(&ApertureTemplate::Macro("C5".to_string(), vec![])), "A macro named like a circle is not a circle");
+    assert_eq!(gerber.aperture(11), Some(&ApertureTemplate::Macro("R2X3".to_string(), vec![])));
+}
+
+#[test]
+fn test_parse_with_spans() {
+    let content = "G04 Spans*\n%MOMM*%\n%FSLAX26Y26*%\nM02*\n";
+
+    let commands = Gerber::parse_with_spans(content).expect("Failed to parse Gerber data");
+
+    let (command, span) = &commands[0];
+    assert_eq!(command, &Command::G04("Spans".to_string()));
+    assert_eq!(&content[span.clone()], "G04 Spans*");
+    assert_eq!(commands.len(), 4);
+}
+
+#[test]
+fn test_step_and_repeat_statement() {
+    let gerber = "%MOMM*%\n%FSLAX26Y26*%\n%SRX2Y1I5.0J0*%\n%SR*%\nM02*\n".parse::<Gerber>()
+        .expect("Failed to parse Gerber data");
+
+    let step_and_repeat = StepAndRepeat { x_repeats: 2, y_repeats: 1, x_step: 5.0, y_step: 0.0 };
+    assert_eq!(gerber.commands[2..4], [Command::SR(Some(step_and_repeat)), Command::SR(None)]);
+}
+
+#[test]
+fn test_unknown_statement() {
+    let content = "%MOMM*%\n%FSLAX26Y26*%\n%IPPOS*%\nM02*\n";
+    let gerber = content.parse::<Gerber>().expect("Failed to parse Gerber data");
+
+    assert_eq!(gerber.commands[2], Command::Unknown("%IPPOS*%".to_string()), "Uninterpreted statements should be kept");
+    assert_eq!(gerber.to_gerber_string(), content, "Uninterpreted statements should be written back as they are");
+}
+
+#[test]
+fn test_bare_flash() {
+    let gerber = "%MOMM*%\n%FSLAX26Y26*%\n%ADD10C,0.1*%\nD10*\nX1000000Y2000000D03*\nD03*\nM02*\n".parse::<Gerber>()
+        .expect("Failed to parse Gerber data");
+
+    assert_eq!(gerber.commands[5], Command::D03(D03Operation { x: None, y: None }), "A bare D03 is a flash, not an aperture selection");
+
+    let flashes: Vec<DrawPrimitive> = gerber.draw_primitives().collect();
+    assert_eq!(flashes, vec![
+        DrawPrimitive::Flash { at: (1.0, 2.0), aperture: 10 },
+        DrawPrimitive::Flash { at: (1.0, 2.0), aperture: 10 },
+    ]);
+}
+
+#[test]
+fn test_stop_codes() {
+    let gerber = "%MOMM*%\n%FSLAX26Y26*%\nM00*\n".parse::<Gerber>()
+        .expect("Failed to parse Gerber data");
+
+    assert_eq!(gerber.commands[2], Command::M00);
+    assert!(gerber.commands[2].is_end_of_file());
+
+    let gerber = "%MOMM*%\n%FSLAX26Y26*%\nM01*\nM02*\n".parse::<Gerber>()
+        .expect("Failed to parse Gerber data");
+
+    assert_eq!(&gerber.commands[2..], &[Command::M01, Command::M02]);
+}
+
+#[test]
+fn test_combined_plot_mode() {
+    let gerber = "%MOMM*%\n%FSLAX26Y26*%\n%ADD10C,0.1*%\nD10*\nX1000000Y0D02*\nG75*\nG03X0Y1000000I-1000000J0D01*\nM02*\n".parse::<Gerber>()
+        .expect("Failed to parse Gerber data");
+
+    assert_eq!(&gerber.commands[6..8], &[
+        Command::G03,
+        Command::D01(D01Operation { x: Some(0), y: Some(1000000), i: Some(-1000000), j: Some(0) }),
+    ], "The plot mode should be set before the draw");
+}
+
+#[test]
+fn test_large_coordinate() {
+    let gerber = "%MOMM*%\n%FSLAX66Y66*%\n%ADD10C,0.1*%\nD10*\nX999999999999Y-999999999999D03*\nM02*\n".parse::<Gerber>()
+        .expect("Failed to parse Gerber data");
+
+    assert_eq!(gerber.commands[4], Command::D03(D03Operation { x: Some(999999999999), y: Some(-999999999999) }));
+}
+
+#[test]
+fn test_image_and_load_names() {
+    let gerber = "%INPANEL_1*%\n%LNTOPCOPPER*%\n%MOMM*%\n%FSLAX26Y26*%\nM02*\n".parse::<Gerber>()
+        .expect("Failed to parse Gerber data");
+
+    assert_eq!(gerber.commands[0], Command::IN("PANEL_1".to_string()));
+    assert_eq!(gerber.commands[1], Command::LN("TOPCOPPER".to_string()));
+
+    let reparsed: Gerber = gerber.to_gerber_string().parse().expect("Failed to parse written Gerber");
+    assert_eq!(reparsed.commands, gerber.commands);
+}
+
+#[test]
+fn test_deprecated_format_codes() {
+    let content = "%MOMM*%\n%FSDAN2X26Y26*%\nX1500000Y0D02*\nM02*\n";
+
+    let gerber = content.parse::<Gerber>().expect("Deprecated FS codes should be accepted");
+
+    let expected_format = FormatSpecification {
+        x_integer_digits: 2,
+        x_decimal_digits: 6,
+        y_integer_digits: 2,
+        y_decimal_digits: 6,
+        zero_omission: ZeroOmission::Leading,
+        notation: Notation::Absolute,
+    };
+    assert_eq!(gerber.commands[1], Command::FS(expected_format));
+
+    let warnings: Vec<String> = gerber.warnings().iter().map(|warning| warning.to_string()).collect();
+    assert_eq!(warnings, vec!["Command 1: deprecated FS codes D, N2 were ignored.".to_string()]);
+}
+
+#[test]
+fn test_display_aperture_definition() {
+    let command = Command::AD(ApertureDefinition {
+        code: 11,
+        template: ApertureTemplate::Rectangle(1.5, 0.8, Some(0.3)),
+    });
+
+    assert_eq!(command.to_string(), "Define aperture D11: rectangle, 1.5 x 0.8, hole 0.3");
+}
+
+#[test]
+fn test_crlf_and_trailing_whitespace() {
+    let content = std::fs::read_to_string("tests/two_square_boxes.gbr").expect("Failed to read Gerber file");
+    let gerber = content.parse::<Gerber>().expect("Failed to parse Gerber data");
+
+    let crlf = content.replace('\n', "\r\n").parse::<Gerber>().expect("CRLF line endings should be accepted");
+    assert_eq!(crlf.commands, gerber.commands);
+
+    let padded = content.replace("D01*", "D01 *  ").replace("example*%", "example *%").replace("*%", "*% \t").replace('\n', "\r\n");
+    let padded = padded.parse::<Gerber>().expect("Whitespace around terminators should be accepted");
+    assert_eq!(padded.commands, gerber.commands);
+}
+
+#[test]
+fn test_unit_conversion() {
+    assert_eq!(Unit::Inches.mm_per_unit(), 25.4);
+    assert_eq!(Unit::Millimeters.mm_per_unit(), 1.0);
+
+    assert_eq!(convert(1.0, Unit::Inches, Unit::Millimeters), 25.4);
+    assert!((convert(25.4, Unit::Millimeters, Unit::Inches) - 1.0).abs() < 1e-12);
+    assert_eq!(convert(3.0, Unit::Inches, Unit::Inches), 3.0);
+}
+
+#[test]
+fn test_byte_order_mark() {
+    let test_file = Path::new("tests/two_square_boxes.gbr");
+    let gerber = Gerber::new(test_file).expect("Failed to parse Gerber file");
+
+    let mut content = b"\xEF\xBB\xBF".to_vec();
+    content.extend(std::fs::read(test_file).expect("Failed to read Gerber file"));
+    let with_bom = Gerber::from_reader(content.as_slice()).expect("The byte order mark should be skipped");
+
+    assert_eq!(with_bom.commands, gerber.commands);
+}
@@ -0,0 +1,19 @@
+use std::fs;
+use gerbers::Gerber;
+use gerbers::geometry::DrawPrimitive;
+
+#[test]
+fn test_diff_added_flash() {
+    let content = fs::read_to_string("tests/polarities_and_apertures.gbr").expect("Failed to read Gerber file");
+    let revised = content.replace("M02*", "X1000000Y1000000D03*\nM02*");
+
+    let original = content.parse::<Gerber>().expect("Failed to parse Gerber data");
+    let revised = revised.parse::<Gerber>().expect("Failed to parse Gerber data");
+
+    let diff = original.diff(&revised);
+
+    assert_eq!(diff.added_flashes, vec![DrawPrimitive::Flash { at: (1.0, 1.0), aperture: 19 }]);
+    assert!(diff.removed_flashes.is_empty() && diff.added_traces.is_empty() && diff.removed_traces.is_empty());
+    assert!(diff.changed_apertures.is_empty());
+    assert!(original.diff(&original).is_empty(), "A file should not differ from itself");
+}
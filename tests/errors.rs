@@ -0,0 +1,87 @@
+use gerbers::{Gerber, Command};
+use gerbers::error::GerberError;
+
+#[test]
+fn test_error_line_number() {
+    let content = "G04 Broken coordinate*\n%MOMM*%\n%FSLAX26Y26*%\n%ADD10C,0.5*%\nD10*\nX99999999999999999999Y0D03*\nM02*\n";
+
+    match content.parse::<Gerber>() {
+        Err(GerberError::ParseError { line, column, .. }) => assert_eq!((line, column), (6, 2), "The error should point at the coordinate"),
+        Err(err) => panic!("Unexpected error: {}", err),
+        Ok(_) => panic!("Broken coordinate was accepted"),
+    }
+}
+
+#[test]
+fn test_collect_all_errors() {
+    let content = "G04 Two broken coordinates*\n%MOMM*%\n%FSLAX26Y26*%\n%ADD10C,0.5*%\nD10*\nX99999999999999999999Y0D03*\nX0Y0D03*\nX0Y99999999999999999999D03*\nM02*\n";
+
+    let (gerber, errors) = Gerber::parse_collecting(content);
+
+    assert_eq!(errors.len(), 2, "Both malformed commands should be reported");
+    assert_eq!(gerber.commands.len(), 7, "Malformed commands should be skipped");
+    assert_eq!(gerber.commands.last(), Some(&Command::M02));
+}
+
+#[test]
+fn test_collect_past_grammar_errors() {
+    let content = "%MOMM*%\n%FSLAX26Y26*%\nGARBAGE*\n%ADD10C,0.5*%\nD10*\nX99999999999999999999Y0D03*\nX0Y0D03*\nM02*\n";
+
+    let (gerber, errors) = Gerber::parse_collecting(content);
+
+    assert_eq!(errors.len(), 2, "Both the unknown statement and the broken coordinate should be reported: {:?}", errors);
+    assert!(matches!(errors[0], GerberError::ParseError { line: 3, column: 1, .. }), "Unexpected error: {}", errors[0]);
+    assert!(matches!(errors[1], GerberError::ParseError { line: 6, column: 2, .. }), "Unexpected error: {}", errors[1]);
+    assert_eq!(gerber.commands.len(), 6, "The statements around the errors should be kept: {:?}", gerber.commands);
+    assert!(matches!(gerber.commands[2], Command::AD(_)));
+    assert_eq!(gerber.commands.last(), Some(&Command::M02));
+}
+
+#[test]
+fn test_format_digits_out_of_range() {
+    let content = "%MOMM*%\n%FSLAX59Y99*%\nM02*\n";
+
+    let error = content.parse::<Gerber>().err().expect("Out of range digits were accepted");
+
+    assert!(error.to_string().contains("X decimal digits in FS command must be between 1 and 6, got 9"), "Unexpected error: {}", error);
+    assert!(matches!(error, GerberError::ParseError { line: 2, column: 8, .. }), "The error should point at the digits: {:?}", error);
+}
+
+#[test]
+fn test_coordinate_overflow() {
+    let content = "%MOMM*%\n%FSLAX66Y66*%\nX99999999999999999999D02*\nM02*\n";
+
+    let error = content.parse::<Gerber>().err().expect("Overflowing coordinate was accepted");
+
+    assert!(error.to_string().contains("X coordinate '99999999999999999999' is out of range"), "Unexpected error: {}", error);
+}
+
+#[test]
+fn test_error_snippet() {
+    let content = "%MOMM*%\n%FSLAX26Y26*%\n%ADD10C,0.5*%\nD10*\nX0Y0D03*\n  X1Y1D99*\nM02*\n";
+
+    let error = content.parse::<Gerber>().err().expect("Unknown operation was accepted");
+
+    let message = error.to_string();
+    assert!(message.contains("6 |   X1Y1D99*\n  |       ^"), "Unexpected error: {}", message);
+}
+
+#[test]
+fn test_reserved_aperture_code() {
+    let content = "%MOMM*%\n%FSLAX26Y26*%\n%ADD5C,0.1*%\nM02*\n";
+
+    let error = content.parse::<Gerber>().err().expect("Reserved aperture code was accepted");
+
+    assert!(error.to_string().contains("Aperture code 'D5' in AD command is reserved"), "Unexpected error: {}", error);
+    assert!(matches!(error, GerberError::ParseError { line: 3, column: 4, .. }), "The error should point at the code: {:?}", error);
+    assert!("%MOMM*%\n%FSLAX26Y26*%\nD07*\nM02*\n".parse::<Gerber>().is_err(), "Selecting a reserved aperture code should fail");
+}
+
+#[test]
+fn test_reserved_block_aperture_code() {
+    let content = "%MOMM*%\n%FSLAX26Y26*%\n%ABD5*%\n%ADD10C,0.5*%\nD10*\nX0Y0D03*\n%AB*%\nM02*\n";
+
+    let error = content.parse::<Gerber>().err().expect("Reserved block aperture code was accepted");
+
+    assert!(error.to_string().contains("Aperture code 'D5' in AB command is reserved"), "Unexpected error: {}", error);
+}
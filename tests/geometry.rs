@@ -0,0 +1,165 @@
+use std::path::Path;
+use std::f64::consts::FRAC_PI_2;
+use gerbers::Gerber;
+use gerbers::geometry::{ContourSegment, DrawPrimitive, InterpolationMode, PlotState};
+
+#[test]
+fn test_resolved_coordinates() {
+    let test_file = Path::new("tests/two_square_boxes.gbr");
+    let gerber = Gerber::new(test_file).expect("Failed to parse Gerber file");
+
+    let points = gerber.resolved_coordinates().expect("Failed to resolve coordinates");
+
+    assert_eq!(points.len(), 10, "Every operation should resolve to a point");
+    assert_eq!(points[0], (0.0, 0.0));
+    assert_eq!(points[1], (5.0, 0.0));
+    // Omitted X is carried forward from the previous operation
+    assert_eq!(points[2], (5.0, 5.0));
+}
+
+#[test]
+fn test_bounding_box() {
+    let test_file = Path::new("tests/polarities_and_apertures.gbr");
+    let gerber = Gerber::new(test_file).expect("Failed to parse Gerber file");
+
+    let (min_x, min_y, max_x, max_y) = gerber.bounding_box().expect("Expected drawable operations");
+
+    assert!(min_x.is_finite() && min_y.is_finite() && max_x.is_finite() && max_y.is_finite());
+    assert!(min_x < max_x && min_y < max_y, "Bounds are not ordered");
+    // The draw along X=0 uses the 0.1mm D10 aperture
+    assert!((min_x + 0.05).abs() < 1e-9);
+}
+
+#[test]
+fn test_notation_switch() {
+    let gerber = "%MOMM*%\n%FSLAX26Y26*%\nX1000000Y1000000D02*\nG91*\nX1000000Y0D02*\nG90*\nX0Y0D02*\nM02*\n".parse::<Gerber>()
+        .expect("Failed to parse Gerber data");
+
+    let points = gerber.resolved_coordinates().expect("Failed to resolve coordinates");
+
+    assert_eq!(points, vec![(1.0, 1.0), (2.0, 1.0), (0.0, 0.0)], "G91 should make coordinates relative until G90");
+}
+
+#[test]
+fn test_arc_segment() {
+    // Counter-clockwise quarter circle from (1, 0) to (0, 1) around the origin
+    let arc = Gerber::arc_segment((1.0, 0.0), (0.0, 1.0), -1.0, 0.0, false, false);
+
+    assert!((arc.center.0).abs() < 1e-9 && (arc.center.1).abs() < 1e-9, "Wrong center: {:?}", arc.center);
+    assert!((arc.radius - 1.0).abs() < 1e-9);
+    assert!((arc.sweep() - FRAC_PI_2).abs() < 1e-9);
+
+    // Single quadrant offsets are unsigned, the center is found from the end points
+    let arc = Gerber::arc_segment((1.0, 0.0), (0.0, 1.0), 1.0, 0.0, false, true);
+
+    assert!((arc.center.0).abs() < 1e-9 && (arc.center.1).abs() < 1e-9, "Wrong center: {:?}", arc.center);
+    assert!((arc.radius - 1.0).abs() < 1e-9);
+}
+
+#[test]
+fn test_trace_lengths() {
+    let test_file = Path::new("tests/two_square_boxes.gbr");
+    let gerber = Gerber::new(test_file).expect("Failed to parse Gerber file");
+
+    let lengths = gerber.trace_lengths();
+
+    // Two 5mm squares drawn with D10
+    assert_eq!(lengths.len(), 1);
+    assert!((lengths[&10] - 40.0).abs() < 1e-9, "Wrong total length: {}", lengths[&10]);
+}
+
+#[test]
+fn test_draw_primitives() {
+    let test_file = Path::new("tests/two_square_boxes.gbr");
+    let gerber = Gerber::new(test_file).expect("Failed to parse Gerber file");
+
+    let primitives: Vec<DrawPrimitive> = gerber.draw_primitives().collect();
+
+    assert_eq!(primitives.len(), 8, "Moves should not yield primitives");
+    assert!(primitives.iter().all(|primitive| matches!(primitive, DrawPrimitive::Line { aperture: 10, .. })));
+    assert_eq!(primitives[0], DrawPrimitive::Line { from: (0.0, 0.0), to: (5.0, 0.0), aperture: 10 });
+}
+
+#[test]
+fn test_region_areas() {
+    let gerber = Gerber::new(Path::new("tests/non-overlapping_contour.gbr")).expect("Failed to parse Gerber file");
+
+    let areas = gerber.region_areas();
+
+    // Both contours are drawn clockwise
+    assert_eq!(areas.len(), 2);
+    assert!((areas[0] + 100.0).abs() < 1e-9, "Wrong square area: {}", areas[0]);
+    assert!((areas[1] + 32.0).abs() < 1e-9, "Wrong diamond area: {}", areas[1]);
+
+    // A half disc of radius 1, closed by its diameter
+    let gerber = "%MOMM*%\n%FSLAX26Y26*%\nG75*\nG36*\nX1000000Y0D02*\nG03X-1000000Y0I-1000000J0D01*\nG01*\nX1000000D01*\nG37*\nM02*\n".parse::<Gerber>()
+        .expect("Failed to parse Gerber data");
+    assert!((gerber.region_areas()[0] - FRAC_PI_2).abs() < 1e-9);
+}
+
+#[test]
+fn test_plot_state() {
+    let gerber = Gerber::new(Path::new("tests/two_square_boxes.gbr")).expect("Failed to parse Gerber file");
+
+    let mut plot_state = PlotState::default();
+    for command in &gerber.commands {
+        plot_state.apply(command).expect("Failed to apply command");
+    }
+
+    assert_eq!(plot_state.current_point(), (6.0, 0.0));
+    assert_eq!(plot_state.current_aperture(), Some(10));
+    assert_eq!(plot_state.interpolation_mode(), InterpolationMode::Linear);
+}
+
+#[test]
+fn test_flash_points() {
+    let gerber = Gerber::new(Path::new("tests/polarities_and_apertures.gbr")).expect("Failed to parse Gerber file");
+
+    let points = gerber.flash_points();
+
+    assert_eq!(points.len(), gerber.flashes().count(), "Every flash should resolve to a point");
+    assert_eq!(points[0], (10.0, 10.0));
+    // Omitted Y is carried forward from the previous flash
+    assert_eq!(points[1], (20.0, 10.0));
+}
+
+#[test]
+fn test_region_triangles() {
+    let gerber = "%MOMM*%\n%FSLAX26Y26*%\nG36*\nX0Y0D02*\nG01*\nX1000000D01*\nY1000000D01*\nX0D01*\nY0D01*\nG37*\nM02*\n".parse::<Gerber>()
+        .expect("Failed to parse Gerber data");
+
+    let primitives: Vec<DrawPrimitive> = gerber.draw_primitives().collect();
+
+    let [DrawPrimitive::RegionFill { triangles, .. }] = primitives.as_slice() else {
+        panic!("Expected a single region, got {:?}", primitives);
+    };
+    assert_eq!(triangles.len(), 2, "A square splits into two triangles");
+
+    // The triangles cover the whole square
+    let area: f64 = triangles.iter()
+        .map(|[a, b, c]| ((b.0 - a.0) * (c.1 - a.1) - (c.0 - a.0) * (b.1 - a.1)).abs() / 2.0)
+        .sum();
+    assert!((area - 1.0).abs() < 1e-9, "Wrong covered area: {}", area);
+}
+
+#[test]
+fn test_region_arc_approximation() {
+    // A unit square with its top side bulging up in a half circle
+    let gerber = "%MOMM*%\n%FSLAX26Y26*%\nG75*\nG36*\nX0Y0D02*\nG01*\nY1000000D01*\nG02*\nX1000000I500000J0D01*\nG01*\nY0D01*\nX0D01*\nG37*\nM02*\n".parse::<Gerber>()
+        .expect("Failed to parse Gerber data");
+
+    let primitives: Vec<DrawPrimitive> = gerber.draw_primitives().arc_tolerance(0.01).collect();
+
+    let [DrawPrimitive::RegionFill { contours, segments, triangles }] = primitives.as_slice() else {
+        panic!("Expected a single region, got {:?}", primitives);
+    };
+    assert_eq!(segments[0][1], ContourSegment::Arc { to: (1.0, 1.0), center: (0.5, 1.0), clockwise: true });
+    assert!(contours[0].len() > 5, "The arc should add vertices, got {:?}", contours[0]);
+    assert!(contours[0].iter().any(|(_, y)| *y > 1.4), "The boundary should follow the bulge");
+
+    // The triangles cover the square and the half disc, within the tolerance
+    let area: f64 = triangles.iter()
+        .map(|[a, b, c]| ((b.0 - a.0) * (c.1 - a.1) - (c.0 - a.0) * (b.1 - a.1)).abs() / 2.0)
+        .sum();
+    assert!((area - (1.0 + std::f64::consts::PI / 8.0)).abs() < 0.02, "Wrong covered area: {}", area);
+}
@@ -0,0 +1,35 @@
+#![cfg(feature = "job")]
+
+use gerbers::Command;
+use gerbers::job::{FileFunction, GerberJob};
+
+#[test]
+fn test_load_job() {
+    let job = GerberJob::from_path("tests/board.gbrjob").expect("Failed to read job file");
+
+    assert_eq!(job.project.as_deref(), Some("Sample"));
+    assert_eq!(job.size, Some((50.0, 40.0)));
+    assert_eq!(job.layer_count, Some(2));
+    assert_eq!(job.files[1].polarity.as_deref(), Some("Negative"));
+
+    let layers = job.load_all();
+
+    assert_eq!(layers.len(), 2);
+    assert_eq!(layers[0].0, FileFunction::Copper { layer: 1, side: "Top".to_string() });
+    assert_eq!(layers[1].0, FileFunction::SolderMask("Top".to_string()));
+    let copper = layers[0].1.as_ref().expect("Failed to parse the copper layer");
+    assert_eq!(copper.commands.last(), Some(&Command::M02));
+}
+
+#[test]
+fn test_load_all_reports_missing_files() {
+    let path = std::env::temp_dir().join("gerbers_missing_layer.gbrjob");
+    std::fs::write(&path, r#"{"FilesAttributes": [{"Path": "missing_layer.gbr", "FileFunction": "Copper,L1,Top"}]}"#)
+        .expect("Failed to write job file");
+    let job = GerberJob::from_path(&path).expect("Failed to read job file");
+
+    let layers = job.load_all();
+
+    assert_eq!(layers.len(), 1, "The missing layer should still be listed");
+    assert!(layers[0].1.is_err());
+}
@@ -0,0 +1,13 @@
+#![cfg(not(feature = "visualizer"))]
+
+use gerbers::{Gerber, Command};
+
+#[test]
+fn test_parse_without_visualizer() {
+    let gerber = "%MOMM*%\n%FSLAX26Y26*%\n%ADD10C,0.1*%\nD10*\nX0Y0D03*\nM02*\n".parse::<Gerber>()
+        .expect("Failed to parse Gerber data");
+
+    assert_eq!(gerber.commands.last(), Some(&Command::M02));
+    assert!(gerber.validate().is_empty());
+    assert_eq!(gerber.flash_counts().get(&10), Some(&1));
+}
@@ -0,0 +1,108 @@
+use std::path::Path;
+use gerbers::Gerber;
+use gerbers::command::{ApertureTemplate, Unit};
+
+#[test]
+fn test_aperture_map() {
+    let test_file = Path::new("tests/polarities_and_apertures.gbr");
+    let gerber = Gerber::new(test_file).expect("Failed to parse Gerber file");
+
+    let apertures = gerber.aperture_map();
+
+    assert_eq!(apertures.get(&12), Some(&ApertureTemplate::Rectangle(0.6, 0.6, None)));
+    assert_eq!(gerber.aperture(12), apertures.get(&12));
+    assert_eq!(gerber.aperture(99), None);
+}
+
+#[test]
+fn test_flash_counts() {
+    let test_file = Path::new("tests/polarities_and_apertures.gbr");
+    let gerber = Gerber::new(test_file).expect("Failed to parse Gerber file");
+
+    let counts = gerber.flash_counts();
+
+    assert_eq!(counts.get(&11), Some(&7));
+    assert_eq!(counts.get(&10), None, "D10 is only used for draws");
+}
+
+#[test]
+fn test_comments() {
+    let test_file = Path::new("tests/polarities_and_apertures.gbr");
+    let gerber = Gerber::new(test_file).expect("Failed to parse Gerber file");
+
+    let comments: Vec<&str> = gerber.comments().collect();
+
+    assert_eq!(comments, vec!["Ucamco ex. 2: Shapes", "Define Apertures", "Start image generation"]);
+    assert_eq!(gerber.apertures().count(), 8);
+    assert_eq!(gerber.flashes().count(), 14);
+}
+
+#[test]
+fn test_stats() {
+    let test_file = Path::new("tests/polarities_and_apertures.gbr");
+    let gerber = Gerber::new(test_file).expect("Failed to parse Gerber file");
+
+    let stats = gerber.stats();
+
+    assert_eq!(stats.apertures, 8);
+    assert_eq!(stats.arcs, 1, "Only the circle drawn outside the regions is an arc draw");
+    assert_eq!(stats.regions, 2);
+    assert_eq!(stats.flashes, 14);
+    assert_eq!(stats.bounding_box, gerber.bounding_box());
+}
+
+#[test]
+fn test_format() {
+    let gerber = Gerber::new(Path::new("tests/two_square_boxes.gbr")).expect("Failed to parse Gerber file");
+
+    let format = gerber.format().expect("The format should be consistent").expect("The file has an FS command");
+    assert_eq!((format.x_integer_digits, format.x_decimal_digits), (2, 6));
+    assert_eq!((format.y_integer_digits, format.y_decimal_digits), (2, 6));
+
+    let conflicting = "%MOMM*%\n%FSLAX26Y26*%\n%FSLAX36Y36*%\nM02*\n".parse::<Gerber>()
+        .expect("Failed to parse Gerber data");
+    assert!(conflicting.format().is_err());
+}
+
+#[test]
+fn test_unit() {
+    let gerber = Gerber::new(Path::new("tests/two_square_boxes.gbr")).expect("Failed to parse Gerber file");
+
+    assert_eq!(gerber.unit().expect("The unit should be consistent"), Some(Unit::Millimeters));
+
+    let conflicting = "%MOMM*%\n%FSLAX26Y26*%\n%MOIN*%\nM02*\n".parse::<Gerber>()
+        .expect("Failed to parse Gerber data");
+    assert!(conflicting.unit().is_err());
+}
+
+#[test]
+fn test_apertures_mm() {
+    let gerber = "%MOIN*%\n%FSLAX25Y25*%\n%ADD10C,0.1*%\n%ADD11R,0.05X0.1*%\nM02*\n".parse::<Gerber>()
+        .expect("Failed to parse Gerber data");
+
+    let apertures = gerber.apertures_mm();
+
+    assert_eq!(apertures[0], (10, ApertureTemplate::Circle(2.54, None)));
+    assert_eq!(apertures[1], (11, ApertureTemplate::Rectangle(1.27, 2.54, None)));
+}
+
+#[test]
+fn test_unused_apertures() {
+    let gerber = "%MOMM*%\n%FSLAX26Y26*%\n%ADD10C,0.1*%\n%ADD11C,0.2*%\nD10*\nX0Y0D03*\nM02*\n".parse::<Gerber>()
+        .expect("Failed to parse Gerber data");
+
+    assert_eq!(gerber.unused_apertures(), vec![11]);
+}
+
+#[test]
+fn test_source_line() {
+    let gerber = Gerber::new(Path::new("tests/two_square_boxes.gbr")).expect("Failed to parse Gerber file");
+
+    // The fifth command is the LP on line 6, after a blank line
+    assert_eq!(gerber.source_line(4), Some(6));
+    assert_eq!(gerber.source_line(10), Some(13));
+    assert_eq!(gerber.source_line(gerber.commands.len()), None);
+
+    let built = Gerber::from(gerber.commands.clone());
+    assert_eq!(built.source_line(4), None, "Commands that weren't parsed have no line");
+}
@@ -0,0 +1,30 @@
+use gerbers::Gerber;
+
+#[test]
+fn test_clear_polarity() {
+    // A 4mm dark square with a 2mm clear square on its middle
+    let gerber = "%MOMM*%\n%FSLAX26Y26*%\n%ADD10R,4X4*%\n%ADD11R,2X2*%\nD10*\nX0Y0D03*\n%LPC*%\nD11*\nX0Y0D03*\nM02*\n".parse::<Gerber>()
+        .expect("Failed to parse Gerber data");
+
+    let raster = gerber.rasterize(254.0);
+
+    assert_eq!((raster.width, raster.height), (40, 40));
+    assert!(!raster.covers(0.0, 0.0), "The clear square should leave a hole");
+    assert!(raster.covers(1.5, 1.5), "The rest of the dark square should stay");
+    assert!(!raster.pixel(20, 20));
+    assert!(raster.pixel(2, 2));
+}
+
+#[test]
+fn test_macro_exposure_off() {
+    // A 4mm disc with a 1mm exposure off circle on its middle, flashed alone
+    // and over a small dark square
+    let gerber = "%MOMM*%\n%FSLAX26Y26*%\n%AMRING*1,1,4,0,0*1,0,1,0,0*%\n%ADD10RING*%\n%ADD11R,0.5X0.5*%\nD10*\nX0Y0D03*\nD11*\nX10000000Y0D03*\nD10*\nX10000000Y0D03*\nM02*\n".parse::<Gerber>()
+        .expect("Failed to parse Gerber data");
+
+    let raster = gerber.rasterize(254.0);
+
+    assert!(!raster.covers(0.0, 0.0), "The exposure off circle should clear the middle of the disc");
+    assert!(raster.covers(1.5, 0.0), "The rest of the disc should stay");
+    assert!(raster.covers(10.0, 0.0), "The cutout should only clear the aperture itself");
+}
@@ -0,0 +1,15 @@
+#![cfg(feature = "serde")]
+
+use std::path::Path;
+use gerbers::{Gerber, Command};
+
+#[test]
+fn test_json_round_trip() {
+    let test_file = Path::new("tests/two_square_boxes.gbr");
+    let gerber = Gerber::new(test_file).expect("Failed to parse Gerber file");
+
+    let json = serde_json::to_string(&gerber.commands).expect("Failed to serialize commands");
+    let commands: Vec<Command> = serde_json::from_str(&json).expect("Failed to deserialize commands");
+
+    assert_eq!(commands, gerber.commands, "Command list is not the same after a round trip.");
+}
@@ -27,6 +27,8 @@ fn test_parse_two_square_boxes() {
             x_decimal_digits: 6,
             y_integer_digits: 2,
             y_decimal_digits: 6,
+            zero_omission: command::ZeroOmission::Leading,
+            notation: command::Notation::Absolute,
         }),
 
         // File attribute (if your parser supports it)
@@ -160,6 +162,8 @@ fn test_non_overlapping_countour() {
             x_decimal_digits: 6,
             y_integer_digits: 2,
             y_decimal_digits: 6,
+            zero_omission: command::ZeroOmission::Leading,
+            notation: command::Notation::Absolute,
         }),
 
         // Define aperture D10 as a circle with diameter 0.010
@@ -279,7 +283,9 @@ fn test_polarities_and_apertures() {
              x_integer_digits: 3,
              x_decimal_digits: 6,
              y_integer_digits: 3,
-             y_decimal_digits: 6
+             y_decimal_digits: 6,
+             zero_omission: command::ZeroOmission::Leading,
+             notation: command::Notation::Absolute,
          }),
          Command::TF(".FileFunction".to_string(),
                     vec!["Other".to_string(),
@@ -0,0 +1,44 @@
+use gerbers::{Gerber, Command};
+use gerbers::command::{Operation, Polarity};
+
+#[test]
+fn test_operations_with_state() {
+    let gerber = "%MOMM*%\n%FSLAX26Y26*%\n%ADD10C,0.1*%\nD10*\nX0Y0D03*\n%LPC*%\n%LR45*%\nX1000000Y0D03*\nM02*\n".parse::<Gerber>()
+        .expect("Failed to parse Gerber data");
+
+    let operations = gerber.operations_with_state();
+
+    assert_eq!(operations.len(), 2);
+    assert!(matches!(operations[1].0, Operation::D03(_)));
+    assert_eq!(operations[0].1.rotation, 0.0);
+    assert_eq!(operations[1].1.rotation, 45.0);
+    assert_eq!(operations[1].1.polarity, Polarity::Clear);
+    assert_eq!(operations[1].1.aperture, Some(10));
+}
+
+#[test]
+fn test_attribute_state_at() {
+    let gerber = "%MOMM*%\n%FSLAX26Y26*%\n%TF.FileFunction,Copper,L1,Top*%\n%ADD10C,0.1*%\nD10*\n%TO.N,GND*%\nX0Y0D03*\n%TD*%\nX1000000Y0D03*\nM02*\n".parse::<Gerber>()
+        .expect("Failed to parse Gerber data");
+
+    let attributes = gerber.attribute_state_at(6);
+
+    assert_eq!(gerber.commands[5], Command::TO(".N".to_string(), vec!["GND".to_string()]));
+    assert_eq!(attributes.net(), Some("GND"));
+    assert_eq!(gerber.attribute_state_at(8).net(), None, "TD should delete the net");
+    assert!(gerber.attribute_state_at(8).file.contains_key(".FileFunction"));
+}
+
+#[test]
+fn test_nets() {
+    let gerber = "%MOMM*%\n%FSLAX26Y26*%\n%ADD10C,0.1*%\nD10*\n%TO.N,GND*%\nX0Y0D03*\n%TO.N,VCC*%\nX1000000Y0D03*\n%TO.N,N/C*%\nX2000000Y0D03*\n%TD*%\nX3000000Y0D03*\nM02*\n".parse::<Gerber>()
+        .expect("Failed to parse Gerber data");
+
+    let nets: Vec<String> = gerber.nets().into_iter().collect();
+    assert_eq!(nets, vec!["GND".to_string(), "VCC".to_string()]);
+
+    let objects = gerber.net_objects();
+    assert_eq!(objects["GND"], vec![5]);
+    assert_eq!(objects["VCC"], vec![7]);
+    assert_eq!(objects.len(), 2);
+}
@@ -0,0 +1,182 @@
+use std::path::Path;
+use gerbers::Gerber;
+use gerbers::Command;
+use gerbers::command::{ApertureDefinition, ApertureTemplate, D01Operation, D02Operation, D03Operation, FormatSpecification, Notation, Polarity, Unit, ZeroOmission};
+use gerbers::error::GerberWarning;
+
+#[test]
+fn test_to_units() {
+    let mut gerber = "%MOIN*%\n%FSLAX25Y25*%\n%ADD10C,0.1*%\nD10*\nX100000Y50000D03*\nM02*\n".parse::<Gerber>()
+        .expect("Failed to parse Gerber data");
+
+    gerber.to_units(Unit::Millimeters);
+
+    assert_eq!(gerber.commands[0], Command::MO(Unit::Millimeters));
+    assert_eq!(gerber.aperture(10), Some(&ApertureTemplate::Circle(2.54, None)));
+
+    // The pad at (1in, 0.5in)
+    let points = gerber.resolved_coordinates().expect("Failed to resolve coordinates");
+    assert!((points[0].0 - 25.4).abs() < 1e-3 && (points[0].1 - 12.7).abs() < 1e-3, "Wrong pad position: {:?}", points[0]);
+
+    // Converting back gives the original geometry within a micron
+    gerber.to_units(Unit::Inches);
+    let points = gerber.resolved_coordinates().expect("Failed to resolve coordinates");
+    assert!((points[0].0 - 1.0).abs() * 25.4 < 1e-3 && (points[0].1 - 0.5).abs() * 25.4 < 1e-3);
+}
+
+#[test]
+fn test_to_units_macro_apertures() {
+    let mut gerber = "%MOMM*%\n%FSLAX34Y34*%\n%AMDOT*1,1,0.5,0,0*%\n%ADD10DOT*%\n%ADD11C,0.254*%\nD10*\nX10000Y0D03*\nM02*\n".parse::<Gerber>()
+        .expect("Failed to parse Gerber data");
+
+    let warnings = gerber.to_units(Unit::Inches);
+
+    assert_eq!(warnings, vec![GerberWarning::UnconvertedMacro { index: 3, aperture: 10 }]);
+    assert_eq!(gerber.aperture(11), Some(&ApertureTemplate::Circle(0.01, None)));
+
+    // Going to inches needs two more decimal digits to keep the precision
+    match &gerber.commands[1] {
+        Command::FS(spec) => assert_eq!((spec.x_integer_digits, spec.x_decimal_digits, spec.y_decimal_digits), (2, 6, 6)),
+        command => panic!("Expected the format specification, got {:?}", command),
+    }
+}
+
+#[test]
+fn test_explicit_operations() {
+    let gerber = "%MOMM*%\n%FSLAX26Y26*%\n%ADD10C,0.1*%\nD10*\nX1000000Y2000000D02*\nG01*\nX3000000D01*\nD03*\nM02*\n".parse::<Gerber>()
+        .expect("Failed to parse Gerber data");
+
+    let commands = gerber.explicit_operations();
+
+    assert_eq!(commands[6], Command::D01(D01Operation { x: Some(3000000), y: Some(2000000), i: None, j: None }), "The omitted Y should be the previous one");
+    assert_eq!(commands[7], Command::D03(D03Operation { x: Some(3000000), y: Some(2000000) }));
+}
+
+#[test]
+fn test_translate() {
+    let mut gerber = Gerber::new(Path::new("tests/two_square_boxes.gbr"))
+        .expect("Failed to parse Gerber file");
+
+    gerber.translate(10.0, -5.0);
+
+    // The corner of the second box, drawn to (11mm, 5mm)
+    let points = gerber.resolved_coordinates().expect("Failed to resolve coordinates");
+    assert_eq!(points[0], (10.0, -5.0));
+    assert_eq!(points[7], (21.0, 0.0));
+
+    // Arc offsets are relative and stay as they are
+    let mut gerber = "%MOMM*%\n%FSLAX26Y26*%\n%ADD10C,0.1*%\nD10*\nX1000000Y0D02*\nG75*\nG03X0Y1000000I-1000000J0D01*\nM02*\n".parse::<Gerber>()
+        .expect("Failed to parse Gerber data");
+
+    gerber.translate(10.0, 0.0);
+
+    assert_eq!(gerber.commands[7], Command::D01(D01Operation { x: Some(10000000), y: Some(1000000), i: Some(-1000000), j: Some(0) }));
+}
+
+#[test]
+fn test_rotate() {
+    let mut gerber = "%MOMM*%\n%FSLAX26Y26*%\n%ADD10C,0.1*%\n%ADD11R,1X2*%\nD10*\nX1000000Y0D02*\nG01*\nX3000000D01*\nD11*\nX0Y0D03*\nM02*\n".parse::<Gerber>()
+        .expect("Failed to parse Gerber data");
+
+    let warnings = gerber.rotate(90.0, (0.0, 0.0));
+
+    assert_eq!(gerber.commands[5], Command::D02(D02Operation { x: Some(0), y: Some(1000000) }));
+    assert_eq!(gerber.commands[7], Command::D01(D01Operation { x: Some(0), y: Some(3000000), i: None, j: None }));
+    assert_eq!(gerber.aperture(11), Some(&ApertureTemplate::Rectangle(2.0, 1.0, None)), "A quarter turn should swap the sides");
+    assert!(warnings.is_empty());
+
+    let warnings = gerber.rotate(45.0, (0.0, 0.0));
+    assert_eq!(warnings, vec![GerberWarning::UnrotatedFlash { index: 9, aperture: 11 }]);
+}
+
+#[test]
+fn test_merge() {
+    let mut gerber = "%MOMM*%\n%FSLAX26Y26*%\n%ADD10C,0.1*%\nD10*\n%LPC*%\nX0Y0D03*\nM02*\n".parse::<Gerber>()
+        .expect("Failed to parse Gerber data");
+    let other = "%MOMM*%\n%FSLAX26Y26*%\n%ADD10R,1X2*%\nD10*\nX1000000Y0D03*\nM02*\n".parse::<Gerber>()
+        .expect("Failed to parse Gerber data");
+
+    gerber.merge(&other).expect("Failed to merge");
+
+    assert_eq!(gerber.aperture(10), Some(&ApertureTemplate::Circle(0.1, None)));
+    assert_eq!(gerber.aperture(11), Some(&ApertureTemplate::Rectangle(1.0, 2.0, None)));
+    assert_eq!(&gerber.commands[6..], &[
+        Command::LP(Polarity::Dark),
+        Command::AD(ApertureDefinition { code: 11, template: ApertureTemplate::Rectangle(1.0, 2.0, None) }),
+        Command::Dnn(11),
+        Command::D03(D03Operation { x: Some(1000000), y: Some(0) }),
+        Command::M02,
+    ]);
+
+    let inches = "%MOIN*%\n%FSLAX26Y26*%\nM02*\n".parse::<Gerber>().expect("Failed to parse Gerber data");
+    assert!(gerber.merge(&inches).is_err(), "Files in different units should not merge");
+}
+
+#[test]
+fn test_merge_resets_modes() {
+    let mut gerber = "%MOMM*%\n%FSLAX26Y26*%\n%AMDOT*1,1,0.5,0,0*%\n%ADD10DOT*%\nD10*\nG74*\nG02*\nX1000000Y0D03*\nG91*\nM02*\n".parse::<Gerber>()
+        .expect("Failed to parse Gerber data");
+    let other = "%MOMM*%\n%FSLAX26Y26*%\n%AMDOT*1,1,2,0,0*%\n%ADD10DOT*%\nD10*\nX1000000Y0D03*\nM02*\n".parse::<Gerber>()
+        .expect("Failed to parse Gerber data");
+
+    gerber.merge(&other).expect("Failed to merge");
+
+    // The flash of the absolute file is not offset by the current point
+    let points = gerber.resolved_coordinates().expect("Failed to resolve coordinates");
+    assert_eq!(points.last(), Some(&(1.0, 0.0)));
+    assert_eq!(&gerber.commands[9..12], &[Command::G90, Command::G75, Command::G01]);
+
+    // The macro of the other file keeps its own shape
+    assert_eq!(gerber.aperture(10), Some(&ApertureTemplate::Macro("DOT".to_string(), vec![])));
+    assert_eq!(gerber.aperture(11), Some(&ApertureTemplate::Macro("DOT_1".to_string(), vec![])));
+    assert!(gerber.commands.iter().any(|command| matches!(command, Command::AM(name, _) if name == "DOT_1")));
+}
+
+#[test]
+fn test_expand_step_and_repeat() {
+    let gerber = "%MOMM*%\n%FSLAX26Y26*%\n%ADD10C,0.5*%\nD10*\n%SRX2Y2I5.0J3.0*%\nX1000000Y1000000D03*\n%SR*%\nM02*\n".parse::<Gerber>()
+        .expect("Failed to parse Gerber data");
+
+    let expanded = gerber.expand_step_and_repeat();
+
+    assert!(!expanded.commands.iter().any(|command| matches!(command, Command::SR(_))), "No SR command should be left");
+    let flashes: Vec<&D03Operation> = expanded.flashes().collect();
+    assert_eq!(flashes, vec![
+        &D03Operation { x: Some(1000000), y: Some(1000000) },
+        &D03Operation { x: Some(6000000), y: Some(1000000) },
+        &D03Operation { x: Some(1000000), y: Some(4000000) },
+        &D03Operation { x: Some(6000000), y: Some(4000000) },
+    ]);
+}
+
+#[test]
+fn test_reformat() {
+    let mut gerber = "%MOMM*%\n%FSLAX26Y26*%\n%ADD10C,0.1*%\nD10*\nX12345678Y-1500000D03*\nM02*\n".parse::<Gerber>()
+        .expect("Failed to parse Gerber data");
+    let original = gerber.resolved_coordinates().expect("Failed to resolve coordinates");
+
+    let format = FormatSpecification {
+        x_integer_digits: 4,
+        x_decimal_digits: 6,
+        y_integer_digits: 4,
+        y_decimal_digits: 6,
+        zero_omission: ZeroOmission::Leading,
+        notation: Notation::Absolute,
+    };
+    let warnings = gerber.reformat(format.clone());
+
+    assert!(warnings.is_empty(), "No decimals should be lost: {:?}", warnings);
+    assert_eq!(gerber.format().expect("The format should be consistent"), Some(&format));
+
+    // The encoded coordinate decodes to the same position under the new format
+    let Command::D03(operation) = &gerber.commands[4] else {
+        panic!("Expected a flash, got {:?}", gerber.commands[4]);
+    };
+    let x = format.decode_x(&format.encode_x(operation.x.unwrap())).expect("Failed to decode X");
+    assert!((format.resolve_x(x) - 12.345678).abs() < 1e-9, "Wrong X: {}", format.resolve_x(x));
+    assert_eq!(gerber.resolved_coordinates().expect("Failed to resolve coordinates"), original);
+
+    // Dropping decimals rounds the coordinates
+    let warnings = gerber.reformat(FormatSpecification { x_decimal_digits: 4, y_decimal_digits: 4, ..format });
+    assert!(matches!(warnings.as_slice(), [GerberWarning::RoundedCoordinates { index: 4 }]), "Got {:?}", warnings);
+}
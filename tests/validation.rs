@@ -0,0 +1,82 @@
+use std::path::Path;
+use gerbers::Gerber;
+use gerbers::error::{GerberError, GerberWarning};
+
+#[test]
+fn test_validate_clean_file() {
+    let gerber = Gerber::new(Path::new("tests/two_square_boxes.gbr")).expect("Failed to parse Gerber file");
+
+    assert!(gerber.validate().is_empty(), "A clean file should have no validation errors");
+}
+
+#[test]
+fn test_validate_undefined_aperture() {
+    let gerber = "%MOMM*%\n%FSLAX26Y26*%\n%ADD10C,0.1*%\nD99*\nX0Y0D03*\nM02*\n".parse::<Gerber>()
+        .expect("Failed to parse Gerber data");
+
+    let errors = gerber.validate();
+
+    assert_eq!(errors.len(), 1);
+    assert!(matches!(&errors[0], GerberError::SemanticError(message) if message.contains("D99")));
+}
+
+#[test]
+fn test_validate_operation_before_format() {
+    let gerber = "%MOMM*%\n%ADD10C,0.1*%\nD10*\nX0Y0D03*\n%FSLAX26Y26*%\nX1000000Y0D03*\nM02*\n".parse::<Gerber>()
+        .expect("Failed to parse Gerber data");
+
+    let errors = gerber.validate();
+
+    assert_eq!(errors.len(), 1);
+    assert!(matches!(&errors[0], GerberError::SemanticError(message) if message.starts_with("Command 3:") && message.contains("FS")));
+}
+
+#[test]
+fn test_validate_unclosed_region() {
+    let gerber = "%MOMM*%\n%FSLAX26Y26*%\nG01*\nG36*\nX0Y0D02*\nX1000000Y0D01*\nY1000000D01*\nX0D01*\nX2000000Y0D02*\nX3000000D01*\nY1000000D01*\nX2000000Y0D01*\nG37*\nM02*\n".parse::<Gerber>()
+        .expect("Failed to parse Gerber data");
+
+    let errors = gerber.validate();
+
+    // Only the first contour is left open
+    assert_eq!(errors.len(), 1);
+    assert!(matches!(&errors[0], GerberError::SemanticError(message) if message.contains("(0, 0)") && message.contains("(0, 1)")));
+}
+
+#[test]
+fn test_validate_duplicate_aperture() {
+    let gerber = "%MOMM*%\n%FSLAX26Y26*%\n%ADD10C,0.1*%\n%ADD10R,1X1*%\nD10*\nX0Y0D03*\nM02*\n".parse::<Gerber>()
+        .expect("Failed to parse Gerber data");
+
+    let errors = gerber.validate();
+
+    assert_eq!(errors.len(), 1);
+    assert!(matches!(&errors[0], GerberError::SemanticError(message) if message.contains("D10") && message.contains("already defined")));
+}
+
+#[test]
+fn test_overlapping_regions() {
+    let square = |x: i32| format!(
+        "G36*\nX{0}Y0D02*\nG01*\nX{1}Y0D01*\nX{1}Y10000000D01*\nX{0}Y10000000D01*\nX{0}Y0D01*\nG37*\n",
+        x, x + 10000000,
+    );
+    let content = format!("%MOMM*%\n%FSLAX26Y26*%\n{}{}{}M02*\n", square(0), square(5000000), square(30000000));
+    let gerber = content.parse::<Gerber>().expect("Failed to parse Gerber data");
+
+    let warnings = gerber.warnings();
+
+    assert_eq!(warnings, vec![GerberWarning::OverlappingRegions { first: 2, second: 10 }]);
+    assert!(gerber.validate().is_empty(), "Overlaps are warnings, not errors");
+}
+
+#[test]
+fn test_validate_integer_digit_overflow() {
+    // 123.4 mm needs 3 integer digits
+    let gerber = "%MOMM*%\n%FSLAX26Y26*%\n%ADD10C,0.1*%\nD10*\nX1000000Y123400000D03*\nM02*\n".parse::<Gerber>()
+        .expect("Failed to parse Gerber data");
+
+    let errors = gerber.validate();
+
+    assert_eq!(errors.len(), 1);
+    assert!(matches!(&errors[0], GerberError::SemanticError(message) if message.starts_with("Command 4: Y coordinate")), "Got {:?}", errors);
+}
@@ -0,0 +1,308 @@
+#![cfg(feature = "visualizer")]
+
+use std::path::Path;
+use gerbers::{Gerber, Command};
+use gerbers::command::{AMPrimitive, ApertureDefinition, ApertureTemplate, D01Operation, D02Operation};
+use gerbers::visualizer::{composite, DrawOperation, GerberVisualizer};
+use raylib::prelude::{Color, Image, Vector2};
+
+#[test]
+fn test_try_process_commands_collects_errors() {
+    let commands = vec![
+        Command::AD(ApertureDefinition {
+            code: 10,
+            template: ApertureTemplate::Macro("MISSING".to_string(), vec![]),
+        }),
+        Command::Dnn(99),
+        Command::D02(D02Operation { x: Some(0), y: Some(0) }),
+        Command::G02,
+        Command::D01(D01Operation { x: Some(1000), y: Some(1000), i: None, j: None }),
+        Command::M02,
+    ];
+
+    let mut visualizer = GerberVisualizer::new(800, 600);
+    let errors = visualizer.try_process_commands(&commands).expect_err("Expected errors to be reported");
+
+    assert_eq!(errors.len(), 3, "Every problem should be reported");
+}
+
+#[test]
+fn test_block_interpolation_mode() {
+    // A linear draw in a block, while the mode outside it is circular
+    let linear_in_block = "%MOMM*%\n%FSLAX26Y26*%\n%ADD10C,0.1*%\nG02*\n%ABD11*%\nD10*\nG01*\nX1000000Y0D01*\n%AB*%\nM02*\n".parse::<Gerber>()
+        .expect("Failed to parse Gerber data");
+
+    let mut visualizer = GerberVisualizer::new(800, 600);
+    assert!(visualizer.try_process_commands(&linear_in_block.commands).is_ok(), "G01 in the block should make the draw linear");
+
+    // An arc missing its offsets in a block, followed by a linear draw outside it
+    let arc_in_block = "%MOMM*%\n%FSLAX26Y26*%\n%ADD10C,0.1*%\nG01*\n%ABD11*%\nD10*\nG02*\nX1000000Y0D01*\n%AB*%\nD10*\nX0Y1000000D01*\nM02*\n".parse::<Gerber>()
+        .expect("Failed to parse Gerber data");
+
+    let mut visualizer = GerberVisualizer::new(800, 600);
+    let errors = visualizer.try_process_commands(&arc_in_block.commands).expect_err("The arc in the block should be reported");
+    assert_eq!(errors.len(), 1, "Only the arc in the block is missing its offsets: {:?}", errors);
+}
+
+#[test]
+fn test_draw_operations() {
+    let gerber = Gerber::new(Path::new("tests/two_square_boxes.gbr")).expect("Failed to parse Gerber file");
+
+    let mut visualizer = GerberVisualizer::new(800, 600);
+    visualizer.process_commands(&gerber.commands);
+    let operations = visualizer.draw_operations(&gerber.commands);
+
+    assert_eq!(operations.len(), 8, "Both boxes should be drawn");
+    assert!(operations.iter().all(|operation| matches!(operation, DrawOperation::Line { .. })));
+}
+
+#[test]
+fn test_polygon_vertices() {
+    let visualizer = GerberVisualizer::new(800, 600);
+
+    let triangle = visualizer.polygon_vertices(0.0, 0.0, 100.0, 3, 0.0);
+    assert_eq!(triangle.len(), 3);
+    assert!((triangle[0].x - 450.0).abs() < 1e-3 && (triangle[0].y - 300.0).abs() < 1e-3, "First vertex should lie on the X axis");
+
+    let rotated = visualizer.polygon_vertices(0.0, 0.0, 100.0, 3, 90.0);
+    assert!((rotated[0].x - 400.0).abs() < 1e-3 && (rotated[0].y - 350.0).abs() < 1e-3, "Rotation should move the first vertex");
+}
+
+#[test]
+fn test_macro_primitives() {
+    let gerber = Gerber::new(Path::new("tests/polarities_and_apertures.gbr")).expect("Failed to parse Gerber file");
+
+    let mut visualizer = GerberVisualizer::new(800, 600);
+    visualizer.process_commands(&gerber.commands);
+
+    assert_eq!(visualizer.macro_primitives(19), vec![AMPrimitive::Thermal(0.0, 0.0, 0.8, 0.55, 0.125, 45.0)]);
+    assert!(visualizer.macro_primitives(10).is_empty(), "Standard apertures have no macro primitives");
+}
+
+#[test]
+fn test_region_contours() {
+    let gerber = Gerber::new(Path::new("tests/non-overlapping_contour.gbr")).expect("Failed to parse Gerber file");
+
+    let mut visualizer = GerberVisualizer::new(800, 600);
+    visualizer.process_commands(&gerber.commands);
+    let operations = visualizer.draw_operations(&gerber.commands);
+
+    assert_eq!(operations.len(), 1, "The region should be the only thing drawn");
+    let DrawOperation::Region { contours, .. } = &operations[0] else {
+        panic!("Expected a region, got {:?}", operations[0]);
+    };
+
+    // A D02 inside the region starts a new contour
+    assert_eq!(contours.len(), 2);
+    assert_eq!(contours[0].len(), 6);
+    assert_eq!(contours[1].len(), 5);
+    assert_eq!(contours[0].first(), contours[0].last(), "Contours should be closed");
+}
+
+#[test]
+fn test_render_to_png() {
+    let gerber = Gerber::new(Path::new("tests/two_square_boxes.gbr")).expect("Failed to parse Gerber file");
+    let output = std::env::temp_dir().join("gerbers_two_square_boxes.png");
+
+    let mut visualizer = GerberVisualizer::new(800, 600);
+    visualizer.render_to_png(&gerber.commands, &output).expect("Failed to render PNG");
+
+    let metadata = std::fs::metadata(&output).expect("PNG should have been written");
+    assert!(metadata.len() > 0, "PNG should not be empty");
+}
+
+#[test]
+fn test_aperture_hole() {
+    let gerber = "%MOMM*%\n%FSLAX26Y26*%\n%ADD10C,1.0X0.4*%\n%ADD11R,1.0X1.0*%\nD10*\nX0Y0D03*\nD11*\nX2000000Y0D03*\nM02*\n".parse::<Gerber>()
+        .expect("Failed to parse Gerber data");
+
+    let mut visualizer = GerberVisualizer::new(800, 600);
+    visualizer.process_commands(&gerber.commands);
+
+    assert_eq!(visualizer.aperture_hole(10), Some(0.4), "The drilled pad should have a cutout");
+    assert_eq!(visualizer.aperture_hole(11), None);
+}
+
+#[test]
+fn test_render_clear_polarity() {
+    let gerber = "%MOMM*%\n%FSLAX26Y26*%\n%ADD10R,10X10*%\n%ADD11R,2X2*%\nD10*\nX0Y0D03*\n%LPC*%\nD11*\nX0Y0D03*\nM02*\n".parse::<Gerber>()
+        .expect("Failed to parse Gerber data");
+    let output = std::env::temp_dir().join("gerbers_clear_polarity.png");
+
+    let mut visualizer = GerberVisualizer::new(800, 600);
+    visualizer.render_to_png(&gerber.commands, &output).expect("Failed to render PNG");
+
+    let image = Image::load_image(output.to_str().unwrap()).expect("Failed to load PNG");
+    let pixels = image.get_image_data();
+    let pixel = |x: i32, y: i32| pixels[(y * image.width() + x) as usize];
+
+    assert_eq!(pixel(400, 300), Color::BLACK, "The clear square should cut through the dark one");
+    assert!(pixels.contains(&Color::GREEN), "The dark square should still be drawn around the cutout");
+}
+
+#[test]
+fn test_zoom_at_cursor() {
+    let gerber = Gerber::new(Path::new("tests/two_square_boxes.gbr")).expect("Failed to parse Gerber file");
+
+    let mut visualizer = GerberVisualizer::new(800, 600);
+    visualizer.process_commands(&gerber.commands);
+
+    let (cursor_x, cursor_y) = visualizer.screen_position(5.0, 5.0);
+    let (other_x, other_y) = visualizer.screen_position(0.0, 0.0);
+    visualizer.zoom_at(2.0, cursor_x, cursor_y);
+
+    let (x, y) = visualizer.screen_position(5.0, 5.0);
+    assert!((x - cursor_x).abs() < 1e-3 && (y - cursor_y).abs() < 1e-3, "The point under the cursor should not move");
+    let (x, y) = visualizer.screen_position(0.0, 0.0);
+    assert!((x - (2.0 * other_x - cursor_x)).abs() < 1e-3 && (y - (2.0 * other_y - cursor_y)).abs() < 1e-3);
+
+    visualizer.pan(10.0, -5.0);
+    let (panned_x, panned_y) = visualizer.screen_position(0.0, 0.0);
+    assert!((panned_x - x - 10.0).abs() < 1e-3 && (panned_y - y + 5.0).abs() < 1e-3);
+}
+
+#[test]
+fn test_transform_point_rotation() {
+    let gerber = "%MOMM*%\n%FSLAX26Y26*%\n%LR90*%\nM02*\n".parse::<Gerber>()
+        .expect("Failed to parse Gerber data");
+
+    let mut visualizer = GerberVisualizer::new(800, 600);
+    visualizer.process_commands(&gerber.commands);
+
+    let (x, y) = visualizer.transform_point(1.0, 0.0);
+    assert!(x.abs() < 1e-9 && (y - 1.0).abs() < 1e-9, "Expected (0, 1), got ({}, {})", x, y);
+}
+
+#[test]
+fn test_rotated_rectangle_corners() {
+    let gerber = "%MOMM*%\n%FSLAX26Y26*%\n%ADD10R,2X2*%\n%LR45*%\nD10*\nX0Y0D03*\nM02*\n".parse::<Gerber>()
+        .expect("Failed to parse Gerber data");
+
+    let mut visualizer = GerberVisualizer::new(800, 600);
+    visualizer.process_commands(&gerber.commands);
+
+    let corners = visualizer.rectangle_corners(0.0, 0.0, 2.0, 2.0);
+    let center_x = corners.iter().map(|corner| corner.x).sum::<f32>() / 4.0;
+    let center_y = corners.iter().map(|corner| corner.y).sum::<f32>() / 4.0;
+    let radius = (corners[0].x - center_x).hypot(corners[0].y - center_y);
+
+    // A square turned by 45 degrees is a diamond, its corners lie on the axes
+    for corner in corners {
+        let (dx, dy) = (corner.x - center_x, corner.y - center_y);
+        assert!(dx.abs() < 1e-3 || dy.abs() < 1e-3, "Corner ({}, {}) is not on an axis", dx, dy);
+        assert!((dx.hypot(dy) - radius).abs() < 1e-3);
+    }
+}
+
+#[test]
+fn test_mirrored_rectangle_corner_order() {
+    // Signed area of the triangle through three corners, its sign gives the winding
+    let winding = |a: Vector2, b: Vector2, c: Vector2| (b.x - a.x) * (c.y - a.y) - (b.y - a.y) * (c.x - a.x);
+
+    let mut windings = Vec::new();
+    for mirroring in ["%LMN*%", "%LMX*%"] {
+        let content = format!("%MOMM*%\n%FSLAX26Y26*%\n%ADD10R,4X2*%\n{}\n%LR30*%\nD10*\nX0Y0D03*\nM02*\n", mirroring);
+        let gerber = content.parse::<Gerber>().expect("Failed to parse Gerber data");
+
+        let mut visualizer = GerberVisualizer::new(800, 600);
+        visualizer.process_commands(&gerber.commands);
+
+        // The corners go around the rectangle, so the two triangles it is drawn with cover it
+        let corners = visualizer.rectangle_corners(0.0, 0.0, 4.0, 2.0);
+        let first = winding(corners[0], corners[1], corners[2]);
+        let second = winding(corners[0], corners[2], corners[3]);
+        assert!(first * second > 0.0, "Triangles wind in opposite directions: {:?}", corners);
+
+        let long_side = (corners[1].x - corners[0].x).hypot(corners[1].y - corners[0].y);
+        let short_side = (corners[2].x - corners[1].x).hypot(corners[2].y - corners[1].y);
+        assert!((long_side - 2.0 * short_side).abs() < 1e-2, "Sides are out of order: {:?}", corners);
+
+        windings.push(first);
+    }
+
+    // Mirroring reverses the winding, which the triangles are drawn in
+    assert!(windings[0] * windings[1] < 0.0, "Mirroring should reverse the corner order: {:?}", windings);
+}
+
+#[test]
+fn test_obround_caps() {
+    let gerber = "%MOMM*%\n%FSLAX26Y26*%\n%ADD10O,0.4X1.0*%\nD10*\nX0Y0D03*\nM02*\n".parse::<Gerber>()
+        .expect("Failed to parse Gerber data");
+
+    let mut visualizer = GerberVisualizer::new(800, 600);
+    visualizer.process_commands(&gerber.commands);
+
+    let (_, ends, radius) = visualizer.obround_parts(0.0, 0.0, 0.4, 1.0);
+
+    assert!((radius - 0.2).abs() < 1e-6, "The caps should be half the shorter side, got {}", radius);
+    assert_eq!(ends[0].x, ends[1].x, "The caps should be on the ends of the longer side");
+    assert!(((ends[0].y - ends[1].y).abs() - 0.6).abs() < 1e-4);
+}
+
+#[test]
+fn test_block_aperture_flash() {
+    let gerber = Gerber::new(Path::new("tests/aperture_block.gbr")).expect("Failed to parse Gerber file");
+
+    let mut visualizer = GerberVisualizer::new(800, 600);
+    visualizer.try_process_commands(&gerber.commands).expect("The block aperture should be defined");
+    let operations = visualizer.draw_operations(&gerber.commands);
+
+    // The block is only drawn where it is flashed, not where it is defined
+    let flashes: Vec<(u32, f32, f32)> = operations.iter()
+        .map(|operation| match operation {
+            DrawOperation::Flash { aperture, x, y, .. } => (*aperture, *x, *y),
+            _ => panic!("Expected only flashes, got {:?}", operation),
+        })
+        .collect();
+    assert_eq!(flashes, vec![(10, 5.0, 5.0), (10, 6.0, 5.0)]);
+}
+
+#[test]
+fn test_set_colors() {
+    let mut visualizer = GerberVisualizer::with_colors(800, 600, Color::BLUE, Color::GOLD);
+    assert_eq!(visualizer.colors(), (Color::BLUE, Color::GOLD));
+
+    visualizer.set_colors(Color::WHITE, Color::RED);
+    assert_eq!(visualizer.colors(), (Color::WHITE, Color::RED));
+
+    // The space key swaps with the alternate colors, and back
+    visualizer.set_alternate_colors(Color::BLACK, Color::GREEN);
+    visualizer.toggle_colors();
+    assert_eq!(visualizer.colors(), (Color::BLACK, Color::GREEN));
+    visualizer.toggle_colors();
+    assert_eq!(visualizer.colors(), (Color::WHITE, Color::RED));
+}
+
+#[test]
+fn test_composite_layers() {
+    let copper = Color { r: 255, g: 0, b: 0, a: 128 };
+    let mask = Color { r: 0, g: 0, b: 255, a: 128 };
+    // The copper covers the first two pixels and the mask the last two
+    let layers = vec![
+        (vec![true, true, false], copper),
+        (vec![false, true, true], mask),
+    ];
+
+    let pixels = composite(Color::BLACK, &layers);
+
+    assert_eq!(pixels.len(), 3);
+    assert_ne!(pixels[1], pixels[0], "The overlap should differ from the copper alone");
+    assert_ne!(pixels[1], pixels[2], "The overlap should differ from the mask alone");
+    assert!(pixels[1].r > 0 && pixels[1].b > 0, "Both layers should show in the overlap");
+}
+
+#[test]
+fn test_fluent_config() {
+    let mut visualizer = GerberVisualizer::new(800, 600);
+    visualizer.background(Color::WHITE)
+        .drawing(Color::BLUE)
+        .scale(2.0)
+        .rotation(90.0)
+        .mirror(true, false);
+
+    assert_eq!(visualizer.colors(), (Color::WHITE, Color::BLUE));
+
+    // (1, 0) is mirrored to (-1, 0), turned to (0, -1) and scaled to (0, -2)
+    let (x, y) = visualizer.transform_point(1.0, 0.0);
+    assert!(x.abs() < 1e-9 && (y + 2.0).abs() < 1e-9, "Expected (0, -2), got ({}, {})", x, y);
+}
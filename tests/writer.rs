@@ -0,0 +1,38 @@
+use std::path::Path;
+use gerbers::{Gerber, Command};
+
+#[test]
+fn test_round_trip() {
+    let test_file = Path::new("tests/two_square_boxes.gbr");
+    let gerber = Gerber::new(test_file).expect("Failed to parse Gerber file");
+
+    let text = gerber.to_gerber_string();
+    assert!(text.contains("%FSLAX26Y26*%"));
+    assert!(text.contains("X5000000Y0D01*"));
+
+    let reparsed: Gerber = text.parse().expect("Failed to parse written Gerber");
+    assert_eq!(reparsed.commands, gerber.commands, "Written text should parse back into the same commands");
+}
+
+#[test]
+fn test_to_excellon() {
+    let gerber = "%MOMM*%\n%FSLAX26Y26*%\n%ADD10C,1.0X0.4*%\n%ADD11R,1.0X1.0X0.6*%\n%ADD12C,0.5*%\nD10*\nX1000000Y2000000D03*\nD11*\nX3000000Y2000000D03*\nD12*\nX5000000Y2000000D03*\nM02*\n".parse::<Gerber>()
+        .expect("Failed to parse Gerber data");
+
+    let drill = gerber.to_excellon();
+
+    assert!(drill.starts_with("M48\nMETRIC\n"));
+    assert_eq!(drill.lines().filter(|line| line.starts_with('T') && line.contains('C')).count(), 2, "One tool per hole diameter");
+    assert!(drill.contains("T01C0.400\n"));
+    assert!(drill.contains("T02\nX3.000Y2.000\n"));
+    assert!(!drill.contains("X5.000"), "Apertures without a hole are not drilled");
+}
+
+#[test]
+fn test_comment_round_trip() {
+    let content = "G04   Spaced   out: #$&()[]{}<>|\\~\t!*\n%MOMM*%\n%FSLAX26Y26*%\nM02*\n";
+    let gerber = content.parse::<Gerber>().expect("Failed to parse Gerber data");
+
+    assert_eq!(gerber.commands[0], Command::G04("  Spaced   out: #$&()[]{}<>|\\~\t!".to_string()));
+    assert!(gerber.to_gerber_string().starts_with("G04   Spaced   out: #$&()[]{}<>|\\~\t!*"), "The comment should be written back unchanged");
+}